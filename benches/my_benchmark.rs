@@ -49,6 +49,29 @@ fn criterion_benchmark_weight(_c : &mut Criterion) {
             ban.genmove()
         })
     });
+    c.bench_function("legal_moves_star", |b| {
+        b.iter(|| {
+            black_box(&ban).legal_moves()
+        })
+    });
+    c.bench_function("moves_star", |b| {
+        b.iter(|| {
+            black_box(&ban).moves().fold(0u64, |acc, mv| acc + mv as u64)
+        })
+    });
+    c.bench_function("flips_star", |b| {
+        b.iter(|| {
+            black_box(&ban).flips(black_box(mylib::bitboard::cell(5, 3)))
+        })
+    });
+
+    let ban = BitBoard::from(
+        "aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w").unwrap();
+    c.bench_function("fixedstones", |b| {
+        b.iter(|| {
+            black_box(&ban).fixedstones()
+        })
+    });
 }
 
 #[cfg(target_arch="aarch64")]
@@ -101,6 +124,29 @@ fn criterion_benchmark_weight(_c : &mut Criterion) {
             ban.genmove()
         })
     });
+    c.bench_function("legal_moves_star", |b| {
+        b.iter(|| {
+            black_box(&ban).legal_moves()
+        })
+    });
+    c.bench_function("moves_star", |b| {
+        b.iter(|| {
+            black_box(&ban).moves().fold(0u64, |acc, mv| acc + mv as u64)
+        })
+    });
+    c.bench_function("flips_star", |b| {
+        b.iter(|| {
+            black_box(&ban).flips(black_box(mylib::bitboard::cell(5, 3)))
+        })
+    });
+
+    let ban = BitBoard::from(
+        "aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w").unwrap();
+    c.bench_function("fixedstones", |b| {
+        b.iter(|| {
+            black_box(&ban).fixedstones()
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark_weight);
@@ -0,0 +1,220 @@
+use super::*;
+
+/// loss of a player in a game.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Stat {
+    /// # of analyzed moves. passes are not counted.
+    pub moves : usize,
+    /// sum of differences between the best and the played moves in discs.
+    pub loss : f32,
+    pub mistakes : usize,
+}
+
+impl std::fmt::Display for Stat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let avg = if self.moves == 0 {0.0} else {self.loss / self.moves as f32};
+        write!(f, "{:.2} in {} moves ({avg:.2}/move), {} mistakes",
+            self.loss, self.moves, self.mistakes)
+    }
+}
+
+/// losses of black and white.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Summary {
+    pub black : Stat,
+    pub white : Stat,
+}
+
+impl Summary {
+    fn add(&mut self, teban : i8, loss : f32, mistake : bool) {
+        let st = if teban == bitboard::SENTE {&mut self.black} else {&mut self.white};
+        st.moves += 1;
+        st.loss += loss;
+        if mistake {st.mistakes += 1;}
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "black: {}", self.black)?;
+        writeln!(f, "white: {}", self.white)
+    }
+}
+
+/// searches every position in a game.
+pub struct Analyzer<'a> {
+    wei : &'a weight::Weight,
+    tt : transptable::TranspositionTable,
+    depth : u8,
+    exact : u32,
+    threshold : f32,
+}
+
+impl<'a> Analyzer<'a> {
+    /// # Arguments
+    /// - `wei` : eval table.
+    /// - `depth` : search depth.
+    /// - `exact` : positions w/ `exact` or less empty cells are solved.
+    /// - `threshold` : moves losing more discs than this are mistakes.
+    /// - `cachesz` : size of the transposition table.
+    pub fn new(wei : &'a weight::Weight, depth : u8, exact : u32, threshold : f32,
+            cachesz : usize) -> Analyzer<'a> {
+        Analyzer {
+            wei,
+            tt : transptable::TranspositionTable::with_capacity(cachesz),
+            depth : depth.max(1),
+            exact,
+            threshold,
+        }
+    }
+
+    /// # Returns
+    /// [(move, value from black's view)] for every legal move on `ban`.
+    /// all of them are searched in the same way to be compared.
+    fn scores(&mut self, ban : &bitboard::BitBoard) -> Vec<(u8, f32)> {
+        if ban.nblank() <= self.exact {
+            // 全部の手を読み切る
+            return bitboard::MoveIter::new(ban.legal_moves()).map(|mv| {
+                (mv, endgame::solve(&ban.r#move(mv).unwrap()) as f32)
+            }).collect();
+        }
+
+        nodebb::NodeBB::think_every_move(ban, self.depth, self.wei, &mut self.tt)
+            .into_iter().map(|(mv, val, _)| (mv, val)).collect()
+    }
+
+    /// annotate every move in `kifu` w/ the best move and values.
+    ///
+    /// # Returns
+    /// losses of both players or error message for an invalid RFEN.
+    pub fn run(&mut self, kifu : &mut kifu::Kifu) -> Result<Summary, String> {
+        let mut summary = Summary::default();
+        for te in kifu.list.iter_mut() {
+            let xy = te.xy();
+            if xy == bitboard::PASS {continue;}
+
+            let ban = bitboard::BitBoard::from(&te.rfen)?;
+            let scores = self.scores(&ban);
+            let fteban = te.teban as f32;
+            let (best, bestval) = *scores.iter()
+                .max_by(|a, b| (a.1 * fteban).partial_cmp(&(b.1 * fteban)).unwrap())
+                .ok_or_else(|| format!("no legal moves on {ban}."))?;
+            let playval = scores.iter().find(|(mv, _)| *mv == xy)
+                .ok_or_else(|| format!("illegal move {} on {ban}.", te.pos()))?.1;
+            // 同じ探索の値なので最善手より良くはならない
+            let loss = (bestval - playval) * fteban;
+            te.best = Some(best);
+            te.bestval = Some(bestval);
+            te.playval = Some(playval);
+            te.mistake = loss > self.threshold;
+            summary.add(te.teban, loss, te.mistake);
+        }
+        Ok(summary)
+    }
+}
+
+/// "dir/kifu1.txt" -> "dir/kifu1_analyzed.txt"
+fn outpath(path : &str) -> String {
+    let p = std::path::Path::new(path);
+    let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("kifu");
+    let name = match p.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{stem}_analyzed.{ext}"),
+        None => format!("{stem}_analyzed"),
+    };
+    p.with_file_name(name).to_str().unwrap().to_string()
+}
+
+/// analyze a kifu file and write the annotated one next to it.
+///
+/// # Arguments
+/// - `path` : kifu file.
+/// - `evaltable` : path of the eval table.
+/// - `depth`, `exact`, `threshold`, `cachesz` : see Analyzer::new().
+///
+/// # Returns
+/// losses of both players or error message.
+pub fn run(path : &str, evaltable : &str, depth : u8, exact : u32,
+        threshold : f32, cachesz : usize) -> Result<Summary, String> {
+    let txt = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let mut kifu = kifu::check(&txt).map_err(|e| format!("{path}:{e}"))?;
+    let mut wei = weight::Weight::new();
+    wei.read(evaltable)?;
+
+    let st = Instant::now();
+    let mut analyzer = Analyzer::new(&wei, depth, exact, threshold, cachesz);
+    let summary = analyzer.run(&mut kifu)?;
+    for (i, te) in kifu.list.iter().enumerate().filter(|(_, te)| te.mistake) {
+        let best = kifu::Te::new(te.best.unwrap(), te.teban, String::new());
+        println!("move {} {}: {:+.2}, best {} {:+.2}", i + 1, te.pos(),
+            te.playval.unwrap(), best.pos(), te.bestval.unwrap());
+    }
+    print!("{summary}");
+    println!("{}msec", st.elapsed().as_millis());
+
+    kifu.set_info("analysis",
+        &format!("depth {depth}, exact {exact}, threshold {threshold:.2}"));
+    kifu.set_info("blackloss", &summary.black.to_string());
+    kifu.set_info("whiteloss", &summary.white.to_string());
+    let out = outpath(path);
+    kifu.save(&out)?;
+    println!("put the annotated kifu into {out}.");
+    Ok(summary)
+}
+
+#[test]
+fn test_analyze() {
+    assert_eq!(outpath("dir/kifu1.txt"), "dir/kifu1_analyzed.txt");
+    assert_eq!(outpath("kifu1"), "kifu1_analyzed");
+
+    // 終盤から毎回最後の合法手を打つ
+    let mut ban = bitboard::BitBoard::from(
+        "aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w").unwrap();
+    let mut kifu = kifu::Kifu::new();
+    loop {
+        if kifu.finish(&mut ban) {break;}
+        let xy = bitboard::MoveIter::new(ban.legal_moves()).last().unwrap();
+        kifu.play(&mut ban, xy).unwrap();
+    }
+    kifu.winneris(ban.count());
+
+    let mut w = weight::Weight::new();
+    w.init();
+    let mut analyzer = Analyzer::new(&w, 3, 20, 0.5, 1024);
+    let summary = analyzer.run(&mut kifu).unwrap();
+    let mut moves = 0;
+    let mut loss = 0.0;
+    for te in kifu.list.iter() {
+        if te.xy() == bitboard::PASS {
+            assert!(te.best.is_none());
+            continue;
+        }
+        moves += 1;
+        let ban = bitboard::BitBoard::from(&te.rfen).unwrap();
+        let fteban = te.teban as f32;
+        let (bestval, playval) = (te.bestval.unwrap(), te.playval.unwrap());
+        assert_eq!(bestval, endgame::solve(&ban) as f32);
+        assert!(bestval * fteban >= playval * fteban);
+        assert_eq!(te.mistake, (bestval - playval) * fteban > 0.5);
+        loss += (bestval - playval) * fteban;
+    }
+    assert_eq!(summary.black.moves + summary.white.moves, moves);
+    assert_eq!(summary.black.loss + summary.white.loss, loss);
+    assert_eq!(summary.black.mistakes + summary.white.mistakes,
+        kifu.list.iter().filter(|te| te.mistake).count());
+    // 注釈付きの棋譜も正しく読める
+    let txt = format!("{}{}", kifu::HEADER, kifu.to_str());
+    let kifu2 = kifu::check(&txt).unwrap();
+    assert_eq!(kifu2.to_str(), kifu.to_str());
+
+    // 探索でも最善手の値は打った手以上
+    let mut analyzer = Analyzer::new(&w, 3, 0, 0.5, 1024);
+    let summary = analyzer.run(&mut kifu).unwrap();
+    assert_eq!(summary.black.moves + summary.white.moves, moves);
+    assert!(summary.black.loss >= 0.0 && summary.white.loss >= 0.0);
+    for te in kifu.list.iter().filter(|te| te.xy() != bitboard::PASS) {
+        let fteban = te.teban as f32;
+        let (bestval, playval) = (te.bestval.unwrap(), te.playval.unwrap());
+        assert!(bestval * fteban >= playval * fteban);
+        if te.best == Some(te.xy()) {assert_eq!(bestval, playval);}
+    }
+}
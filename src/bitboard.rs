@@ -19,14 +19,59 @@ const STR_NUM : &str = "012345678";
 pub const STONE_SENTE : &str = "@@";
 pub const STONE_GOTE : &str = "[]";
 pub const LSB_CELL : u64 = 0x0000000000000001;
-const LT_CELL : u64 = LSB_CELL;
-const RT_CELL : u64 = 0x0000000000000080;
-const LB_CELL : u64 = 0x0100000000000000;
-const RB_CELL : u64 = 0x8000000000000000;
-const CORNER_CELL : u64 = 0x8100000000000081;
 const GUARD_RIGHT : u64 = 0xfefefefefefefefe;
 const GUARD_LEFT : u64 = 0x7f7f7f7f7f7f7f7f;
 const GUARD_VERTICAL : u64 = 0x7e7e7e7e7e7e7e7e;
+const COL_LEFT : u64 = 0x0101010101010101;
+const COL_RIGHT : u64 = 0x8080808080808080;
+const ROW_TOP : u64 = 0x00000000000000ff;
+const ROW_BOTTOM : u64 = 0xff00000000000000;
+
+/// 確定石を調べる1方向(とその逆方向)
+struct StableDir {
+    /// 隣のマスまでのビット数
+    shift : u32,
+    /// fwd()で盤外からはみ出してくるビットを消すマスク
+    fwd_mask : u64,
+    /// bwd()で盤外からはみ出してくるビットを消すマスク
+    bwd_mask : u64,
+    /// 順方向の隣が盤外になるマス
+    fwd_edge : u64,
+    /// 逆方向の隣が盤外になるマス
+    bwd_edge : u64,
+}
+
+impl StableDir {
+    /// 順方向の隣が`bits`に含まれるマス
+    #[inline]
+    fn fwd(&self, bits : u64) -> u64 {
+        (bits >> self.shift) & self.fwd_mask
+    }
+
+    /// 逆方向の隣が`bits`に含まれるマス
+    #[inline]
+    fn bwd(&self, bits : u64) -> u64 {
+        (bits << self.shift) & self.bwd_mask
+    }
+
+    /// どちらかの隣が`bits`に含まれるか盤外のマス
+    #[inline]
+    fn neighbor(&self, bits : u64) -> u64 {
+        self.fwd(bits) | self.bwd(bits) | self.fwd_edge | self.bwd_edge
+    }
+}
+
+/// 横、縦、斜め(\)、斜め(/)
+const STABLE_DIRS : [StableDir ; 4] = [
+    StableDir {shift : 1, fwd_mask : GUARD_LEFT, bwd_mask : GUARD_RIGHT,
+        fwd_edge : COL_RIGHT, bwd_edge : COL_LEFT},
+    StableDir {shift : NUMCELL as u32, fwd_mask : u64::MAX, bwd_mask : u64::MAX,
+        fwd_edge : ROW_BOTTOM, bwd_edge : ROW_TOP},
+    StableDir {shift : NUMCELL as u32 + 1, fwd_mask : GUARD_LEFT, bwd_mask : GUARD_RIGHT,
+        fwd_edge : COL_RIGHT | ROW_BOTTOM, bwd_edge : COL_LEFT | ROW_TOP},
+    StableDir {shift : NUMCELL as u32 - 1, fwd_mask : GUARD_RIGHT, bwd_mask : GUARD_LEFT,
+        fwd_edge : COL_LEFT | ROW_BOTTOM, bwd_edge : COL_RIGHT | ROW_TOP},
+];
 const BITPTN : [u64 ; 9] = [
     0, 0x1, 0x3, 0x7, 0xf, 0x1f, 0x3f, 0x7f, 0xff,
 ];
@@ -114,26 +159,6 @@ const TBLCHKREV : [i8 ; 16130] = [
 0,0,0,1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,
 ];
 
-// ビットが立っているところに相手の石があったら着手可能かもしれない
-const TBL_SURROUND : [u64 ; CELL_2D] = [
-    // y0
-    0x0302u64, 0x0604u64, 0x0705u64 << 1, 0x0705u64 << 2, 0x0705u64 << 3, 0x0705u64 << 4, 0x6020u64, 0xC040u64,
-    // y1
-    0x030200u64, 0x060400u64, 0x070500u64 << 1, 0x070500u64 << 2, 0x070500u64 << 3, 0x070500u64 << 4, 0x602000u64, 0xC04000u64,
-    // y2
-    0x030203u64 << 8, 0x060406u64 << 8, 0x070507u64 << 9, 0x070507u64 << 10, 0x070507u64 << 11, 0x070507u64 << 12, 0x030103u64 << 13, 0xC040C0u64 << 8,
-    // y3
-    0x030203u64 << 16, 0x060406u64 << 16, 0x070507u64 << 17, 0x070507u64 << 18, 0x070507u64 << 19, 0x070507u64 << 20, 0x030103u64 << 21, 0xC040C0u64 << 16,
-    // y4
-    0x030203u64 << 24, 0x060406u64 << 24, 0x070507u64 << 25, 0x070507u64 << 26, 0x070507u64 << 27, 0x070507u64 << 28, 0x030103u64 << 29, 0xC040C0u64 << 24,
-    // y5
-    0x030203u64 << 32, 0x060406u64 << 32, 0x070507u64 << 33, 0x070507u64 << 34, 0x070507u64 << 35, 0x070507u64 << 36, 0x030103u64 << 37, 0xC040C0u64 << 32,
-    // y6
-    0x000203u64 << 40, 0x000406u64 << 40, 0x000507u64 << 41, 0x000507u64 << 42, 0x000507u64 << 43, 0x000507u64 << 44, 0x000103u64 << 45, 0x0040C0u64 << 40,
-    // y7
-    0x0203u64 << 48, 0x0406u64 << 48, 0x0507u64 << 49, 0x0507u64 << 50, 0x0507u64 << 51, 0x0507u64 << 52, 0x0103u64 << 53, 0x40C0u64 << 48,
-];
-
 macro_rules! bit_left {
     ($x : expr) => {
         $x >>= 1;
@@ -206,17 +231,81 @@ pub fn index2xy(xy : u8) -> (u8, u8) {
 /// # Returns  
 ///   vector of cell index.
 pub fn cells2vec(cells : u64) -> Vec<u8> {
-    let mut remain = cells;
-    let sz = cells.count_ones() as usize;
-    let mut ret = Vec::with_capacity(sz);
-    while remain != 0 {
-        let idx = remain.trailing_zeros() as u8;
-        ret.push(idx);
-        remain ^= 1 << idx;
-    }
-    ret
+    MoveIter::new(cells).collect()
 }
 
+/// 4方向のシフト量と、はみ出し防止のために相手の石にかけるマスク。
+/// 横、縦、斜め(/)、斜め(\)
+const SHIFT_GUARD : [(u32, u64) ; 4] = [
+    (1, GUARD_VERTICAL),
+    (NUMCELL as u32, u64::MAX),
+    (NUMCELL as u32 - 1, GUARD_VERTICAL),
+    (NUMCELL as u32 + 1, GUARD_VERTICAL),
+];
+
+/// Kogge-Stoneで`gen`から`pro`をつたって左シフト方向に埋める。
+#[inline]
+fn fill_shl(gen : u64, pro : u64, shift : u32) -> u64 {
+    let mut gen = gen;
+    let mut pro = pro;
+    gen |= pro & (gen << shift);
+    pro &= pro << shift;
+    gen |= pro & (gen << (shift * 2));
+    pro &= pro << (shift * 2);
+    gen | (pro & (gen << (shift * 4)))
+}
+
+/// Kogge-Stoneで`gen`から`pro`をつたって右シフト方向に埋める。
+#[inline]
+fn fill_shr(gen : u64, pro : u64, shift : u32) -> u64 {
+    let mut gen = gen;
+    let mut pro = pro;
+    gen |= pro & (gen >> shift);
+    pro &= pro >> shift;
+    gen |= pro & (gen >> (shift * 2));
+    pro &= pro >> (shift * 2);
+    gen | (pro & (gen >> (shift * 4)))
+}
+
+/// ビットの立っているマスを小さい順に返す。Vecを作らない。
+///
+/// # Examples
+/// ```
+/// use mylib::bitboard::BitBoard;
+/// let ban = BitBoard::new();
+/// let moves = ban.moves().collect::<Vec<_>>();
+/// assert_eq!(Some(moves), ban.genmove());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct MoveIter {
+    bits : u64,
+}
+
+impl MoveIter {
+    pub fn new(bits : u64) -> MoveIter {
+        MoveIter {bits}
+    }
+}
+
+impl Iterator for MoveIter {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.bits == 0 {return None;}
+
+        let idx = self.bits.trailing_zeros() as u8;
+        self.bits &= self.bits - 1;
+        Some(idx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let sz = self.bits.count_ones() as usize;
+        (sz, Some(sz))
+    }
+}
+
+impl ExactSizeIterator for MoveIter {}
+
 /// # Returns  
 /// (1~8, 1~8)
 /// # Arguments
@@ -239,6 +328,19 @@ pub fn cell(x: u8, y: u8) -> u8 {
 }
 
 
+/// do_move()で変わった物
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Undo {
+    /// 打った手
+    pub xy : u8,
+    /// 返った石
+    pub flips : u64,
+    /// 打つ前の手番
+    pub teban : i8,
+    /// 打つ前のパスの回数
+    pub pass : i8,
+}
+
 #[derive(PartialEq, Clone)]
 pub struct BitBoard {
     pub black: u64,
@@ -483,6 +585,48 @@ impl BitBoard {
         )
     }
 
+    /// draw the board w/ coordinates.
+    ///
+    /// # Arguments
+    /// - `last` : the last move to be marked like ">@@<".
+    /// - `marks` : cells to be marked w/ "..", legal moves for example.
+    pub fn render(&self, last : Option<u8>, marks : u64) -> String {
+        let mut txt = String::from("  ");
+        for c in STR_GOTE.chars().skip(1) {
+            txt += &format!(" {c} ");
+        }
+        txt += "\n";
+        for y in 0..NUMCELL {
+            let mut sep = '|';
+            txt += &format!("{}", y + 1);
+            for x in 0..NUMCELL {
+                let xy = (x + y * NUMCELL) as u8;
+                let bit = LSB_CELL << xy;
+                let islast = last == Some(xy);
+                txt.push(if islast {'>'} else {sep});
+                txt += if self.black & bit != 0 {
+                        STONE_SENTE
+                    } else if self.white & bit != 0 {
+                        STONE_GOTE
+                    } else if marks & bit != 0 {
+                        ".."
+                    } else {
+                        "__"
+                    };
+                sep = if islast {'<'} else {'|'};
+            }
+            txt.push(sep);
+            txt += "\n";
+        }
+        let turn = match self.teban {
+            SENTE => format!("{STONE_SENTE}'s turn."),
+            GOTE => format!("{STONE_GOTE}'s turn."),
+            _ => "finished.".to_string(),
+        };
+        txt + &format!("{STONE_SENTE}:{} {STONE_GOTE}:{} {turn}",
+            self.black.count_ones(), self.white.count_ones())
+    }
+
     pub fn flipturn(&mut self) {
         self.teban = -self.teban;
     }
@@ -545,184 +689,75 @@ impl BitBoard {
         }
     }
 
-    /**
-     * x : 0~7
-     * y : 0~7
-     */
-    fn reverse(&mut self, xy : u8) {
+    /// 手番側の石を`xy`に置いて挟んだ石を返す。
+    ///
+    /// # Returns
+    /// 返した石
+    fn reverse(&mut self, xy : u8) -> u64 {
         if xy >= CELL_2D as u8 {
             panic!("{xy} is out of range.");
         }
 
+        let pos = LSB_CELL << xy;
+        let revall = self.flips(xy);
+        if self.teban == SENTE {
+            self.black |= revall | pos;
+            self.white ^= revall;
+        } else {
+            self.white |= revall | pos;
+            self.black ^= revall;
+        }
+        revall
+    }
+
+    /// 手番側が`xy`に置いたときに返る石
+    ///
+    /// # Arguments
+    /// - `xy` : 空いているマス(0~63)
+    ///
+    /// # Returns
+    /// 返る石のビット。打てないマスなら0。
+    pub fn flips(&self, xy : u8) -> u64 {
         let (mine, oppo) = if self.teban == SENTE {
             (self.black, self.white)
         } else {
             (self.white, self.black)
         };
-
         let pos = LSB_CELL << xy;
-        let (x, y) = index2xy(xy);
-        let x = x as usize;
-        let y = y as usize;
-
         let mut revall = 0;
-
-        // 右
-        const USETZCNT : bool = false;
-        // const USETZCNT : bool = true;
-        // const USE_BIT_CARRY_RIGHT : bool = false;
-        const USE_BIT_CARRY_RIGHT : bool = true;
-        if USETZCNT {
-            let shift = BitBoard::index(x, y) + 1;
-            let mask = (1u64 << (NUMCELL - 1 - x)) - 1;
-            let obits = (oppo >> shift) ^ mask;
-            let o = obits.trailing_zeros();
-            let mbits = (mine >> shift) & mask;
-            let m = mbits >> o;  // その先の自分の石
-            // 相手の石が並んでいて、そのすぐ先に自分の石がある
-            // if 0 < o && o <= 8 && (m & 0x1) != 0 {
-            // if 0 < o && o < (7 - x as u32) && (m & 0x1) != 0 {
-            if 0 < o  && (m & 0x1) != 0 {
-                let rev = (1u64 << o) - 1;
-                revall |= rev << shift;
-            }
-        } else if USE_BIT_CARRY_RIGHT {
-            let op = oppo & GUARD_VERTICAL;
-            let neighbor = pos << 1;
-            let op = op + neighbor;
-            let rev = op & mine & !neighbor;
-            if rev != 0 {revall |= rev - neighbor;}
-        } else {
-            let mut bit = pos << 1;
-            let mut rev = 0;
-            let mut b = bit & oppo & GUARD_RIGHT;
-            while b != 0 {
-                rev |= bit;
-
-                bit_right!(bit);
-                b = bit & oppo & GUARD_RIGHT;
-            }
-            revall |= rev * ((mine & bit & GUARD_RIGHT) != 0) as u64;
+        for (shift, guard) in SHIFT_GUARD {
+            let op = oppo & guard;
+            let rev = fill_shl(pos, op, shift) & op;
+            if (rev << shift) & mine != 0 {revall |= rev;}
+            let rev = fill_shr(pos, op, shift) & op;
+            if (rev >> shift) & mine != 0 {revall |= rev;}
         }
+        revall
+    }
 
-        // 左
-        const USELZCNT : bool = false;
-        // const USELZCNT : bool = true;
-        // const USE_BIT_CARRY_LEFT : bool = false;
-        const USE_BIT_CARRY_LEFT : bool = true;
-        if USELZCNT {
-            let shift = BitBoard::index(NUMCELL - 1 - x, NUMCELL - 1 - y) + 1;
-            let mask = 0xff00000000000000u64 << (NUMCELL - x);
-            let obits = (oppo << shift) ^ mask;  // 石のあるところがゼロになる
-            let o = obits.leading_zeros();  // 相手の石が並んでいる数
-            let mbits = (mine << shift) & mask;
-            let m = mbits << o;  // その先の自分の石
-            // let obito = (oppo << shift) & mask;
-            // println!("(x{x},y{y}), {shift} {mask:x} {oppo:x} {mine:x} {obito:x} {obits:x} {o}>0 {mbits:x} ({m:x} & MSB) != 0");
-            // 相手の石が並んでいて、そのすぐ先に自分の石がある
-            if o > 0 && (m & (0x1 << 63)) != 0 {
-                let rev = (1u64 << o) - 1;
-                let rev = rev << BitBoard::index(x - o as usize, y);
-                revall |= rev;
-            }
-        } else if USE_BIT_CARRY_LEFT {
-            let op = oppo.reverse_bits() & GUARD_VERTICAL;
-            let neighbor = pos.reverse_bits() << 1;
-            let op = op + neighbor;
-            let rev = op & mine.reverse_bits() & !neighbor;
-            if rev != 0 {revall |= (rev - neighbor).reverse_bits();}
+    /// 手番側が打てるマス
+    ///
+    /// # Returns
+    /// 打てるマスのビット。パスなら0。
+    pub fn legal_moves(&self) -> u64 {
+        let (mine, oppo) = if self.teban == SENTE {
+            (self.black, self.white)
         } else {
-            let mut bit = pos >> 1;
-            let mut rev = 0;
-            let mut b = bit & oppo & GUARD_LEFT;
-            while b != 0 {
-                rev |= bit;
-
-                bit_left!(bit);
-                b = bit & oppo & GUARD_LEFT;
-            }
-            if (mine & bit & GUARD_LEFT) != 0 {revall |= rev;}
-        }
-
-        // 下
-        let mut bit = pos << NUMCELL;
-        let mut rev = 0;
-        let mut b = bit & oppo;
-        while b != 0 {
-            rev |= bit;
-
-            bit_down!(bit);
-            b = bit & oppo;
-        }
-        revall |= rev * ((mine & bit) != 0) as u64;
-
-        // 上
-        let mut bit = pos >> NUMCELL;
-        let mut rev = 0;
-        let mut b = bit & oppo;
-        while b != 0 {
-            rev |= bit;
-
-            bit_up!(bit);
-            b = bit & oppo;
-        }
-        revall |= rev * ((mine & bit) != 0) as u64;
-
-        // 右下
-        let mut bit = pos << (NUMCELL + 1);
-        let mut rev = 0;
-        let mut b = bit & oppo & GUARD_RIGHT;
-        while b != 0 {
-            rev |= bit;
-
-            bit_rightdown!(bit);
-            b = bit & oppo & GUARD_RIGHT;
-        }
-        revall |= rev * ((mine & bit & GUARD_RIGHT) != 0) as u64;
-
-        // 右上
-        let mut bit = pos >> (NUMCELL - 1);
-        let mut rev = 0;
-        let mut b = bit & oppo & GUARD_RIGHT;
-        while b != 0 {
-            rev |= bit;
-
-            bit_rightup!(bit);
-            b = bit & oppo & GUARD_RIGHT;
-        }
-        revall |= rev * ((mine & bit & GUARD_RIGHT) != 0) as u64;
-
-        // 左上
-        let mut bit = pos >> (NUMCELL + 1);
-        let mut rev = 0;
-        let mut b = bit & oppo & GUARD_LEFT;
-        while b != 0 {
-            rev |= bit;
-
-            bit_leftup!(bit);
-            b = bit & oppo & GUARD_LEFT;
-        }
-        revall |= rev * ((mine & bit & GUARD_LEFT) != 0) as u64;
-
-        // 左下
-        let mut bit = pos << (NUMCELL - 1);
-        let mut rev = 0;
-        let mut b = bit & oppo & GUARD_LEFT;
-        while b != 0 {
-            rev |= bit;
-
-            bit_leftdown!(bit);
-            b = bit & oppo & GUARD_LEFT;
+            (self.white, self.black)
+        };
+        let mut moves = 0;
+        for (shift, guard) in SHIFT_GUARD {
+            let op = oppo & guard;
+            moves |= (fill_shl(mine, op, shift) & op) << shift;
+            moves |= (fill_shr(mine, op, shift) & op) >> shift;
         }
-        revall |= rev * ((mine & bit & GUARD_LEFT) != 0) as u64;
+        moves & !(mine | oppo)
+    }
 
-        if self.teban == SENTE {
-            self.black = mine | revall | pos;
-            self.white = oppo ^ revall;
-        } else {
-            self.white = mine | revall | pos;
-            self.black = oppo ^ revall;
-        }
+    /// 手番側が打てるマスを順に返す。パスのときは何も返さない。
+    #[allow(dead_code)]
+    pub fn moves(&self) -> MoveIter {
+        MoveIter::new(self.legal_moves())
     }
 
     #[allow(dead_code)]
@@ -975,48 +1010,59 @@ impl BitBoard {
         Ok(ban)
     }
 
+    /// 盤をコピーせずにその場で`xy`に打つ。
+    /// undo_move()で元に戻せる。
+    ///
+    /// # Arguments
+    /// - `xy` : PASS : pass, 0 ~ 63 : cell index.
+    ///
     /// # Returns
-    /// - None : no empty cells.
-    /// - Some(vec![PASS])  : no available cells. pass.
-    /// - Some(Vec![n]) : available cells.
-    pub fn genmove(&self) -> Option<Vec<u8>> {
-        let stones = self.black | self.white;
-        if stones == u64::MAX {return None;}
-
-        let (mine, oppo) = if self.teban == SENTE {
-            (self.black, self.white)
-        } else {
-            (self.white, self.black)
+    /// - Ok(Undo) : undo_move()に渡す。
+    /// - Err(msg) : 石があって打てない。盤は変わらない。
+    pub fn do_move(&mut self, xy : u8) -> Result<Undo, &str> {
+        let undo = Undo {
+            xy, flips : 0, teban : self.teban, pass : self.pass
         };
+        if xy == PASS {  // pass
+            self.pass();
+            return Ok(undo);
+        }
 
-        let mut bits = 0;
-        let mut bit = LSB_CELL;
-        for y in 0..NUMCELL {
-            let row8 = 0xffu64 << y * 8;
-            let exist = row8 & stones;
-            // その列の升が全部埋まってたら次へ。
-            if exist == row8 {
-                bit_down!(bit);
-                continue;
-            }
+        if self.is_filled(xy) {
+            return Err("stone exists.");
+        }
 
-            for x in 0..NUMCELL {
-                let b = bit;
-                bit_right!(bit);
-                let exist = b & stones;
-                if exist != 0 {
-                    continue;
-                }
-                let xy = BitBoard::index(x, y);
-                // check surrounding stones.
-                if (TBL_SURROUND[xy] & oppo) == 0 {continue;}
+        let flips = self.reverse(xy);
+        self.flipturn();
+        self.resetpass();
 
-                if self.checkreverse_ex(xy, oppo, mine) {
-                    bits |= b;
-                }
+        Ok(Undo {flips, ..undo})
+    }
+
+    /// do_move()で打った手を戻す。
+    pub fn undo_move(&mut self, undo : &Undo) {
+        if undo.xy != PASS {
+            let pos = LSB_CELL << undo.xy;
+            if undo.teban == SENTE {
+                self.black ^= undo.flips | pos;
+                self.white ^= undo.flips;
+            } else {
+                self.white ^= undo.flips | pos;
+                self.black ^= undo.flips;
             }
         }
+        self.teban = undo.teban;
+        self.pass = undo.pass;
+    }
+
+    /// # Returns
+    /// - None : no empty cells.
+    /// - Some(vec![PASS])  : no available cells. pass.
+    /// - Some(Vec![n]) : available cells.
+    pub fn genmove(&self) -> Option<Vec<u8>> {
+        if self.is_full() {return None;}
 
+        let bits = self.legal_moves();
         if bits == 0 {  // pass
             // return Some(vec![]);
             return Some(vec![PASS]);
@@ -1194,7 +1240,8 @@ impl BitBoard {
     }
 
     /// オーグメンテーション
-    /// 回転させたものや鏡反転させたものを生成する。
+    /// 回転させたものや鏡反転させたもの(対角線も含む8通り)と、
+    /// それぞれの色を入れ替えたものを生成する。
     ///
     /// # Arguments
     /// - fsb
@@ -1216,412 +1263,95 @@ impl BitBoard {
             (self.rotate180().rotate90(), fsb, fsw, score),
             (self.flip_horz(), fsb, fsw, score),
             (self.flip_vert(), fsb, fsw, score),
+            (self.rotate90().flip_horz(), fsb, fsw, score),
+            (self.rotate90().flip_vert(), fsb, fsw, score),
             // flip color
             (self.flip_all(), fsw, fsb, -score),
             (self.rotate90().flip_all(), fsw, fsb, -score),
             (self.rotate180().flip_all(), fsw, fsb, -score),
             (self.rotate180().rotate90().flip_all(), fsw, fsb, -score),
             (self.flip_horz().flip_all(), fsw, fsb, -score),
-            (self.flip_vert().flip_all(), fsw, fsb, -score)
+            (self.flip_vert().flip_all(), fsw, fsb, -score),
+            (self.rotate90().flip_horz().flip_all(), fsw, fsb, -score),
+            (self.rotate90().flip_vert().flip_all(), fsw, fsb, -score),
         ]
     }
 
-    #[allow(dead_code)]
-    fn fixstones_right(startbit : u64, tgt : u64, count : &mut i32) -> u64 {
-        let mut fcells = 0u64;
-        let mut bit = startbit;
-        for _i in 1..7 {  // →
-            bit_right!(bit);
-            if (tgt & bit) == 0 {return fcells;}
-
-            fcells |= bit;
-            *count += 1;
-        }
-        fcells
-    }
-
-    #[allow(dead_code)]
-    fn fixstones_left(startbit : u64, tgt : u64, count : &mut i32) -> u64 {
-        let mut fcells = 0u64;
-        let mut bit = startbit;
-        for _i in 1..7 {  // →
-            bit_left!(bit);
-            if (tgt & bit) == 0 {return fcells;}
-
-            fcells |= bit;
-            *count += 1;
-        }
-        fcells
-    }
-
-    #[allow(dead_code)]
-    fn fixstones_up(startbit : u64, tgt : u64, count : &mut i32) -> u64 {
-        let mut fcells = 0u64;
-        let mut bit = startbit;
-        for _i in 1..7 {  // ↓
-            bit_up!(bit);
-            if (tgt & bit) == 0 {return fcells;}
-
-            fcells |= bit;
-            *count += 1;
+    /// 各方向について、端から石で埋まっている列に含まれるマスを返す。
+    /// 盤外は埋まっているとみなす。
+    ///
+    /// # Arguments
+    /// - `occupied` : 石のあるマス
+    ///
+    /// # Returns
+    /// [横, 縦, 斜め(\), 斜め(/)]
+    fn filled_lines(occupied : u64) -> [u64 ; 4] {
+        let mut lines = [0u64 ; 4];
+        for (line, dir) in lines.iter_mut().zip(STABLE_DIRS.iter()) {
+            let mut fwd = occupied;
+            let mut bwd = occupied;
+            for _i in 0..NUMCELL - 1 {
+                fwd = occupied & (dir.fwd(fwd) | dir.fwd_edge);
+                bwd = occupied & (dir.bwd(bwd) | dir.bwd_edge);
+            }
+            *line = fwd & bwd;
         }
-        fcells
+        lines
     }
 
-    #[allow(dead_code)]
-    fn fixstones_down(startbit : u64, tgt : u64, count : &mut i32) -> u64 {
-        let mut fcells = 0u64;
-        let mut bit = startbit;
-        for _i in 1..7 {  // ↓
-            bit_down!(bit);
-            if (tgt & bit) == 0 {return fcells;}
+    /// 確定石を探す。
+    /// 4方向すべてについて、
+    /// - 列が埋まっている
+    /// - 片側が盤外か同じ色の確定石
+    ///
+    /// のどちらかを満たす石を確定石とし、増えなくなるまで繰り返す。
+    /// ここで見つかる石は必ず返らない。(全ての確定石を見つけるわけではない。)
+    ///
+    /// # Returns
+    /// (黒の確定石, 白の確定石)
+    pub fn stable_bits(&self) -> (u64, u64) {
+        let black = self.black;
+        let white = self.white;
+        let lines = Self::filled_lines(black | white);
+        let mut fcellsb = 0u64;
+        let mut fcellsw = 0u64;
+        loop {
+            let mut safeb = black;
+            let mut safew = white;
+            for (line, dir) in lines.iter().zip(STABLE_DIRS.iter()) {
+                safeb &= line | dir.neighbor(fcellsb);
+                safew &= line | dir.neighbor(fcellsw);
+            }
+            if safeb == fcellsb && safew == fcellsw {break;}
 
-            fcells |= bit;
-            *count += 1;
+            fcellsb = safeb;
+            fcellsw = safew;
         }
-        fcells
+        (fcellsb, fcellsw)
     }
 
+    /// 確定石の数を数える。
+    ///
+    /// # Returns
+    /// (黒の確定石の数, 白の確定石の数)
     #[allow(dead_code)]
     pub fn fixedstones(&self) -> (i8, i8) {
-        // return (0, 0);  // この関数が遅いのかを見極める用
-        let mut count = 0;
-        let black = self.black;
-        let white = self.white;
-        let mut fcellsb : u64 = black & CORNER_CELL;
-        let mut fcellsw : u64 = white & CORNER_CELL;
-
-        if (fcellsb | fcellsw) == 0 {return (0, 0);}
-
-        // 四隅と辺
-        let bit = LT_CELL;
-        if (black & bit) != 0 {
-            fcellsb |= Self::fixstones_right(bit, black, &mut count);
-            fcellsb |= Self::fixstones_down(bit, black, &mut count);
-        } else if (white & bit) != 0 {
-            fcellsw |= Self::fixstones_right(bit, white, &mut count);
-            fcellsw |= Self::fixstones_down(bit, white, &mut count);
-        }
-        let bit = RT_CELL;
-        if (black & bit) != 0 {
-            fcellsb |= Self::fixstones_left(bit, black, &mut count);
-            fcellsb |= Self::fixstones_down(bit, black, &mut count);
-        } else if (white & bit) != 0 {
-            fcellsw |= Self::fixstones_left(bit, white, &mut count);
-            fcellsw |= Self::fixstones_down(bit, white, &mut count);
-        }
-        let bit = LB_CELL;
-        if (black & bit) != 0 {
-            fcellsb |= Self::fixstones_right(bit, black, &mut count);
-            fcellsb |= Self::fixstones_up(bit, black, &mut count);
-        } else if (white & bit) != 0 {
-            fcellsw |= Self::fixstones_right(bit, white, &mut count);
-            fcellsw |= Self::fixstones_up(bit, white, &mut count);
-        }
-        let bit = RB_CELL;
-        if (black & bit) != 0 {
-            fcellsb |= Self::fixstones_left(bit, black, &mut count);
-            fcellsb |= Self::fixstones_up(bit, black, &mut count);
-        } else if (white & bit) != 0 {
-            fcellsw |= Self::fixstones_left(bit, white, &mut count);
-            fcellsw |= Self::fixstones_up(bit, white, &mut count);
-        }
-        if count < 4 {
-            return (fcellsb.count_ones() as i8, fcellsw.count_ones() as i8);
-        }
-        // 中身
-        // こんな感じなら確定石
-        // xx?  x??
-        // x@?  x@?
-        // x??  xx?
-        for x in 1..7 {
-            let mut cnt = 0;
-            // 左3つ fcells[] == @
-            // 上 fcells[] == @
-            let mut bit = LSB_CELL << BitBoard::index(x, 1);
-            let mut wbit = 0x0000000000010103 << BitBoard::index(x - 1, 0);
-            // let mut wbit = 0x0000000000000107 << BitBoard::index(x - 1, 0);
-            for _y in 0..6 {
-                // println!("bit:{bit:b}, wbit:{wbit:b}");
-
-                if (bit & (fcellsb | fcellsw)) != 0 {
-                   // println!("if fcb | fcw");
-                    bit_down!(bit);
-                    bit_down!(wbit);
-                    continue;
-                }
-
-                if (bit & black) != 0 {
-                    if (fcellsb & wbit) != wbit {
-                        // println!("fcellsb : {fcellsb:b}");
-                        break;
-                    }
-
-                    fcellsb |= bit;
-                    cnt += 1;
-                    // println!("fcellsb : {fcellsb:b}, {cnt}");
-                } else if (bit & white) != 0 {
-                    if (fcellsw & wbit) != wbit {
-                        // println!("fcellsw : {fcellsw:b}");
-                        break;
-                    }
-
-                    fcellsw |= bit;
-                    cnt += 1;
-                } else {
-                    break;
-                }
-
-                bit_down!(bit);
-                bit_down!(wbit);
-            }
-            // 左3つ fcells[] == @
-            // 下 fcells[] == @
-            let mut bit = LSB_CELL << BitBoard::index(x, NUMCELL - 2);
-            let mut wbit = 0x0000000000030101 << BitBoard::index(x - 1, NUMCELL - 3);
-            // let mut wbit = 0x0000000000000407 << BitBoard::index(x - 1, NUMCELL - 3);
-            for _y in (0..6).rev() {
-                if (bit & (fcellsb | fcellsw)) != 0 {
-                    bit_up!(bit);
-                    bit_up!(wbit);
-                    continue;
-                }
-
-                if (bit & black) != 0 {
-                    if (fcellsb & wbit) != wbit {break;}
-
-                    fcellsb |= bit;
-                    cnt += 1;
-                } else if (bit & white) != 0 {
-                    if (fcellsw & wbit) != wbit {break;}
-
-                    fcellsw |= bit;
-                    cnt += 1;
-                    // println!("xy: {x:x}, {y:x}");
-                    // println!("bit:{bit:x}, wbit:{wbit:x}");
-                    // println!("fcellsw : {fcellsw:b}, {cnt}");
-                } else {
-                    break;
-                }
-
-                bit_up!(bit);
-                bit_up!(wbit);
-            }
-            if cnt == 0 {break;}
-        }
-        // xxx  xxx
-        // x@?  ?@x
-        // ???  ???
-        for y in 1..7 {
-            let mut cnt = 0;
-            // 上3つ fcells[] == @
-            // 左 fcells[] == @
-            let mut bit : u64 = LSB_CELL << BitBoard::index(1, y);
-            let mut wbit = 0x0000000000000107 << BitBoard::index(0, y - 1);
-            // let mut wbit = 0x0000000000010103 << BitBoard::index(0, y - 1);
-            for _x in 1..7 {
-                if (bit & (fcellsb | fcellsw)) != 0 {
-                    bit_right!(bit);
-                    bit_right!(wbit);
-                    continue;
-                }
-
-                if (bit & black) != 0 {
-                    if (fcellsb & wbit) != wbit {break;}
-
-                    fcellsb |= bit;
-                    cnt += 1;
-                } else if (bit & white) != 0 {
-                    if (fcellsw & wbit) != wbit {break;}
-
-                    fcellsw |= bit;
-                    cnt += 1;
-                } else {
-                    break;
-                }
-
-                bit_right!(bit);
-                bit_right!(wbit);
-            }
-            // 上3つ fcells[] == @
-            // 右 fcells[] == @
-            let mut bit : u64 = LSB_CELL << BitBoard::index(NUMCELL - 2, y);
-            let mut wbit = 0x0000000000000407 << BitBoard::index(NUMCELL - 3, y - 1);
-            for _x in (1..7).rev() {
-                if (bit & (fcellsb | fcellsw)) != 0 {
-                    bit_left!(bit);
-                    bit_left!(wbit);
-                    continue;
-                }
-
-                if (bit & black) != 0 {
-                    if (fcellsb & wbit) != wbit {break;}
-
-                    fcellsb |= bit;
-                    cnt += 1;
-                } else if (bit & white) != 0 {
-                    if (fcellsw & wbit) != wbit {break;}
-
-                    fcellsw |= bit;
-                    cnt += 1;
-                } else {
-                    break;
-                }
-
-                bit_left!(bit);
-                bit_left!(wbit);
-            }
-            if cnt == 0 {break;}
-        }
-        //
-        // ?xx  ??x
-        // ?@x  ?@x
-        // ??x  ?xx
-        for x in (1..7).rev() {
-            let mut cnt = 0;
-            // 右3つ fcells[] == @
-            // 上 fcells[] == @
-            let mut bit : u64 = LSB_CELL << BitBoard::index(x, 1);
-            let mut wbit = 0x0000000000040406 << BitBoard::index(x - 1, 0);
-            // let mut wbit = 0x0000000000070100 << BitBoard::index(x - 1, 0);
-            for _y in 1..7 {
-                if (bit & (fcellsb | fcellsw)) != 0 {
-                    bit_down!(bit);
-                    bit_down!(wbit);
-                    continue;
-                }
-
-                if (bit & black) != 0 {
-                    if (fcellsb & wbit) != wbit {break;}
-
-                    fcellsb |= bit;
-                    cnt += 1;
-                } else if (bit & white) != 0 {
-                    if (fcellsw & wbit) != wbit {break;}
-
-                    fcellsw |= bit;
-                    cnt += 1;
-                } else {
-                    break;
-                }
-
-                bit_down!(bit);
-                bit_down!(wbit);
-            }
-            // 右3つ fcells[] == @
-            // 下 fcells[] == @
-            let mut bit : u64 = LSB_CELL << BitBoard::index(x, NUMCELL - 2);
-            let mut wbit = 0x0000000000060404 << BitBoard::index(x - 1, NUMCELL - 3);
-            for _y in (1..7).rev() {
-                if (bit & (fcellsb | fcellsw)) != 0 {
-                    bit_up!(bit);
-                    bit_up!(wbit);
-                    continue;
-                }
-
-                if (bit & black) != 0 {
-                    if (fcellsb & wbit) != wbit {break;}
-
-                    fcellsb |= bit;
-                    cnt += 1;
-                } else if (bit & white) != 0 {
-                    if (fcellsw & wbit) != wbit {break;}
-
-                    fcellsw |= bit;
-                    cnt += 1;
-                } else {
-                    break;
-                }
-
-                bit_up!(bit);
-                bit_up!(wbit);
-            }
-            if cnt == 0 {break;}
-        }
-
-        // ???  ???
-        // ?@x  x@?
-        // xxx  xxx
-        for y in (1..7).rev() {
-            let mut cnt = 0;
-            // 下3つ fcells[] == @
-            // 左 fcells[] == @
-            let mut bit : u64 = LSB_CELL << BitBoard::index(1, y);
-            let mut wbit = 0x0000000000070100 << BitBoard::index(0, y - 1);
-            for _x in 1..7 {
-                // println!("bit:{bit:08x}, wbit:{wbit:08x}");
-
-                if (bit & (fcellsb | fcellsw)) != 0 {
-                    bit_right!(bit);
-                    bit_right!(wbit);
-                    continue;
-                }
-
-                if (bit & black) != 0 {
-                    if (fcellsb & wbit) != wbit {break;}
-
-                    fcellsb |= bit;
-                    cnt += 1;
-                } else if (bit & white) != 0 {
-                    if (fcellsw & wbit) != wbit {break;}
-
-                    fcellsw |= bit;
-                    cnt += 1;
-                    // println!("fcellsw : {fcellsw:08x}, {cnt}");
-                } else {
-                    break;
-                }
-
-                bit_right!(bit);
-                bit_right!(wbit);
-            }
-            // 下3つ fcells[] == @
-            // 右 fcells[] == @
-            let mut bit : u64 = LSB_CELL << BitBoard::index(NUMCELL - 2, y);
-            let mut wbit = 0x0000000000070400 << BitBoard::index(NUMCELL - 3, y - 1);
-            for _x in (1..7).rev() {
-                let cb = (bit & black) != 0;
-                let cw = (bit & white) != 0;
-                if !(cb | cw) {break;}
-
-                if (bit & (fcellsb | fcellsw)) != 0 {
-                    bit_left!(bit);
-                    bit_left!(wbit);
-                    continue;
-                }
-
-                if (bit & black) != 0 {
-                    if (fcellsb & wbit) != wbit {break;}
-
-                    fcellsb |= bit;
-                    cnt += 1;
-                } else if (bit & white) != 0 {
-                    if (fcellsw & wbit) != wbit {break;}
-
-                    fcellsw |= bit;
-                    cnt += 1;
-                } else {
-                    break;
-                }
-
-                bit_left!(bit);
-                bit_left!(wbit);
-            }
-            if cnt == 0 {break;}
-        }
-        //
-        // xは@と同じ色の確定石
-        // println!("fc:{fcells:?}, {count:?}");
-        // println!("fc:{count:?}");
-        // for i in 0..8 {
-        //     for j in 0..8 {
-        //         print!("{},", fcells[i * 8 + j]);
-        //     }
-        //     println!("");
-        // }
-        // println!("fc:{fcellsb:b}, {fcellsw:b}");
+        let (fcellsb, fcellsw) = self.stable_bits();
         (fcellsb.count_ones() as i8, fcellsw.count_ones() as i8)
     }
+
+    /// 確定石から最終的な石の差の範囲を求める。
+    /// 自分の確定石は最後まで自分の石、相手の確定石は最後まで相手の石なので
+    /// 空きマスを勝った方に数えても数えなくても範囲に入る。
+    ///
+    /// # Returns
+    /// 手番側から見た(下限, 上限)
+    pub fn stable_bounds(&self) -> (i8, i8) {
+        let (fb, fw) = self.stable_bits();
+        let (mine, oppo) = if self.teban == SENTE {(fb, fw)} else {(fw, fb)};
+        let n = CELL_2D as i8;
+        (2 * mine.count_ones() as i8 - n, n - 2 * oppo.count_ones() as i8)
+    }
 }
 
 /// count # of empty cells
@@ -1733,7 +1463,7 @@ fn testbitbrd() {
     assert_eq!(b.black, 0x004121110905037E);
     assert_eq!(b.white, 0xffbedeeef6fafc80);
     assert!(b.checkreverse(BitBoard::index(0, 0)));
-    assert_eq!(b.fixedstones(), (0, 15));
+    assert_eq!(b.fixedstones(), (0, 45));
     assert_eq!(b.count(),
       6 + 2 + 2 + 2 + 2 + 2 + 2 -
       (1 + 6 + 1 + 5 + 2 + 4 + 3 + 3 + 4 + 2 + 5 + 1 + 8));
@@ -1752,7 +1482,7 @@ fn testbitbrd() {
     assert_eq!(b90.to_string(), "h/AeAa/AdAb/AcAc/AbAd/AaAe/Bf/1Fa w");
     assert_eq!(b90.black, 0x7e03050911214100);
     assert_eq!(b90.white, 0x80FCFAF6EEDEBEFF);
-    assert_eq!(b90.fixedstones(), (0, 15));
+    assert_eq!(b90.fixedstones(), (0, 45));
     let br = b90.r#move(cell(1, 8));
     assert!(br.is_ok());
     let br = br.unwrap();
@@ -1763,7 +1493,7 @@ fn testbitbrd() {
     assert_eq!(b180.black, 0x7EC0A09088848200);
     assert_eq!(b180.white, 0x013f5f6f777b7dff);
     // b180.put();
-    assert_eq!(b180.fixedstones(), (0, 15));
+    assert_eq!(b180.fixedstones(), (0, 45));
     let b = b180.r#move(cell(8, 8));
     assert!(b.is_ok());
     let b = b.unwrap();
@@ -1777,7 +1507,7 @@ fn testbitbrd() {
     assert_eq!(b.black, 0xFF7D7B776F5F3F01);
     assert_eq!(b.white, 0x0082848890A0C07E);
     assert!(b.checkreverse(BitBoard::index(7, 0)));
-    assert_eq!(b.fixedstones(), (15, 0));
+    assert_eq!(b.fixedstones(), (45, 0));
     assert_eq!(b.count(),
       -(6 + 2 + 2 + 2 + 2 + 2 + 2) +
       (1 + 6 + 1 + 5 + 2 + 4 + 3 + 3 + 4 + 2 + 5 + 1 + 8));
@@ -1796,7 +1526,7 @@ fn testbitbrd() {
     assert_eq!(b90.to_string(), "1fA/bF/aAaE/aBaD/aCaC/aDaB/aEaA/H b");
     assert_eq!(b90.black, 0xFFBEDEEEF6FAFC80);
     assert_eq!(b90.white, 0x004121110905037E);
-    assert_eq!(b90.fixedstones(), (15, 0));
+    assert_eq!(b90.fixedstones(), (45, 0));
     let br = b90.r#move(cell(1, 1));
     assert!(br.is_ok());
     let br = br.unwrap();
@@ -1810,7 +1540,7 @@ fn testbitbrd() {
     assert_eq!(b180.black, 0x80FCFAF6EEDEBEFF);
     assert_eq!(b180.white, 0x7E03050911214100);
     assert!(b180.checkreverse(BitBoard::index(0, 7)));
-    assert_eq!(b180.fixedstones(), (15, 0));
+    assert_eq!(b180.fixedstones(), (45, 0));
     assert_eq!(b180.count(),
       -(6 + 2 + 2 + 2 + 2 + 2 + 2) +
       (1 + 6 + 1 + 5 + 2 + 4 + 3 + 3 + 4 + 2 + 5 + 1 + 8));
@@ -2067,7 +1797,7 @@ fn testbitbrd() {
     let b = b.unwrap();
     assert_eq!(b.to_obf(),
         "X-X-X----XXX----XXXXXXXX-XXX----X-X-X-----X--X----X---X---X----X O");
-    assert_eq!(b.fixedstones(), (2, 0));
+    assert_eq!(b.fixedstones(), (3, 0));
     assert_eq!(b.count(), 26);
     let mv = b.genmove();
     assert_eq!(mv, Some(vec![cell(0, 0)]));
@@ -2086,7 +1816,7 @@ fn testbitbrd() {
     let b = b.unwrap();
     assert_eq!(b.to_obf(),
         "X----X---X---X----X--X-----X-X-X----XXX-XXXXXXXX----XXX----X-X-X O");
-    assert_eq!(b.fixedstones(), (2, 0));
+    assert_eq!(b.fixedstones(), (3, 0));
     assert_eq!(b.count(), 26);
     let mv = b.genmove();
     // assert_eq!(mv, Some(vec![]));
@@ -7036,3 +6766,189 @@ fn test_progress() {
         assert!(ban.is_progress(prgs));
     }
 }
+
+#[test]
+fn test_legal_moves() {
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(28);
+    for _i in 0..100 {
+        let mut ban = BitBoard::new();
+        while !ban.is_full() && !ban.is_passpass() {
+            let (mine, oppo) = if ban.teban == SENTE {
+                (ban.black, ban.white)
+            } else {
+                (ban.white, ban.black)
+            };
+            // 1マスずつ調べた結果と同じになる
+            let mut bits = 0;
+            for xy in 0..CELL_2D {
+                if ban.is_filled(xy as u8) {continue;}
+
+                if ban.checkreverse_ex(xy, oppo, mine) {
+                    bits |= LSB_CELL << xy;
+                    assert_ne!(ban.flips(xy as u8), 0, "{ban} {xy}");
+                } else {
+                    assert_eq!(ban.flips(xy as u8), 0, "{ban} {xy}");
+                }
+            }
+            assert_eq!(ban.legal_moves(), bits, "{ban}");
+            assert_eq!(ban.moves().len(), bits.count_ones() as usize);
+            assert_eq!(ban.moves().collect::<Vec<_>>(), cells2vec(bits));
+
+            let moves = ban.genmove().unwrap();
+            let mv = moves[rng.gen_range(0, moves.len())];
+            let nb = ban.r#move(mv).unwrap();
+            if mv != PASS {
+                // 返った石は置いた石と挟んだ石だけ
+                let flips = ban.flips(mv);
+                assert_eq!(flips & mine, 0);
+                assert_eq!(flips & !oppo, 0);
+                assert_eq!((nb.black ^ ban.black) | (nb.white ^ ban.white),
+                           flips | (LSB_CELL << mv));
+            }
+            ban = nb;
+        }
+    }
+}
+
+#[test]
+fn test_do_move() {
+    use rand::{Rng, SeedableRng};
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(29);
+    for _i in 0..100 {
+        let mut ban = BitBoard::new();
+        let mut history = Vec::new();
+        while !ban.is_full() && !ban.is_passpass() {
+            let moves = ban.genmove().unwrap();
+            for &mv in moves.iter() {
+                let mut b = ban.clone();
+                let undo = b.do_move(mv).unwrap();
+                assert!(b == ban.r#move(mv).unwrap(), "{ban} {mv}");
+                b.undo_move(&undo);
+                assert!(b == ban, "{ban} {mv}");
+            }
+            let mv = moves[rng.gen_range(0, moves.len())];
+            history.push((ban.clone(), ban.do_move(mv).unwrap()));
+        }
+        // 最後から全部戻す
+        while let Some((prev, undo)) = history.pop() {
+            ban.undo_move(&undo);
+            assert!(ban == prev);
+        }
+        assert!(ban == BitBoard::new());
+    }
+    let mut ban = BitBoard::new();
+    let before = ban.clone();
+    assert!(ban.do_move(cell(4, 4)).is_err());
+    assert!(ban == before);
+}
+
+/// 総当たりで確定石を求める。
+/// どちらの手番がどの順番で打っても返らない石を確定石とする。
+#[cfg(test)]
+fn stable_bits_bruteforce(ban : &BitBoard) -> (u64, u64) {
+    let mut visited = std::collections::HashSet::new();
+    let mut stack = vec![(ban.black, ban.white)];
+    let mut flipped = 0u64;
+    while let Some((black, white)) = stack.pop() {
+        if !visited.insert((black, white)) {continue;}
+
+        let empty = !(black | white);
+        for xy in 0..CELL_2D {
+            if empty & (LSB_CELL << xy) == 0 {continue;}
+
+            for teban in [SENTE, GOTE] {
+                let b = BitBoard {black, white, teban, pass : 0};
+                if !b.checkreverse(xy) {continue;}
+
+                let nb = b.r#move(xy as u8).unwrap();
+                flipped |= (black & nb.white) | (white & nb.black);
+                stack.push((nb.black, nb.white));
+            }
+        }
+    }
+    (ban.black & !flipped, ban.white & !flipped)
+}
+
+#[test]
+fn test_stable_bits() {
+    use rand::{Rng, SeedableRng};
+
+    let check = |ban : &BitBoard| {
+        let (fb, fw) = ban.stable_bits();
+        let (eb, ew) = stable_bits_bruteforce(ban);
+        // 見つけた石は必ず確定石
+        assert_eq!(fb & !eb, 0, "{ban}");
+        assert_eq!(fw & !ew, 0, "{ban}");
+        assert_eq!(ban.fixedstones(),
+            (fb.count_ones() as i8, fw.count_ones() as i8));
+    };
+
+    // 全部埋まっていれば全部確定石
+    let ban = BitBoard::from("aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBbB1/BbBb/dD/Dd w").unwrap();
+    assert!(ban.is_full());
+    assert_eq!(ban.stable_bits(), (ban.black, ban.white));
+    assert_eq!(stable_bits_bruteforce(&ban), (ban.black, ban.white));
+    // 埋まった列に囲まれた内側の石
+    let ban = BitBoard::from("1Fa/Bf/AaAe/AbAd/AcAc/AdAb/AeAa/h w").unwrap();
+    assert_eq!(ban.fixedstones(), (0, 45));
+    check(&ban);
+    let ban = BitBoard::from("aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w").unwrap();
+    check(&ban);
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(27);
+    for _i in 0..100 {
+        let mut ban = BitBoard::new();
+        let nempty = rng.gen_range(1, 9);
+        while !ban.is_full() && !ban.is_passpass() {
+            if CELL_2D - (ban.black | ban.white).count_ones() as usize <= nempty {
+                break;
+            }
+            let moves = ban.genmove().unwrap();
+            let mv = moves[rng.gen_range(0, moves.len())];
+            ban = ban.r#move(mv).unwrap();
+        }
+        check(&ban);
+    }
+}
+
+#[test]
+fn test_rotated_mirrored() {
+    let ban = BitBoard::from("2A1A1A1/3c2/Ac1bA/3c2/2cAa1/1a1Aa2A/A3a3/4A3 w").unwrap();
+    let (fsb, fsw) = ban.fixedstones();
+    let variants = ban.rotated_mirrored(fsb, fsw, 10);
+    assert_eq!(variants.len(), 16);
+    let key = |b : &BitBoard| (b.black, b.white, b.teban);
+    let keys = variants.iter().map(|(b, ..)| key(b))
+        .collect::<std::collections::HashSet<_>>();
+    // 対称性のない局面なので全部違う
+    assert_eq!(keys.len(), 16);
+    for (b, fb, fw, score) in variants.iter() {
+        assert_eq!(b.count(), ban.count() * b.teban * ban.teban);
+        assert_eq!(b.fixedstones(), (*fb, *fw));
+        assert_eq!(*score, if b.teban == ban.teban {10} else {-10});
+        // どれから作っても同じ集合になる
+        let keys2 = b.rotated_mirrored(*fb, *fw, *score).iter()
+            .map(|(b, ..)| key(b)).collect::<std::collections::HashSet<_>>();
+        assert_eq!(keys, keys2);
+    }
+}
+
+#[test]
+fn test_render() {
+    let ban = BitBoard::new();
+    let txt = ban.render(None, ban.legal_moves());
+    let lines = txt.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 10);
+    assert_eq!(lines[0], "   a  b  c  d  e  f  g  h ");
+    assert_eq!(lines[3], "3|__|__|__|__|..|__|__|__|");
+    assert_eq!(lines[9], "@@:2 []:2 @@'s turn.");
+    let xy = cells2vec(ban.legal_moves())[0];
+    let ban = ban.r#move(xy).unwrap();
+    let (x, y) = cell2xy(xy);
+    let line = ban.render(Some(xy), 0).lines().nth(y as usize).unwrap().to_string();
+    assert_eq!(&line[x as usize * 3 - 2..x as usize * 3 + 2], ">@@<");
+    assert!(ban.render(None, 0).ends_with("@@:4 []:1 []'s turn."));
+}
@@ -0,0 +1,838 @@
+use super::*;
+use std::collections::HashSet;
+
+const FIELD_NONE : u32 = 0x0;
+const FIELD_FIXEDSTONES : u32 = 0x1;
+const FIELD_MOVENO : u32 = 0x2;
+const FIELD_STAGE : u32 = 0x4;
+const FIELD_TARGET : u32 = 0x8;
+
+/// shard format
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    /// little endian records.
+    /// black:u64, white:u64, teban:i8, score:i8,
+    /// [fixed black:i8, fixed white:i8], [move#:u8], [stage:u8], [target:f32]
+    Binary,
+    /// rfen,score[,fixedblack,fixedwhite][,moveno][,stage][,target]
+    Csv,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Binary => write!(f, "bin"),
+            Format::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+impl Format {
+    pub fn from(txt : &str) -> Option<Format> {
+        match txt.to_ascii_lowercase().as_str() {
+            "bin" | "binary" => Some(Format::Binary),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// training target of positions.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Target {
+    /// final score of the game.
+    Result,
+    /// root search value recorded in the kifu.
+    Search,
+    /// (1 - w) * final score + w * search value.
+    Blend(f32),
+    /// TD(λ) return over search values of the following positions.
+    Td(f32),
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Result => write!(f, "result"),
+            Target::Search => write!(f, "search"),
+            Target::Blend(w) => write!(f, "blend:{w}"),
+            Target::Td(lambda) => write!(f, "td:{lambda}"),
+        }
+    }
+}
+
+impl Target {
+    /// - result
+    /// - search
+    /// - blend[:w] : 0 <= w <= 1. default 0.5.
+    /// - td[:lambda] : 0 <= lambda <= 1. default 0.7.
+    pub fn from(txt : &str) -> Option<Target> {
+        let lower = txt.to_ascii_lowercase();
+        let (name, param) = match lower.split_once(':') {
+            Some((name, param)) => (name, Some(param.parse::<f32>().ok()?)),
+            None => (lower.as_str(), None),
+        };
+        if param.is_some_and(|p| !(0.0..=1.0).contains(&p)) {
+            return None;
+        }
+        match name {
+            "result" if param.is_none() => Some(Target::Result),
+            "search" if param.is_none() => Some(Target::Search),
+            "blend" => Some(Target::Blend(param.unwrap_or(0.5))),
+            "td" => Some(Target::Td(param.unwrap_or(0.7))),
+            _ => None,
+        }
+    }
+
+    /// targets of the positions in a kifu from black's view.
+    /// a position w/o search value falls back to the final score.
+    ///
+    /// # Returns
+    /// a target for every move or None if the kifu has no result.
+    pub fn targets(&self, kifu : &kifu::Kifu) -> Option<Vec<f32>> {
+        let score = kifu.score? as f32;
+        let vals = kifu.list.iter().map(|te| te.val.unwrap_or(score));
+        Some(match self {
+            Target::Result => vec![score ; kifu.list.len()],
+            Target::Search => vals.collect(),
+            Target::Blend(w) => vals.map(|v| (1.0 - w) * score + w * v).collect(),
+            Target::Td(lambda) => {
+                // G(t) = (1 - λ) * V(t + 1) + λ * G(t + 1)
+                // 終局後は V = G = 最終結果
+                let mut ret = vec![0.0 ; kifu.list.len()];
+                let mut g = score;
+                let mut v = score;
+                for (t, te) in kifu.list.iter().enumerate().rev() {
+                    g = (1.0 - lambda) * v + lambda * g;
+                    ret[t] = g;
+                    v = te.val.unwrap_or(g);
+                }
+                ret
+            },
+        })
+    }
+}
+
+/// read comma separated optional fields.
+///
+/// - `txt` : COMMA separated fields.
+///   - fixedstones : # of fixed stones of black and white.
+///   - moveno      : move number in the kifu. 1~.
+///   - stage       : progress of weights. 0~2.
+///   - target      : training target from black's view. see Target.
+fn read_fields(txt : &str) -> Result<u32, String> {
+    let mut res = FIELD_NONE;
+    for fld in txt.split(',').filter(|f| !f.is_empty()) {
+        match fld.to_ascii_lowercase().as_str() {
+            "fixedstones" => {res |= FIELD_FIXEDSTONES},
+            "moveno" => {res |= FIELD_MOVENO},
+            "stage" => {res |= FIELD_STAGE},
+            "target" => {res |= FIELD_TARGET},
+            _ => {return Err(format!("unknown field: \"{fld}\""));}
+        }
+    }
+    Ok(res)
+}
+
+/// list up kifu files in `dir`. file names have to contain "kifu".
+///
+/// # Returns
+/// sorted file names or error message.
+pub fn listkifu(dir : &str) -> Result<Vec<String>, String> {
+    let files = std::fs::read_dir(dir).map_err(|e| format!("{dir}: {e}"))?;
+    let mut files = files.filter_map(|entry| {
+        entry.ok().and_then(|e|
+            e.path().file_name().and_then(|n|
+                n.to_str().map(String::from)
+            )
+        )}).filter(|fnm| fnm.contains("kifu"))
+        .collect::<Vec<String>>();
+    files.sort();
+    Ok(files)
+}
+
+/// read a kifu file strictly. see kifu::check().
+///
+/// # Returns
+/// kifu or error message w/ the KifuError for a malformed kifu.
+/// the score is None for "on going...".
+pub fn readkifu(path : &str) -> Result<kifu::Kifu, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("{path}: {e}"))?;
+    kifu::check(&content).map_err(|e| format!("{path}:{e}"))
+}
+
+/// a position in a kifu.
+pub struct Position {
+    pub ban : bitboard::BitBoard,
+    /// final score from black's view.
+    pub score : i8,
+    /// # of fixed stones. (black, white)
+    pub fixedstones : (i8, i8),
+    /// move number in the kifu. 1~.
+    pub moveno : u8,
+    /// training target from black's view.
+    pub target : f32,
+}
+
+impl Position {
+    fn to_bytes(&self, fields : u32, buf : &mut Vec<u8>) {
+        buf.extend_from_slice(&self.ban.black.to_le_bytes());
+        buf.extend_from_slice(&self.ban.white.to_le_bytes());
+        buf.push(self.ban.teban as u8);
+        buf.push(self.score as u8);
+        if fields & FIELD_FIXEDSTONES != 0 {
+            buf.push(self.fixedstones.0 as u8);
+            buf.push(self.fixedstones.1 as u8);
+        }
+        if fields & FIELD_MOVENO != 0 {buf.push(self.moveno);}
+        if fields & FIELD_STAGE != 0 {buf.push(self.ban.progress() as u8);}
+        if fields & FIELD_TARGET != 0 {
+            buf.extend_from_slice(&self.target.to_le_bytes());
+        }
+    }
+
+    /// read records written by to_bytes().
+    fn from_bytes(fields : u32, buf : &[u8]) -> Option<Vec<Position>> {
+        let sz = record_size(fields);
+        if !buf.len().is_multiple_of(sz) {return None;}
+
+        Some(buf.chunks(sz).map(|rec| {
+            let ban = bitboard::BitBoard {
+                black : u64::from_le_bytes(rec[0..8].try_into().unwrap()),
+                white : u64::from_le_bytes(rec[8..16].try_into().unwrap()),
+                teban : rec[16] as i8,
+                pass : 0,
+            };
+            let score = rec[17] as i8;
+            let mut i = 18;
+            let fixedstones = if fields & FIELD_FIXEDSTONES != 0 {
+                i += 2;
+                (rec[i - 2] as i8, rec[i - 1] as i8)
+            } else {
+                ban.fixedstones()
+            };
+            let moveno = if fields & FIELD_MOVENO != 0 {
+                i += 1;
+                rec[i - 1]
+            } else {
+                0
+            };
+            if fields & FIELD_STAGE != 0 {i += 1;}
+            let target = if fields & FIELD_TARGET != 0 {
+                f32::from_le_bytes(rec[i..i + 4].try_into().unwrap())
+            } else {
+                score as f32
+            };
+            Position {ban, score, fixedstones, moveno, target}
+        }).collect())
+    }
+
+    /// read a line written by to_csv().
+    fn from_csv(fields : u32, line : &str) -> Option<Position> {
+        let mut elem = line.split(',');
+        let ban = bitboard::BitBoard::from(elem.next()?).ok()?;
+        let score = elem.next()?.parse::<i8>().ok()?;
+        let fixedstones = if fields & FIELD_FIXEDSTONES != 0 {
+            (elem.next()?.parse::<i8>().ok()?, elem.next()?.parse::<i8>().ok()?)
+        } else {
+            ban.fixedstones()
+        };
+        let moveno = if fields & FIELD_MOVENO != 0 {
+            elem.next()?.parse::<u8>().ok()?
+        } else {
+            0
+        };
+        if fields & FIELD_STAGE != 0 {elem.next()?;}
+        let target = if fields & FIELD_TARGET != 0 {
+            elem.next()?.parse::<f32>().ok()?
+        } else {
+            score as f32
+        };
+        Some(Position {ban, score, fixedstones, moveno, target})
+    }
+
+    fn to_csv(&self, fields : u32) -> String {
+        let mut line = format!("{},{}", self.ban, self.score);
+        if fields & FIELD_FIXEDSTONES != 0 {
+            line += &format!(",{},{}", self.fixedstones.0, self.fixedstones.1);
+        }
+        if fields & FIELD_MOVENO != 0 {line += &format!(",{}", self.moveno);}
+        if fields & FIELD_STAGE != 0 {line += &format!(",{}", self.ban.progress());}
+        if fields & FIELD_TARGET != 0 {line += &format!(",{}", self.target);}
+        line
+    }
+}
+
+/// size of a record in a binary shard.
+fn record_size(fields : u32) -> usize {
+    let mut sz = 8 + 8 + 1 + 1;
+    if fields & FIELD_FIXEDSTONES != 0 {sz += 2;}
+    if fields & FIELD_MOVENO != 0 {sz += 1;}
+    if fields & FIELD_STAGE != 0 {sz += 1;}
+    if fields & FIELD_TARGET != 0 {sz += 4;}
+    sz
+}
+
+fn csv_header(fields : u32) -> String {
+    let mut line = String::from("rfen,score");
+    if fields & FIELD_FIXEDSTONES != 0 {line += ",fixedblack,fixedwhite";}
+    if fields & FIELD_MOVENO != 0 {line += ",moveno";}
+    if fields & FIELD_STAGE != 0 {line += ",stage";}
+    if fields & FIELD_TARGET != 0 {line += ",target";}
+    line
+}
+
+/// 対称な局面(回転、鏡像、色反転)の中で最小のものを代表にする。
+fn normalize(ban : &bitboard::BitBoard) -> (u64, u64, i8) {
+    normalize_sign(ban).0
+}
+
+/// the representative of symmetric positions and a sign.
+///
+/// # Returns
+/// (representative, sign) : score of `ban` is sign * score of the representative.
+pub fn normalize_sign(ban : &bitboard::BitBoard) -> ((u64, u64, i8), i8) {
+    ban.rotated_mirrored(0, 0, 0).iter().enumerate()
+        .map(|(i, (b, ..))| {
+            // 後半8つは色反転
+            ((b.black, b.white, b.teban), if i < 8 {1} else {-1})
+        }).min().unwrap()
+}
+
+/// field names in manifest.json to optional field bits.
+fn fields_from_manifest(names : &[&str]) -> Result<u32, String> {
+    let mut res = FIELD_NONE;
+    for name in names {
+        match *name {
+            "black" | "white" | "teban" | "rfen" | "score" | "fixedwhite" => {},
+            "fixedblack" => {res |= FIELD_FIXEDSTONES},
+            "moveno" => {res |= FIELD_MOVENO},
+            "stage" => {res |= FIELD_STAGE},
+            "target" => {res |= FIELD_TARGET},
+            _ => {return Err(format!("unknown field: \"{name}\""));}
+        }
+    }
+    Ok(res)
+}
+
+/// optional field bits to COMMA separated names for export().
+fn fields_to_str(fields : u32) -> String {
+    [
+        (FIELD_FIXEDSTONES, "fixedstones"),
+        (FIELD_MOVENO, "moveno"),
+        (FIELD_STAGE, "stage"),
+        (FIELD_TARGET, "target"),
+    ].iter().filter(|(bit, _)| fields & bit != 0)
+        .map(|(_, name)| *name).collect::<Vec<_>>().join(",")
+}
+
+/// a value of `key` in a manifest.json written by export().
+/// strings are returned w/o quotes and arrays w/o brackets.
+fn manifest_item<'a>(json : &'a str, key : &str) -> Option<&'a str> {
+    let start = json.find(&format!("\"{key}\":"))? + key.len() + 3;
+    let rest = &json[start..];
+    if let Some(array) = rest.strip_prefix('[') {
+        return Some(&array[..array.find(']')?]);
+    }
+    let end = rest.find([',', '}'])?;
+    Some(rest[..end].trim_matches('"'))
+}
+
+/// positions collected from kifus.
+/// positions which are symmetric to a collected one are skipped if `dedup`.
+pub struct Dataset {
+    pub positions : Vec<Position>,
+    seen : HashSet<(u64, u64, i8)>,
+    augment : bool,
+    dedup : bool,
+    target : Target,
+    pub nkifus : usize,
+    pub duplicates : usize,
+    /// positions w/ this or less empty cells have exact scores. 0 : none.
+    pub exact : u32,
+}
+
+impl Dataset {
+    /// # Arguments
+    /// - `augment` : add every rotated, mirrored and color flipped position.
+    pub fn new(augment : bool) -> Dataset {
+        Dataset {
+            positions : Vec::new(),
+            seen : HashSet::new(),
+            augment,
+            dedup : false,
+            target : Target::Result,
+            nkifus : 0,
+            duplicates : 0,
+            exact : 0,
+        }
+    }
+
+    /// set the training target of positions added after this.
+    pub fn set_target(&mut self, target : Target) {
+        self.target = target;
+    }
+
+    /// skip positions symmetric to a collected one after this.
+    /// only the first result of a shared position remains,
+    /// so this is for exporting and not for training.
+    pub fn set_dedup(&mut self, dedup : bool) {
+        self.dedup = dedup;
+    }
+
+    /// add positions in a kifu.
+    /// full boards are skipped since counting stones is enough.
+    pub fn add_kifu(&mut self, kifu : &kifu::Kifu) -> Result<(), String> {
+        let score = kifu.score.ok_or("no result in the kifu.")?;
+        let targets = self.target.targets(kifu).unwrap();
+        for ((i, te), &target) in kifu.list.iter().enumerate().zip(targets.iter()) {
+            let ban = bitboard::BitBoard::from(&te.rfen)?;
+            if ban.is_full() {continue;}
+
+            if self.dedup && !self.seen.insert(normalize(&ban)) {
+                self.duplicates += 1;
+                continue;
+            }
+
+            let moveno = (i + 1) as u8;
+            let (fsb, fsw) = ban.fixedstones();
+            if !self.augment {
+                self.positions.push(Position {
+                    ban, score, fixedstones : (fsb, fsw), moveno, target});
+                continue;
+            }
+
+            let mut variants = HashSet::new();
+            let variant = ban.rotated_mirrored(fsb, fsw, score);
+            for (j, (b, fb, fw, sc)) in variant.into_iter().enumerate() {
+                // 対称な局面からは同じものができる
+                if !variants.insert((b.black, b.white, b.teban)) {continue;}
+
+                // 後半8つは色反転
+                let target = if j < 8 {target} else {-target};
+                self.positions.push(Position {
+                    ban : b, score : sc, fixedstones : (fb, fw), moveno, target});
+            }
+        }
+        self.nkifus += 1;
+        Ok(())
+    }
+
+    /// replace scores and targets w/ exact scores.
+    ///
+    /// # Arguments
+    /// - `lb` : solver. positions w/ many empty cells are left as they are.
+    ///
+    /// # Returns
+    /// # of labelled positions or error message.
+    pub fn label(&mut self, lb : &mut endgame::Labeller) -> Result<usize, String> {
+        let mut n = 0;
+        for pos in self.positions.iter_mut() {
+            if let Some(score) = lb.label(&pos.ban)? {
+                pos.score = score;
+                pos.target = score as f32;
+                n += 1;
+            }
+        }
+        self.exact = self.exact.max(lb.maxempties());
+        Ok(n)
+    }
+
+    /// read a dataset written by export().
+    ///
+    /// # Returns
+    /// (dataset, format, COMMA separated optional fields, max # of positions in a shard)
+    /// or error message.
+    pub fn load(dir : &str) -> Result<(Dataset, Format, String, usize), String> {
+        let path = std::path::Path::new(dir).join("manifest.json");
+        let json = std::fs::read_to_string(&path)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        let invalid = |key : &str| format!("{}: invalid {key}.", path.display());
+        let format = manifest_item(&json, "format").and_then(Format::from)
+            .ok_or(invalid("format"))?;
+        let names = manifest_item(&json, "fields").ok_or(invalid("fields"))?
+            .split(',').map(|f| f.trim_matches('"')).collect::<Vec<_>>();
+        let flds = fields_from_manifest(&names)?;
+        let number = |key : &str| {
+            manifest_item(&json, key).and_then(|n| n.parse::<usize>().ok())
+                .ok_or(invalid(key))
+        };
+
+        let mut ds = Dataset::new(manifest_item(&json, "augment") == Some("true"));
+        ds.nkifus = number("kifus")?;
+        ds.duplicates = number("duplicates")?;
+        ds.exact = manifest_item(&json, "exact")
+            .and_then(|n| n.parse::<u32>().ok()).unwrap_or(0);
+        let shards = manifest_item(&json, "shards").ok_or(invalid("shards"))?;
+        let mut shardsize = 0;
+        for shard in shards.split('}').filter(|s| s.contains("\"path\"")) {
+            let fname = manifest_item(shard, "path").ok_or(invalid("path"))?;
+            let path = std::path::Path::new(dir).join(fname);
+            let positions = match format {
+                Format::Binary => {
+                    let buf = std::fs::read(&path)
+                        .map_err(|e| format!("{}: {e}", path.display()))?;
+                    Position::from_bytes(flds, &buf)
+                },
+                Format::Csv => {
+                    let txt = std::fs::read_to_string(&path)
+                        .map_err(|e| format!("{}: {e}", path.display()))?;
+                    txt.lines().skip(1).map(|l| Position::from_csv(flds, l))
+                        .collect::<Option<Vec<_>>>()
+                },
+            }.ok_or(format!("{}: invalid record.", path.display()))?;
+            shardsize = shardsize.max(positions.len());
+            for pos in positions {
+                ds.seen.insert(normalize(&pos.ban));
+                ds.positions.push(pos);
+            }
+        }
+        if ds.positions.len() != number("positions")? {
+            return Err(invalid("positions"));
+        }
+        Ok((ds, format, fields_to_str(flds), shardsize))
+    }
+
+    /// add positions in kifu files in `dir`.
+    pub fn read_dir(&mut self, dir : &str) -> Result<(), String> {
+        for fname in listkifu(dir)?.iter() {
+            let path = std::path::Path::new(dir).join(fname);
+            let path = path.to_str().unwrap();
+            let kifu = readkifu(path)?;
+            self.add_kifu(&kifu).map_err(|e| format!("{path}: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// write positions into shards and a manifest.
+    ///
+    /// # Arguments
+    /// - `outdir` : directory for shards and manifest.json.
+    /// - `format` : shard format.
+    /// - `fields` : COMMA separated optional fields. see read_fields().
+    /// - `shardsize` : max # of positions in a shard.
+    ///
+    /// # Returns
+    /// list of shard file names or error message.
+    pub fn export(&self, outdir : &str, format : Format, fields : &str,
+            shardsize : usize) -> Result<Vec<String>, String> {
+        let flds = read_fields(fields)?;
+        std::fs::create_dir_all(outdir).map_err(|e| format!("{outdir}: {e}"))?;
+
+        let mut shards = Vec::new();
+        for (i, chunk) in self.positions.chunks(shardsize.max(1)).enumerate() {
+            let fname = format!("shard{i:05}.{format}");
+            let content = match format {
+                Format::Binary => {
+                    let mut buf = Vec::with_capacity(chunk.len() * record_size(flds));
+                    for pos in chunk.iter() {
+                        pos.to_bytes(flds, &mut buf);
+                    }
+                    buf
+                },
+                Format::Csv => {
+                    let mut lines = vec![csv_header(flds)];
+                    lines.extend(chunk.iter().map(|pos| pos.to_csv(flds)));
+                    (lines.join("\n") + "\n").into_bytes()
+                },
+            };
+            let path = std::path::Path::new(outdir).join(&fname);
+            std::fs::write(&path, content)
+                .map_err(|e| format!("{}: {e}", path.display()))?;
+            shards.push((fname, chunk.len()));
+        }
+
+        let path = std::path::Path::new(outdir).join("manifest.json");
+        std::fs::write(&path, self.manifest(format, flds, &shards))
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(shards.into_iter().map(|(fname, _n)| fname).collect())
+    }
+
+    fn manifest(&self, format : Format, fields : u32,
+            shards : &[(String, usize)]) -> String {
+        let fldnames = [
+            (FIELD_FIXEDSTONES, "\"fixedblack\",\"fixedwhite\""),
+            (FIELD_MOVENO, "\"moveno\""),
+            (FIELD_STAGE, "\"stage\""),
+            (FIELD_TARGET, "\"target\""),
+        ].iter().filter(|(bit, _)| fields & bit != 0)
+            .map(|(_, name)| *name).collect::<Vec<_>>();
+        let mut fldnames = fldnames.join(",");
+        if !fldnames.is_empty() {fldnames.insert(0, ',');}
+        let board = match format {
+            Format::Binary => "\"black\",\"white\",\"teban\"",
+            Format::Csv => "\"rfen\"",
+        };
+        let shardlist = shards.iter().map(|(fname, n)| {
+            format!("{{\"path\":\"{fname}\",\"positions\":{n}}}")
+        }).collect::<Vec<_>>().join(",");
+        format!("{{\"format\":\"{format}\",\"version\":1,\
+            \"fields\":[{board},\"score\"{fldnames}],\
+            \"record_size\":{},\"augment\":{},\"kifus\":{},\
+            \"positions\":{},\"duplicates\":{},{}\"shards\":[{shardlist}]}}\n",
+            if format == Format::Binary {record_size(fields)} else {0},
+            self.augment, self.nkifus, self.positions.len(), self.duplicates,
+            if self.exact > 0 {format!("\"exact\":{},", self.exact)} else {String::new()})
+    }
+}
+
+/// solve positions w/ `maxempties` or less empty cells and label them.
+/// empty cells at the end are counted for the winner whichever the oracle is.
+/// (see endgame::final_score())
+/// solved positions are cached in `dir`/solved.txt.
+fn label_exact(ds : &mut Dataset, dir : &str, oracle : endgame::Oracle,
+        maxempties : u32) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("{dir}: {e}"))?;
+    let path = std::path::Path::new(dir).join(endgame::CACHEFILE);
+    let mut lb = endgame::Labeller::new(oracle, maxempties);
+    let cached = lb.open_cache(path.to_str().unwrap())?;
+    let n = ds.label(&mut lb)?;
+    println!("{n} positions labelled. {} solved, {} hits in {cached} cached positions.",
+        lb.solved, lb.hits);
+    Ok(())
+}
+
+/// export positions in kifus as a dataset.
+///
+/// # Arguments
+/// - `ds` : dataset to add positions to.
+/// - `kifudir` : directory including kifu files.
+/// - `outdir` : directory for shards and manifest.json.
+/// - `format` : shard format.
+/// - `fields` : COMMA separated optional fields.
+/// - `shardsize` : max # of positions in a shard.
+/// - `exact` : solver and max # of empty cells to label positions exactly.
+pub fn run(ds : &mut Dataset, kifudir : &str, outdir : &str, format : Format,
+        fields : &str, shardsize : usize, exact : Option<(endgame::Oracle, u32)>)
+        -> Result<(), String> {
+    ds.read_dir(kifudir)?;
+    if let Some((oracle, maxempties)) = exact {
+        label_exact(ds, outdir, oracle, maxempties)?;
+    }
+    let shards = ds.export(outdir, format, fields, shardsize)?;
+    println!("{} kifus, {} positions, {} duplicates, {} shards.",
+        ds.nkifus, ds.positions.len(), ds.duplicates, shards.len());
+    Ok(())
+}
+
+/// label positions in a dataset exactly and write them back.
+/// an interrupted job is resumed w/ `dir`/solved.txt.
+///
+/// # Arguments
+/// - `dir` : directory including shards and manifest.json.
+/// - `oracle` : solver.
+/// - `maxempties` : positions w/ more empty cells are left as they are.
+pub fn relabel(dir : &str, oracle : endgame::Oracle, maxempties : u32)
+        -> Result<(), String> {
+    let (mut ds, format, fields, shardsize) = Dataset::load(dir)?;
+    label_exact(&mut ds, dir, oracle, maxempties)?;
+    let shards = ds.export(dir, format, &fields, shardsize)?;
+    println!("{} positions, {} shards.", ds.positions.len(), shards.len());
+    Ok(())
+}
+
+#[test]
+fn test_dataset() {
+    let ban0 = bitboard::BitBoard::new();
+    let moves = ban0.genmove().unwrap();
+    let ban1 = ban0.r#move(moves[0]).unwrap();
+    let mut kifu = kifu::Kifu::new();
+    kifu.append(moves[0], ban0.teban, ban0.to_string());
+    kifu.append(0, ban1.teban, ban1.to_string());
+    kifu.winneris(4);
+    // 2局目の2手目までは1局目と対称な局面
+    let ban2 = ban0.r#move(moves[1]).unwrap();
+    let mv = ban2.genmove().unwrap()[0];
+    let ban3 = ban2.r#move(mv).unwrap();
+    let mut kifu2 = kifu::Kifu::new();
+    kifu2.append(moves[1], ban0.teban, ban0.to_string());
+    kifu2.append(mv, ban2.teban, ban2.to_string());
+    kifu2.append(0, ban3.teban, ban3.to_string());
+    kifu2.winneris(-2);
+
+    // 重複を除かなければ全部残る
+    let mut ds = Dataset::new(false);
+    ds.add_kifu(&kifu).unwrap();
+    ds.add_kifu(&kifu2).unwrap();
+    assert_eq!(ds.positions.len(), 5);
+    assert_eq!(ds.duplicates, 0);
+
+    let mut ds = Dataset::new(false);
+    ds.set_dedup(true);
+    ds.add_kifu(&kifu).unwrap();
+    ds.add_kifu(&kifu2).unwrap();
+    assert_eq!(ds.nkifus, 2);
+    assert_eq!(ds.positions.len(), 3);
+    assert_eq!(ds.duplicates, 2);
+    assert_eq!(ds.positions[1].moveno, 2);
+    assert_eq!(ds.positions[2].moveno, 3);
+    assert_eq!(ds.positions[2].score, -2);
+    assert!(ds.add_kifu(&kifu::Kifu::new()).is_err());
+
+    let mut aug = Dataset::new(true);
+    aug.add_kifu(&kifu).unwrap();
+    // 対称な局面は1つにまとめる
+    // 初期局面は180度回転と対角線で対称なので4通り。
+    // 1手目の後は対称性がないので16通り。
+    assert_eq!(aug.positions.len(), 4 + 16);
+    assert!(aug.positions.iter().all(|p| {
+        let te = &kifu.list[p.moveno as usize - 1];
+        p.score == if p.ban.teban == te.teban {4} else {-4}
+    }));
+
+    let dir = std::env::temp_dir().join(
+        format!("ruversi_test_dataset_{}", std::process::id()));
+    let outdir = dir.to_str().unwrap();
+    let shards = aug.export(outdir, Format::Binary, "fixedstones,stage", 8).unwrap();
+    assert_eq!(shards, ["shard00000.bin", "shard00001.bin", "shard00002.bin"]);
+    let bin = std::fs::read(dir.join("shard00000.bin")).unwrap();
+    assert_eq!(bin.len(), 8 * record_size(FIELD_FIXEDSTONES | FIELD_STAGE));
+    assert_eq!(bin[..8], aug.positions[0].ban.black.to_le_bytes());
+    assert_eq!(bin[8..16], aug.positions[0].ban.white.to_le_bytes());
+    assert_eq!(bin[16..21], [1, 4, 0, 0, 0]);
+    let bin = std::fs::read(dir.join("shard00002.bin")).unwrap();
+    assert_eq!(bin.len(), 4 * 21);
+    let manifest = std::fs::read_to_string(dir.join("manifest.json")).unwrap();
+    assert_eq!(manifest, "{\"format\":\"bin\",\"version\":1,\
+        \"fields\":[\"black\",\"white\",\"teban\",\"score\",\"fixedblack\",\"fixedwhite\",\"stage\"],\
+        \"record_size\":21,\"augment\":true,\"kifus\":1,\"positions\":20,\"duplicates\":0,\
+        \"shards\":[{\"path\":\"shard00000.bin\",\"positions\":8},\
+        {\"path\":\"shard00001.bin\",\"positions\":8},\
+        {\"path\":\"shard00002.bin\",\"positions\":4}]}\n");
+
+    let shards = ds.export(outdir, Format::Csv, "moveno", 100).unwrap();
+    assert_eq!(shards, ["shard00000.csv"]);
+    let csv = std::fs::read_to_string(dir.join("shard00000.csv")).unwrap();
+    assert_eq!(csv, format!("rfen,score,moveno\n{ban0},4,1\n{ban1},4,2\n{ban3},-2,3\n"));
+    assert!(ds.export(outdir, Format::Csv, "moveno,rating", 100).is_err());
+
+    let mut kifu3 = kifu::Kifu::new();
+    kifu3.append_val(moves[0], ban0.teban, ban0.to_string(), Some(1.0));
+    kifu3.append_val(0, ban1.teban, ban1.to_string(), Some(-3.0));
+    kifu3.winneris(4);
+    let mut aug = Dataset::new(true);
+    aug.set_target(Target::Search);
+    aug.add_kifu(&kifu3).unwrap();
+    assert!(aug.positions.iter().all(|p| {
+        let te = &kifu3.list[p.moveno as usize - 1];
+        let val = te.val.unwrap();
+        p.target == if p.ban.teban == te.teban {val} else {-val}
+    }));
+    aug.positions.truncate(1);
+    aug.export(outdir, Format::Binary, "target", 8).unwrap();
+    let bin = std::fs::read(dir.join("shard00000.bin")).unwrap();
+    assert_eq!(bin.len(), record_size(FIELD_TARGET));
+    assert_eq!(bin[18..], 1f32.to_le_bytes());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_readkifu() {
+    let ban0 = bitboard::BitBoard::new();
+    let mv = ban0.genmove().unwrap()[0];
+    let ban1 = ban0.r#move(mv).unwrap();
+    let mut kifu = kifu::Kifu::new();
+    kifu.append(mv, ban0.teban, ban0.to_string());
+    kifu.append(ban1.genmove().unwrap()[0], ban1.teban, ban1.to_string());
+    kifu.winneris(2);
+    let path = std::env::temp_dir().join(
+        format!("ruversi_test_readkifu_{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    kifu.save(path).unwrap();
+    assert_eq!(readkifu(path).unwrap().score, Some(2));
+
+    // 終わっていない棋譜や壊れた行は引き分け扱いで読まない
+    let txt = std::fs::read_to_string(path).unwrap();
+    std::fs::write(path, txt.replace("SENTE won. 2", "on going...")).unwrap();
+    let ongoing = readkifu(path).unwrap();
+    assert_eq!(ongoing.score, None);
+    assert!(Dataset::new(false).add_kifu(&ongoing).is_err());
+    let lines = txt.lines().filter(|l| !l.contains("won.")).collect::<Vec<_>>();
+    std::fs::write(path, lines.join("\n")).unwrap();
+    assert!(readkifu(path).is_err());
+    std::fs::write(path, txt.replace(" b", " z")).unwrap();
+    assert!(readkifu(path).is_err());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_target() {
+    assert_eq!(Target::from("result"), Some(Target::Result));
+    assert_eq!(Target::from("Search"), Some(Target::Search));
+    assert_eq!(Target::from("blend"), Some(Target::Blend(0.5)));
+    assert_eq!(Target::from("blend:0.25"), Some(Target::Blend(0.25)));
+    assert_eq!(Target::from("td"), Some(Target::Td(0.7)));
+    assert_eq!(Target::from("td:1"), Some(Target::Td(1.0)));
+    assert_eq!(Target::from("td:1.5"), None);
+    assert_eq!(Target::from("blend:x"), None);
+    assert_eq!(Target::from("result:0.5"), None);
+    assert_eq!(Target::from("mcts"), None);
+    assert_eq!(Target::Blend(0.25).to_string(), "blend:0.25");
+    assert_eq!(Target::from(&Target::Td(0.5).to_string()), Some(Target::Td(0.5)));
+
+    let mut kifu = kifu::Kifu::new();
+    kifu.append_val(19, bitboard::SENTE, "a".to_string(), Some(2.0));
+    kifu.append_val(18, bitboard::GOTE, "b".to_string(), Some(6.0));
+    kifu.append(17, bitboard::SENTE, "c".to_string());
+    assert_eq!(Target::Result.targets(&kifu), None);
+    kifu.winneris(10);
+    assert_eq!(Target::Result.targets(&kifu).unwrap(), [10.0, 10.0, 10.0]);
+    assert_eq!(Target::Search.targets(&kifu).unwrap(), [2.0, 6.0, 10.0]);
+    assert_eq!(Target::Blend(0.25).targets(&kifu).unwrap(), [8.0, 9.0, 10.0]);
+    // λ=1は最終結果、λ=0は次の局面の探索値
+    assert_eq!(Target::Td(1.0).targets(&kifu).unwrap(), [10.0, 10.0, 10.0]);
+    assert_eq!(Target::Td(0.0).targets(&kifu).unwrap(), [6.0, 10.0, 10.0]);
+    // G2 = 10, G1 = 0.5 * 10 + 0.5 * 10, G0 = 0.5 * 6 + 0.5 * 10
+    assert_eq!(Target::Td(0.5).targets(&kifu).unwrap(), [8.0, 10.0, 10.0]);
+}
+
+#[test]
+fn test_relabel() {
+    // 終盤の局面だけを読み切る
+    let mut ban = bitboard::BitBoard::new();
+    let mut kifu = kifu::Kifu::new();
+    while !ban.is_full() && !ban.is_passpass() {
+        let mv = ban.genmove().unwrap()[0];
+        kifu.append(mv, ban.teban, ban.to_string());
+        ban = ban.r#move(mv).unwrap();
+    }
+    kifu.winneris(ban.count());
+
+    let dir = std::env::temp_dir().join(
+        format!("ruversi_test_relabel_{}", std::process::id()));
+    let outdir = dir.to_str().unwrap();
+    for (format, fields) in [(Format::Binary, "moveno,target"), (Format::Csv, "stage")] {
+        let mut ds = Dataset::new(false);
+        ds.add_kifu(&kifu).unwrap();
+        ds.export(outdir, format, fields, 16).unwrap();
+        let (loaded, fmt, flds, shardsize) = Dataset::load(outdir).unwrap();
+        assert_eq!((fmt, flds.as_str(), shardsize), (format, fields, 16));
+        assert_eq!(loaded.positions.len(), ds.positions.len());
+        assert_eq!(loaded.nkifus, 1);
+        for (a, b) in loaded.positions.iter().zip(ds.positions.iter()) {
+            assert!(a.ban == b.ban);
+            assert_eq!((a.score, a.target, a.fixedstones), (b.score, b.target, b.fixedstones));
+        }
+
+        relabel(outdir, endgame::Oracle::Internal, 6).unwrap();
+        let (labelled, ..) = Dataset::load(outdir).unwrap();
+        assert_eq!(labelled.exact, 6);
+        for (a, b) in labelled.positions.iter().zip(ds.positions.iter()) {
+            if a.ban.nblank() <= 6 {
+                assert_eq!(a.score, endgame::solve(&a.ban));
+                assert_eq!(a.target, a.score as f32);
+            } else {
+                assert_eq!(a.score, b.score);
+            }
+        }
+        // 2回目は全部キャッシュから
+        relabel(outdir, endgame::Oracle::Internal, 6).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    let path = dir.join("manifest.json");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(&path, "{\"format\":\"txt\"}").unwrap();
+    assert!(Dataset::load(outdir).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
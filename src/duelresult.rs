@@ -129,6 +129,54 @@ impl DuelResult {
 
         (r, err_margin * 1.96)
     }
+
+    /// log likelihood ratio of elo1 against elo0.
+    /// normal approximation of the trinomial(win, draw, lose) model.
+    ///
+    /// # Arguments
+    /// - elo0 : elo difference for H0.
+    /// - elo1 : elo difference for H1.
+    pub fn llr(&self, elo0 : f64, elo1 : f64) -> f64 {
+        if self.total == 0 {return 0.0;}
+
+        let n = self.total as f64;
+        let twin = (self.win[SENTE] + self.win[GOTE]) as f64;
+        let tdraw = (self.draw[SENTE] + self.draw[GOTE]) as f64;
+        let tlose = (self.lose[SENTE] + self.lose[GOTE]) as f64;
+        let score = (twin + tdraw * 0.5) / n;
+        let var = (twin * (1.0 - score).powi(2) + tdraw * (0.5 - score).powi(2)
+            + tlose * score.powi(2)) / n;
+        // 全勝や全敗でも判定できるように
+        let var = var.max(1e-3);
+        let expected = |elo : f64| 1.0 / (1.0 + 10f64.powf(-elo / 400.0));
+        let s0 = expected(elo0);
+        let s1 = expected(elo1);
+        n * (s1 - s0) * (2.0 * score - s0 - s1) / (2.0 * var)
+    }
+
+    /// sequential probability ratio test.
+    ///
+    /// # Arguments
+    /// - elo0 : elo difference for H0.
+    /// - elo1 : elo difference for H1.
+    /// - alpha : false positive rate.
+    /// - beta : false negative rate.
+    ///
+    /// # Returns
+    /// - Some(true) : H1 was accepted.
+    /// - Some(false) : H0 was accepted.
+    /// - None : more games are needed.
+    pub fn sprt(&self, elo0 : f64, elo1 : f64, alpha : f64, beta : f64)
+            -> Option<bool> {
+        let llr = self.llr(elo0, elo1);
+        if llr >= ((1.0 - beta) / alpha).ln() {
+            Some(true)
+        } else if llr <= (beta / (1.0 - alpha)).ln() {
+            Some(false)
+        } else {
+            None
+        }
+    }
 }
 
 
@@ -231,3 +279,33 @@ ev1   ,win,draw,lose
 ev1 @@,2,4,3
 ev1 [],3,16,7");
 }
+
+#[test]
+fn test_sprt() {
+    let dr = DuelResult::new();
+    assert_eq!(dr.llr(0.0, 10.0), 0.0);
+    assert_eq!(dr.sprt(0.0, 10.0, 0.05, 0.05), None);
+
+    let mut dr = DuelResult::new();
+    dr.win = [70, 70];
+    dr.draw = [10, 10];
+    dr.lose = [20, 20];
+    dr.total = 200;
+    assert!(dr.llr(0.0, 10.0) > 2.945);
+    assert_eq!(dr.sprt(0.0, 10.0, 0.05, 0.05), Some(true));
+    let op = dr.opponent();
+    assert!((op.llr(0.0, 10.0) + dr.llr(-10.0, 0.0)).abs() < 1e-9);
+    assert_eq!(op.sprt(0.0, 10.0, 0.05, 0.05), Some(false));
+
+    let mut dr = DuelResult::new();
+    dr.win = [3, 2];
+    dr.lose = [2, 3];
+    dr.total = 10;
+    assert_eq!(dr.sprt(0.0, 10.0, 0.05, 0.05), None);
+
+    // 全勝
+    let mut dr = DuelResult::new();
+    dr.win = [10, 10];
+    dr.total = 20;
+    assert_eq!(dr.sprt(0.0, 10.0, 0.05, 0.05), Some(true));
+}
@@ -0,0 +1,282 @@
+use super::*;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+
+/// file name of solved positions in a dataset.
+pub const CACHEFILE : &str = "solved.txt";
+const CACHE_HEADER : &str =
+    "# ruversi solved positions. black white teban score(empties to the winner)\n";
+
+/// # Returns
+/// final score of a finished game from black's view.
+/// empty cells are counted for the winner like edax and WTHOR.
+pub fn final_score(ban : &bitboard::BitBoard) -> i8 {
+    let score = ban.count();
+    let empties = ban.nblank() as i8;
+    match score {
+        0 => 0,
+        1.. => score + empties,
+        _ => score - empties,
+    }
+}
+
+/// 手番側から見た最終的な石の差を完全読みで求める。
+fn negamax(ban : &mut bitboard::BitBoard, mut alpha : i8, beta : i8) -> i8 {
+    let moves = ban.legal_moves();
+    if moves == 0 {
+        let oppo = ban.r#move(bitboard::PASS).unwrap();
+        if ban.is_full() || oppo.legal_moves() == 0 {
+            return final_score(ban) * ban.teban;
+        }
+
+        let undo = ban.do_move(bitboard::PASS).unwrap();
+        let val = -negamax(ban, -beta, -alpha);
+        ban.undo_move(&undo);
+        return val;
+    }
+
+    // 確定石で結果の範囲が窓の外なら読まない
+    let (lower, upper) = ban.stable_bounds();
+    if upper <= alpha {return upper;}
+    if lower >= beta {return lower;}
+
+    let mut order = bitboard::MoveIter::new(moves).collect::<Vec<u8>>();
+    if ban.nblank() > 6 {
+        // 相手の打てる手が少なくなる手から読む
+        order.sort_by_cached_key(|&mv| {
+            ban.r#move(mv).unwrap().legal_moves().count_ones()
+        });
+    }
+    let mut best = -(bitboard::CELL_2D as i8) - 1;
+    for mv in order {
+        let undo = ban.do_move(mv).unwrap();
+        let val = -negamax(ban, -beta, -alpha);
+        ban.undo_move(&undo);
+        if val > best {
+            best = val;
+            if val > alpha {
+                alpha = val;
+                if alpha >= beta {break;}
+            }
+        }
+    }
+    best
+}
+
+/// solve a position exactly.
+///
+/// # Returns
+/// final score from black's view w/ perfect play. see final_score().
+pub fn solve(ban : &bitboard::BitBoard) -> i8 {
+    let mut ban = ban.clone();
+    let n = bitboard::CELL_2D as i8;
+    negamax(&mut ban, -n - 1, n + 1) * ban.teban
+}
+
+/// solver for labelling.
+pub enum Oracle {
+    /// solve() in this program.
+    Internal,
+    /// edax as an external program.
+    Edax(edaxrunner::EdaxRunner),
+}
+
+impl Oracle {
+    /// # Returns
+    /// final score from black's view w/ perfect play or error message.
+    /// both oracles count empty cells for the winner.
+    fn solve(&self, ban : &bitboard::BitBoard) -> Result<i8, String> {
+        match self {
+            Oracle::Internal => Ok(solve(ban)),
+            Oracle::Edax(er) => {
+                // edaxの評価値は手番側から見た値
+                let (_xy, score) = er.run(&ban.to_obf())?;
+                let score = score.parse::<i8>()
+                    .map_err(|e| format!("invalid score \"{score}\" from edax. ({e})"))?;
+                Ok(score * ban.teban)
+            },
+        }
+    }
+}
+
+/// labels positions w/ exact scores.
+/// solved positions are cached by the symmetry normalized board and
+/// appended to a file so that an interrupted job can be resumed.
+pub struct Labeller {
+    oracle : Oracle,
+    maxempties : u32,
+    cache : HashMap<(u64, u64, i8), i8>,
+    cachefile : Option<std::fs::File>,
+    pub solved : usize,
+    pub hits : usize,
+}
+
+impl Labeller {
+    /// # Arguments
+    /// - `oracle` : solver.
+    /// - `maxempties` : positions w/ more empty cells are not solved.
+    pub fn new(oracle : Oracle, maxempties : u32) -> Labeller {
+        Labeller {
+            oracle,
+            maxempties,
+            cache : HashMap::new(),
+            cachefile : None,
+            solved : 0,
+            hits : 0,
+        }
+    }
+
+    pub fn maxempties(&self) -> u32 {
+        self.maxempties
+    }
+
+    /// read solved positions in `path` and append new ones to it.
+    ///
+    /// # Returns
+    /// # of positions read or error message.
+    pub fn open_cache(&mut self, path : &str) -> Result<usize, String> {
+        let exists = std::path::Path::new(path).exists();
+        if exists {
+            let file = std::fs::File::open(path).map_err(|e| format!("{path}: {e}"))?;
+            for (i, line) in BufReader::new(file).lines().enumerate() {
+                let line = line.map_err(|e| format!("{path}: {e}"))?;
+                // 数え方の違う古いキャッシュは混ぜない
+                if i == 0 && line != CACHE_HEADER.trim_end() {
+                    return Err(format!("{path}: unknown header \"{line}\". please remove it."));
+                }
+                if line.starts_with('#') || line.is_empty() {continue;}
+
+                let (key, score) = parse_cache(&line)
+                    .ok_or(format!("{path}:{}: invalid line \"{line}\".", i + 1))?;
+                self.cache.insert(key, score);
+            }
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true)
+            .open(path).map_err(|e| format!("{path}: {e}"))?;
+        if !exists {
+            file.write_all(CACHE_HEADER.as_bytes())
+                .map_err(|e| format!("{path}: {e}"))?;
+        }
+        self.cachefile = Some(file);
+        Ok(self.cache.len())
+    }
+
+    /// # Returns
+    /// - Ok(Some(score)) : exact score from black's view.
+    /// - Ok(None) : too many empty cells.
+    /// - Err(msg) : failed to solve.
+    pub fn label(&mut self, ban : &bitboard::BitBoard) -> Result<Option<i8>, String> {
+        if ban.nblank() > self.maxempties {return Ok(None);}
+
+        let (key, sign) = dataset::normalize_sign(ban);
+        if let Some(score) = self.cache.get(&key) {
+            self.hits += 1;
+            return Ok(Some(score * sign));
+        }
+
+        let score = self.oracle.solve(ban)?;
+        // 代表局面から見た値で覚える
+        self.cache.insert(key, score * sign);
+        self.solved += 1;
+        if let Some(file) = self.cachefile.as_mut() {
+            writeln!(file, "{:016x} {:016x} {} {}", key.0, key.1, key.2, score * sign)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(Some(score))
+    }
+}
+
+/// "black white teban score"
+fn parse_cache(line : &str) -> Option<((u64, u64, i8), i8)> {
+    let elem = line.split_whitespace().collect::<Vec<_>>();
+    if elem.len() != 4 {return None;}
+
+    let black = u64::from_str_radix(elem[0], 16).ok()?;
+    let white = u64::from_str_radix(elem[1], 16).ok()?;
+    let teban = elem[2].parse::<i8>().ok()?;
+    let score = elem[3].parse::<i8>().ok()?;
+    Some(((black, white, teban), score))
+}
+
+/// 枝刈りなしで全部読む。黒から見た値。
+#[cfg(test)]
+fn minimax(ban : &bitboard::BitBoard) -> i8 {
+    let moves = match ban.genmove() {
+        None => {return final_score(ban);},
+        Some(mvs) => mvs,
+    };
+    if moves[0] == bitboard::PASS {
+        let passed = ban.r#move(bitboard::PASS).unwrap();
+        if passed.genmove().is_none_or(|mvs| mvs[0] == bitboard::PASS) {
+            return final_score(ban);
+        }
+        return minimax(&passed);
+    }
+    let vals = moves.iter().map(|&mv| minimax(&ban.r#move(mv).unwrap()));
+    if ban.teban == bitboard::SENTE {vals.max()} else {vals.min()}.unwrap()
+}
+
+#[test]
+fn test_solve() {
+    let mut bans = vec![bitboard::BitBoard::from(
+        "aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w").unwrap()];
+    // 決まった手順で終盤まで進める
+    for seed in 0..4 {
+        let mut ban = bitboard::BitBoard::new();
+        let mut i = seed;
+        while ban.nblank() > 8 && !ban.is_passpass() {
+            let moves = ban.genmove().unwrap();
+            ban = ban.r#move(moves[i % moves.len()]).unwrap();
+            i = (i * 7 + 3) % 101;
+        }
+        bans.push(ban);
+    }
+    // 空きマスは勝った方に数える
+    let ban = bitboard::BitBoard::from("H/H/8/8/8/8/8/8 b").unwrap();
+    assert_eq!(solve(&ban), 64);
+    assert_eq!(solve(&ban.flip_all()), -64);
+    assert_eq!(final_score(&bitboard::BitBoard::from("D4/d4/8/8/8/8/8/8 b").unwrap()), 0);
+    for ban in bans.iter() {
+        assert_eq!(solve(ban), minimax(ban), "{ban}");
+        // 色反転すると符号が変わる
+        assert_eq!(solve(&ban.flip_all()), -solve(ban), "{ban}");
+        // 確定石から求めた範囲に入る
+        let (lower, upper) = ban.stable_bounds();
+        let score = solve(ban) * ban.teban;
+        assert!(lower <= score && score <= upper, "{ban}");
+    }
+}
+
+#[test]
+fn test_labeller() {
+    let ban = bitboard::BitBoard::from(
+        "aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w").unwrap();
+    let score = solve(&ban);
+
+    let path = std::env::temp_dir().join(
+        format!("ruversi_test_labeller_{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut lb = Labeller::new(Oracle::Internal, 10);
+    assert_eq!(lb.open_cache(path).unwrap(), 0);
+    assert_eq!(lb.label(&bitboard::BitBoard::new()).unwrap(), None);
+    assert_eq!(lb.label(&ban).unwrap(), Some(score));
+    // 対称な局面は解き直さない
+    assert_eq!(lb.label(&ban.rotate90()).unwrap(), Some(score));
+    assert_eq!(lb.label(&ban.flip_all()).unwrap(), Some(-score));
+    assert_eq!((lb.solved, lb.hits), (1, 2));
+    drop(lb);
+
+    // 途中から再開
+    let mut lb = Labeller::new(Oracle::Internal, 10);
+    assert_eq!(lb.open_cache(path).unwrap(), 1);
+    assert_eq!(lb.label(&ban.flip_horz().flip_all()).unwrap(), Some(-score));
+    assert_eq!((lb.solved, lb.hits), (0, 1));
+    let content = std::fs::read_to_string(path).unwrap();
+    assert!(content.starts_with(CACHE_HEADER));
+    assert_eq!(content.lines().count(), 2);
+    assert!(parse_cache("0 0 1").is_none());
+    // 数え方の違う古いキャッシュ
+    std::fs::write(path, "# ruversi solved positions. black white teban score\n").unwrap();
+    assert!(Labeller::new(Oracle::Internal, 10).open_cache(path).is_err());
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,188 @@
+use super::*;
+
+/// read positions w/ ground-truth scores.
+///
+/// # Arguments
+/// - `path` : a dataset directory written by --export-dataset or
+///   a text file w/ "<rfen> <score from black's view>" in every line.
+///
+/// # Returns
+/// list of (board, score from black's view) or error message.
+/// the target of each position is the score in a dataset.
+pub fn load(path : &str) -> Result<Vec<(bitboard::BitBoard, f32)>, String> {
+    if std::path::Path::new(path).is_dir() {
+        let (ds, ..) = dataset::Dataset::load(path)?;
+        // --exactや--targetで付けた値で比べる
+        return Ok(ds.positions.into_iter().map(|p| (p.ban, p.target)).collect());
+    }
+
+    let txt = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let mut positions = Vec::new();
+    for (i, line) in txt.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('#') || line.is_empty() {continue;}
+
+        let invalid = || format!("{path}:{}: invalid line \"{line}\".", i + 1);
+        let (rfen, score) = line.rsplit_once(' ').ok_or_else(invalid)?;
+        let ban = bitboard::BitBoard::from(rfen)
+            .map_err(|e| format!("{path}:{}: {e}", i + 1))?;
+        let score = score.parse::<f32>().map_err(|_| invalid())?;
+        positions.push((ban, score));
+    }
+    Ok(positions)
+}
+
+/// evaluate positions w/ threads.
+///
+/// # Arguments
+/// - `weight` : eval table.
+/// - `positions` : list of (board, score from black's view).
+/// - `depth` : search depth. 0 for static evaluation.
+/// - `cachesz` : size of transposition tables for every thread.
+/// - `nthreads` : # of threads.
+pub fn check(weight : &weight::Weight, positions : &[(bitboard::BitBoard, f32)],
+        depth : u8, cachesz : usize, nthreads : usize) -> trainer::Metrics {
+    let evaluate = |chunk : &[(bitboard::BitBoard, f32)]| {
+        let mut report = trainer::Metrics::default();
+        let mut tt = if depth > 0 {
+            Some(transptable::TranspositionTable::with_capacity(cachesz))
+        } else {
+            None
+        };
+        for (ban, truth) in chunk.iter() {
+            let val = tt.as_mut().and_then(|tt| {
+                let mut node = nodebb::NodeBB::root(depth);
+                nodebb::NodeBB::think_ab_simple_gk_tt(ban, depth, &mut node, weight, tt)
+            }).unwrap_or_else(|| {
+                // 探索できない局面は静的評価で
+                if ban.is_full() {
+                    ban.count() as f32
+                } else {
+                    weight.forward(ban, ban.progress()).0
+                }
+            });
+            report.add(ban.progress(), ban.nblank(), val, *truth);
+        }
+        report
+    };
+    if positions.is_empty() {return trainer::Metrics::default();}
+
+    let chunk = positions.len().div_ceil(nthreads.max(1));
+    thread::scope(|s| {
+        let handles = positions.chunks(chunk).map(|c| {
+            s.spawn(move || evaluate(c))
+        }).collect::<Vec<_>>();
+        handles.into_iter().fold(trainer::Metrics::default(), |mut sum, h| {
+            sum.merge(&h.join().unwrap());
+            sum
+        })
+    })
+}
+
+/// put reports side by side.
+///
+/// # Arguments
+/// - `reports` : reports of eval tables on the same positions.
+pub fn table(reports : &[trainer::Metrics]) -> String {
+    let mut txt = format!("{:<8}{:>8}", "range", "n");
+    for i in 0..reports.len() {
+        txt += &format!(" |{:>9}{:>7}{:>7}", format!("mse{}", i + 1),
+            format!("mae{}", i + 1), format!("sign{}", i + 1));
+    }
+    txt += "\n";
+    let rows = reports.iter().map(|r| r.rows()).collect::<Vec<_>>();
+    for (i, (name, e)) in rows[0].iter().enumerate() {
+        if e.n == 0 {continue;}
+
+        txt += &format!("{name:<8}{:>8}", e.n);
+        for row in rows.iter() {
+            let (mse, mae, sign) = row[i].1.get();
+            txt += &format!(" |{mse:>9.2}{mae:>7.2}{:>6.1}%", sign * 100.0);
+        }
+        txt += "\n";
+    }
+    txt
+}
+
+/// compare eval tables on positions w/ ground-truth scores.
+///
+/// # Arguments
+/// - `path` : positions. see load().
+/// - `tables` : paths of eval tables.
+/// - `depth` : search depth. 0 for static evaluation.
+/// - `cachesz` : size of transposition tables.
+pub fn run(path : &str, tables : &[&str], depth : u8, cachesz : usize)
+        -> Result<(), String> {
+    let positions = load(path)?;
+    if positions.is_empty() {
+        return Err(format!("no positions in {path}."));
+    }
+    println!("{} positions in {path}, {}.", positions.len(),
+        if depth == 0 {String::from("static evaluation")} else {format!("depth {depth}")});
+
+    let nthreads = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut reports = Vec::new();
+    for (i, table) in tables.iter().enumerate() {
+        let mut weight = weight::Weight::new();
+        weight.read(table)?;
+        println!("table{}: {table}", i + 1);
+        let st = Instant::now();
+        reports.push(check(&weight, &positions, depth, cachesz, nthreads));
+        println!("  {}msec", st.elapsed().as_millis());
+    }
+    print!("{}", table(&reports));
+    Ok(())
+}
+
+#[test]
+fn test_evalcheck() {
+    let path = std::env::temp_dir().join(
+        format!("ruversi_test_evalcheck_{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::write(path, "# rfen score\n\
+        8/8/8/3Aa3/3aA3/8/8/8 b 0\n\
+        8/8/3A4/3Ba2/3aA3/8/8/8 w 4\n\
+        \n\
+        aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w -2.5\n").unwrap();
+    let positions = load(path).unwrap();
+    assert_eq!(positions.len(), 3);
+    assert_eq!(positions[2].1, -2.5);
+
+    let mut w = weight::Weight::new();
+    w.init();
+    let report = check(&w, &positions, 0, 1024, 2);
+    assert_eq!(report.errors(None).n, 3);
+    // 60, 59, 0 empty cells
+    let rows = report.rows();
+    let n = |name : &str| rows.iter().find(|(nm, _)| nm == name).unwrap().1.n;
+    assert_eq!((n("e60"), n("e50-59"), n("e0-9")), (1, 1, 1));
+    // 1スレッドでも同じ
+    assert_eq!(check(&w, &positions, 0, 1024, 1), report);
+    // 浅い探索
+    let report2 = check(&w, &positions, 1, 1024, 1);
+    assert_eq!(report2.errors(None).n, 3);
+    let txt = table(&[report, report2]);
+    assert!(txt.starts_with("range"));
+    assert!(txt.contains("mse2"));
+    assert!(txt.lines().any(|l| l.starts_with("e0-9")));
+    assert!(txt.lines().any(|l| l.starts_with("e60 ")));
+    // 局面のない行は出さない
+    assert!(!txt.lines().any(|l| l.starts_with("e30-39")));
+
+    std::fs::write(path, "8/8/8/3Aa3/3aA3/8/8/8 b x\n").unwrap();
+    assert!(load(path).err().unwrap().ends_with(":1: invalid line \"8/8/8/3Aa3/3aA3/8/8/8 b x\"."));
+    std::fs::remove_file(path).unwrap();
+
+    // データセットは結果ではなく教師信号と比べる
+    let dir = std::env::temp_dir().join(
+        format!("ruversi_test_evalcheck_{}", std::process::id()));
+    let mut ds = dataset::Dataset::new(false);
+    ds.positions.push(dataset::Position {
+        ban : positions[1].0.clone(), score : 4, fixedstones : (0, 0), moveno : 2, target : 1.5});
+    ds.export(dir.to_str().unwrap(), dataset::Format::Csv, "target", 10).unwrap();
+    let loaded = load(dir.to_str().unwrap()).unwrap();
+    assert_eq!(loaded.len(), 1);
+    assert!(loaded[0].0 == positions[1].0);
+    assert_eq!(loaded[0].1, 1.5);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,15 +1,32 @@
 use super::*;
 
-use std::io::{self, Write};
 // use std::sync::{Arc, RwLock};
 
-type SearchFn = fn(&bitboard::BitBoard, u8, &mut nodebb::NodeBB, &weight::Weight, &mut transptable::TranspositionTable) -> Option<f32>;
+pub type SearchFn = fn(&bitboard::BitBoard, u8, &mut nodebb::NodeBB, &weight::Weight, &mut transptable::TranspositionTable) -> Option<f32>;
+/// root search value, searched tree and thinking time.
+pub type Search = (f32, nodebb::NodeBB, std::time::Duration);
+
+/// what a player does on its turn. see player::Player::act().
+pub enum Action {
+    /// cell index or PASS, search result by this program if any.
+    Move(u8, Option<Search>),
+    /// take back moves until the last move of the side to move.
+    Undo,
+    /// restart from a kifu. see GameBB::from_kifu().
+    Load(kifu::Kifu),
+    /// swap players.
+    Flip,
+    /// start over from the first position of the game.
+    Restart,
+    /// give up the game.
+    Resign,
+}
 
 pub struct GameBB {
     ban : bitboard::BitBoard,
     pub kifu : kifu::Kifu,
-    cachesize : usize,
-    verbose : myoption::Verbose
+    verbose : myoption::Verbose,
+    recordval : bool,
 }
 
 impl GameBB {
@@ -17,8 +34,8 @@ impl GameBB {
         GameBB {
             ban : bitboard::BitBoard::new(),
             kifu : kifu::Kifu::new(),
-            cachesize : 100,
             verbose : myoption::Verbose::Normal,
+            recordval : false,
         }
     }
 
@@ -26,762 +43,175 @@ impl GameBB {
         GameBB {
             ban: bitboard::BitBoard::from(rfen).unwrap(),
             kifu: kifu::Kifu::new(),
-            cachesize : 100,
             verbose : myoption::Verbose::Normal,
+            recordval : false,
         }
     }
 
-    pub fn set_cachesize(&mut self, cachesz : usize) {
-        self.cachesize = cachesz;
-    }
-
-    pub fn set_verbose(&mut self, vb : &myoption::Verbose) {
-        self.verbose = vb.clone();
+    /// continue a game in `kifu`.
+    /// moves in `kifu` are kept and the result is cleared.
+    ///
+    /// # Returns
+    /// a game or error message for an invalid or finished game.
+    pub fn from_kifu(kifu : kifu::Kifu) -> Result<GameBB, String> {
+        let (ban, kifu) = GameBB::resume(kifu)?;
+        Ok(GameBB {
+            ban,
+            kifu,
+            verbose : myoption::Verbose::Normal,
+            recordval : false,
+        })
     }
 
-    pub fn is_verbose(&self) -> bool {self.verbose == myoption::Verbose::Full}
-    pub fn not_silent(&self) -> bool {self.verbose != myoption::Verbose::Silent}
-
-    #[allow(dead_code)]
-    pub fn start(&mut self, f : SearchFn, depth : u8)
-            -> Result<(), String> {
-        let mut tt = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let wei = unsafe{nodebb::WEIGHT.as_ref().unwrap()};
-        loop {
-            // show
-            // self.ban.put();
-            if self.is_verbose() {println!("{}", self.ban);}
-            // think
-            let st = Instant::now();
-            let mut node = nodebb::NodeBB::root(depth);
-            let val = f(&self.ban, depth, &mut node, wei, &mut tt).unwrap();
-            // let (val, node) = node::Node::think(&self.ban, 7).unwrap();
-            // let (val, node) = node::Node::think_ab(&self.ban, 7).unwrap();
-            let ft = st.elapsed();
-            if self.not_silent() {
-                println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-            }
-            let best = node.best.unwrap();
-            let xy = best.xypos();
-            // apply move
-            let ban = self.ban.r#move(xy).unwrap();
-            let rfen = self.ban.to_string();
-            let teban = self.ban.teban;
-            self.ban = ban;
-
-            // save to kifu
-            self.kifu.append(xy, teban, rfen);
-
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
-            }
-
-            tt.next();
+    /// # Returns
+    /// (board to be played, kifu w/o the result) or error message.
+    fn resume(mut kifu : kifu::Kifu) -> Result<(bitboard::BitBoard, kifu::Kifu), String> {
+        kifu.validate().map_err(|(_, kind, msg)| format!("{kind} error. {msg}"))?;
+        let last = kifu.list.last().ok_or_else(|| String::from("no moves in the kifu."))?;
+        let ban = bitboard::BitBoard::from(&last.rfen)?;
+        if last.xy() == bitboard::PASS && ban.is_full() {
+            return Err(String::from("the game is over."));
         }
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
-        Ok(())
-    }
-
-    #[allow(dead_code)]
-    pub fn starto(&mut self, f : SearchFn, depth : u8) -> Result<(), String> {
-        let mut tt = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let wei = unsafe{nodebb::WEIGHT.as_ref().unwrap()};
-        loop {
-            // show
-            // self.ban.put();
-            if self.is_verbose() {println!("{}", self.ban);}
-            // think
-            let st = Instant::now();
-            let mut node = nodebb::NodeBB::root(depth);
-            let val = f(&self.ban, depth, &mut node, wei, &mut tt).unwrap();
-
-            let ft = st.elapsed();
-            if self.not_silent() {
-                println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-            }
-            let best = node.best.as_ref().unwrap();
-            let xy = best.xypos();
-            // apply move
-            let ban = self.ban.r#move(xy).unwrap();
-            let rfen = self.ban.to_string();
-            let teban = self.ban.teban;
-            self.ban = ban;
-
-            // save to kifu
-            self.kifu.append(xy, teban, rfen);
-
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
-            }
-
-            tt.next();
+        let ban = ban.r#move(last.xy())?;
+        // RFENにはパスの回数が無いので両者の合法手を見る
+        if ban.is_full()
+                || ban.legal_moves() == 0 && ban.r#move(bitboard::PASS)?.legal_moves() == 0 {
+            return Err(String::from("the game is over."));
         }
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
-        Ok(())
+        kifu.score = None;
+        Ok((ban, kifu))
     }
 
-    pub fn startgk(&mut self, f : SearchFn, depth : u8) -> Result<(), String> {
-        let mut tt = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let wei = unsafe{nodebb::WEIGHT.as_ref().unwrap()};
-        loop {
-            let mut node = nodebb::NodeBB::root(depth);
-            // show
-            // self.ban.put();
-            // println!("{}", self.ban);
-            // think
-            // let st = Instant::now();
-            let _val = f(&self.ban, depth, &mut node, wei, &mut tt).unwrap();
-
-            // let ft = st.elapsed();
-            // println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-            let best = node.best.as_ref().unwrap();
-            let xy = best.xypos();
-            // apply move
-            let ban = self.ban.r#move(xy).unwrap();
-            let rfen = self.ban.to_string();
-            let teban = self.ban.teban;
-            self.ban = ban;
-
-            // save to kifu
-            self.kifu.append(xy, teban, rfen);
-
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
-            }
-
-            tt.next();
-        }
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
+    /// take back moves until the last move of the side to move.
+    fn undo(&mut self) -> Result<(), String> {
+        let teban = self.ban.teban;
+        let idx = self.kifu.list.iter()
+            .rposition(|te| te.teban == teban && te.xy() != bitboard::PASS)
+            .ok_or_else(|| String::from("nothing to undo."))?;
+        self.ban = bitboard::BitBoard::from(&self.kifu.list[idx].rfen)?;
+        self.kifu.list.truncate(idx);
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn start_against_stdin(
-            &mut self, f : SearchFn, depth : u8, turnin : i8)
-                -> Result<(), String> {
-        let mut tt = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let wei = unsafe{nodebb::WEIGHT.as_ref().unwrap()};
-        loop {
-            let xy;
-            if self.ban.teban == turnin {
-                // show
-                if self.is_verbose() {self.ban.put();}
-                let movable = self.ban.genmove().unwrap();
-                if movable.is_empty() {
-                    if self.not_silent() {println!("auto pass.");}
-                    xy = bitboard::PASS;
-                } else if movable.len() == 1 {
-                    xy = if movable[0] == bitboard::PASS {  // pass
-                        bitboard::PASS
-                    } else {
-                        movable[0]
-                    };
-                } else {
-                    loop {
-                        if self.is_verbose() {print!("your turn[a1 ~ h8]:");}
-                        io::stdout().flush().unwrap();
-                        let mut txt = String::new();
-                        io::stdin().read_line(&mut txt).unwrap();
-                        txt.pop();
-                        let xx = "0abcdefgh".find(txt.chars().nth(0).unwrap()).unwrap_or(10) as u8;
-                        let yy = txt.chars().nth(1).unwrap().to_digit(10);
-                        if yy.is_none() {
-                            if self.is_verbose() {
-                                println!("invalid position : {txt}");
-                            }
-                            continue;
-                        }
-                        let yy = yy.unwrap() as u8;
-                        if xx > 8 || yy > 8 {
-                            if self.is_verbose() {
-                                println!("invalid position : {txt}");
-                            }
-                            continue;
-                        }
-                        let pos = bitboard::cell(xx, yy);
-                        if movable.contains(&pos) {
-                            xy = pos;
-                            break;
-                        }
-                        if self.is_verbose() {
-                            println!("{txt} is not allowed.");
-                        }
-                    }
-                }
-            } else {
-                if self.is_verbose() {println!("{}", self.ban);}
-                // think
-                let st = Instant::now();
-                let mut node = nodebb::NodeBB::root(depth);
-                let val = f(&self.ban, depth, &mut node, wei, &mut tt).unwrap();
-                // let (val, node) = node::Node::think(&self.ban, 7).unwrap();
-                // let (val, node) = node::Node::think_ab(&self.ban, 7).unwrap();
-                let ft = st.elapsed();
-                if self.not_silent() {
-                    println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-                }
-                let best = node.best.unwrap();
-                xy = best.xypos();
-            }
-            // apply move
-            let ban = self.ban.r#move(xy).unwrap();
-            let rfen = self.ban.to_string();
-            let teban = self.ban.teban;
-            self.ban = ban;
-
-            // save to kifu
-            self.kifu.append(xy, teban, rfen);
-
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
-            }
-
-            tt.next();
+    /// go back to the first position of the game.
+    pub fn restart(&mut self) -> Result<(), String> {
+        if let Some(te) = self.kifu.list.first() {
+            self.ban = bitboard::BitBoard::from(&te.rfen)?;
+            self.kifu.list.clear();
         }
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
+        self.kifu.score = None;
+        self.kifu.info.retain(|(k, _)| k != "resign");
         Ok(())
     }
 
-    pub fn starto_against_stdin(&mut self,
-            f : SearchFn, depth : u8, turnin : i8) -> Result<(), String> {
-        let mut tt = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let wei = unsafe{nodebb::WEIGHT.as_ref().unwrap()};
-        loop {
-            let xy;
-            if self.ban.teban == turnin {
-                // show
-                if self.is_verbose() {self.ban.put();}
-                let movable = self.ban.genmove().unwrap();
-                if movable.is_empty() {
-                    if self.not_silent() {println!("auto pass.");}
-                    xy = bitboard::PASS;
-                } else if movable.len() == 1 {
-                    xy = movable[0];
-                } else {
-                    loop {
-                        if self.is_verbose() {print!("your turn[a1 ~ h8]:");}
-                        io::stdout().flush().unwrap();
-                        let mut txt = String::new();
-                        io::stdin().read_line(&mut txt).unwrap();
-                        txt.pop();
-                        let xx = "0abcdefgh".find(txt.chars().nth(0).unwrap()).unwrap_or(10) as u8;
-                        let yy = txt.chars().nth(1).unwrap().to_digit(10);
-                        if yy.is_none() {
-                            if self.is_verbose() {
-                                println!("invalid position : {txt}");
-                            }
-                            continue;
-                        }
-                        let yy = yy.unwrap() as u8;
-                        if xx > 8 || yy > 8 {
-                            if self.is_verbose() {
-                                println!("invalid position : {txt}");
-                            }
-                            continue;
-                        }
-                        let pos = bitboard::cell(xx, yy);
-                        if movable.contains(&pos) {
-                            xy = pos;
-                            break;
-                        }
-                        if self.is_verbose() {println!("{txt} is not allowed.");}
-                    }
-                }
-            } else {
-                if self.is_verbose() {println!("{}", self.ban);}
-                // think
-                let st = Instant::now();
-                let mut node = nodebb::NodeBB::root(depth);
-                let val = f(&self.ban, depth, &mut node, wei, &mut tt).unwrap();
-                // let (val, node) = node::Node::think(&self.ban, 7).unwrap();
-                // let (val, node) = node::Node::think_ab(&self.ban, 7).unwrap();
-                let ft = st.elapsed();
-                if self.not_silent() {
-                    println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-                }
-                let best = node.best.as_ref().unwrap();
-                xy = best.xypos();
-            }
-            // apply move
-            let ban = self.ban.r#move(xy).unwrap();
-            let rfen = self.ban.to_string();
-            let teban = self.ban.teban;
-            self.ban = ban;
-
-            // save to kifu
-            self.kifu.append(xy, teban, rfen);
-
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
-            }
-
-            tt.next();
-        }
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
-        Ok(())
+    /// the current board.
+    pub fn board(&self) -> &bitboard::BitBoard {
+        &self.ban
     }
 
-    #[allow(dead_code)]
-    pub fn start_against_edax(&mut self,
-            f : SearchFn, depth : u8, turnin : i8) -> Result<(), String> {
-        let er = edaxrunner::EdaxRunner::new();
-        let mut tt = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let wei = unsafe{nodebb::WEIGHT.as_ref().unwrap()};
-        loop {
-            let xy;
-            if self.ban.teban == turnin {
-                // show
-                if self.is_verbose() {println!("{}", self.ban);}
-                // self.ban.put();
-                let movable = self.ban.genmove().unwrap();
-                if movable.is_empty() {
-                    if self.not_silent() {println!("auto pass.");}
-                    xy = bitboard::PASS;
-                } else if movable.len() == 1 {
-                    xy = movable[0];
-                } else {
-                    // launch edax
-                    match er.run(&self.ban.to_obf()) {
-                        Ok((pos, _)) => {
-                            let xx = "0abcdefgh".find(pos.chars().nth(0).unwrap()).unwrap_or(10) as u8;
-                            let yy = pos.chars().nth(1).unwrap().to_digit(10).unwrap() as u8;
-                            xy = bitboard::cell(xx, yy);
-                        },
-                        Err(msg) => panic!("error running edax... [{msg}]"),
-                    }
-                }
-           } else {
-                println!("{}", self.ban);
-                // think
-                let st = Instant::now();
-                let mut node = nodebb::NodeBB::root(depth);
-                let val = f(&self.ban, depth, &mut node, wei, &mut tt).unwrap();
-                let ft = st.elapsed();
-                if self.not_silent() {
-                    println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-                }
-                let best = node.best.unwrap();
-                xy = best.xypos();
-            }
-            // apply move
-            let ban = self.ban.r#move(xy).unwrap();
-            let rfen = self.ban.to_string();
-            let teban = self.ban.teban;
-            self.ban = ban;
-
-            // save to kifu
-            self.kifu.append(xy, teban, rfen);
-
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
-            }
-
-            tt.next();
-        }
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
-        Ok(())
+    pub fn set_verbose(&mut self, vb : &myoption::Verbose) {
+        self.verbose = vb.clone();
     }
 
-    /// play a game against Edax.  
-    /// # Arguments  
-    /// - f : fn for searching.  
-    /// - depth : searching depth.  
-    /// - turnin : Edax's turn.  
-    /// # Returns  
-    /// () or Error message.
-    pub fn starto_against_edax(&mut self,
-            f : SearchFn, depth : u8, turnin : i8, econf : &std::path::PathBuf)
-                -> Result<(), String> {
-        let er = edaxrunner::EdaxRunner::from_config(econf)?;
-        let wei = unsafe{nodebb::WEIGHT.as_ref().unwrap()};
-        let mut tt = transptable::TranspositionTable::with_capacity(self.cachesize);
-        loop {
-            // show
-            if self.is_verbose() {println!("{}", self.ban);}
-            let xy;
-            if self.ban.teban == turnin {
-                // self.ban.put();
-                let movable = self.ban.genmove().unwrap();
-                if movable.is_empty() {
-                    if self.not_silent() {println!("auto pass.");}
-                    xy = bitboard::PASS;
-                } else if movable.len() == 1 {
-                    xy = movable[0];
-                } else {
-                    // launch edax
-                    match er.run(&self.ban.to_obf()) {
-                        Ok((pos, _)) => {
-                            let xx = "0abcdefgh".find(pos.chars().nth(0).unwrap()).unwrap_or(10) as u8;
-                            let yy = pos.chars().nth(1).unwrap().to_digit(10).unwrap() as u8;
-                            xy = bitboard::cell(xx, yy);
-                        },
-                        Err(msg) => panic!("error running edax... [{msg}]"),
-                    }
-                }
-            } else {
-                // think
-                let st = Instant::now();
-                let mut node = nodebb::NodeBB::root(depth);
-                let val = f(&self.ban, depth, &mut node, wei, &mut tt).unwrap();
-                let ft = st.elapsed();
-                if self.not_silent() {
-                    println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-                }
-                let best = node.best.as_ref().unwrap();
-                xy = best.xypos();
-            }
-            // apply move
-            let ban = match self.ban.r#move(xy) {
-                Err(msg) => {
-                    self.ban.put();
-                    println!("rfen:{}", self.ban);
-                    panic!("{msg} @ {xy}");
-                },
-                Ok(ban) => {ban},
-            };
-            let rfen = self.ban.to_string();
-            let teban = self.ban.teban;
-            self.ban = ban;
-
-            // save to kifu
-            self.kifu.append(xy, teban, rfen);
-
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
-            }
+    /// record root search values and PVs in the kifu.
+    pub fn set_recordval(&mut self, rec : bool) {
+        self.recordval = rec;
+    }
 
-            tt.next();
+    /// record a move in the kifu.
+    /// # of nodes and time are recorded for moves searched by this program,
+    /// the root search value and PV also w/ set_recordval().
+    fn record(&mut self, xy : u8, teban : i8, rfen : String, search : Option<&Search>) {
+        self.kifu.append(xy, teban, rfen);
+        let Some((val, node, ft)) = search else {return;};
+
+        let te = self.kifu.list.last_mut().unwrap();
+        te.nodes = Some(node.kyokumen);
+        te.time = Some(ft.as_secs_f32());
+        if self.recordval {
+            te.val = Some(*val);
+            te.pv = Some(node.best_order());
         }
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
-        Ok(())
     }
 
-    /// play a game against another Ruversi.  
-    /// # Arguments  
-    /// - f : fn for searching.  
-    /// - depth : searching depth.  
-    /// - turnin : another Ruversi's turn.  
-    /// # Returns  
-    /// () or Error message.
-    pub fn starto_against_ruversi(&mut self,
-        f : SearchFn, depth : u8, turnin : i8, econf : &str)
+    /// play a game and show moves and the result.
+    ///
+    /// # Arguments
+    /// - `sente`, `gote` : players.
+    ///
+    /// # Returns
+    /// () or error message.
+    pub fn start(&mut self, sente : &mut dyn player::Player, gote : &mut dyn player::Player)
             -> Result<(), String> {
-        let mut tt = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let wei = unsafe{nodebb::WEIGHT.as_ref().unwrap()};
-        let mut rr = edaxrunner::RuversiRunner::from_config(
-                &std::path::PathBuf::from(econf))?;
-        rr.set_verbose(self.is_verbose());
-        loop {
-            // show
-            if self.is_verbose() {println!("{}", self.ban);}
-            let xy;
-            if self.ban.teban == turnin {
-                // self.ban.put();
-                let movable = self.ban.genmove().unwrap();
-                if movable.is_empty() {
-                    if self.not_silent() {println!("auto pass.");}
-                    xy = bitboard::PASS;
-                } else if movable.len() == 1 {
-                    xy = if movable[0] == bitboard::PASS {
-                        if self.not_silent() {println!("auto pass.");}
-                        bitboard::PASS
-                    } else {
-                        movable[0]
-                    };
-                } else {
-                    // launch another Ruversi
-                    match rr.run(&self.ban.to_string()) {
-                        Ok((pos, _)) => {
-                            let xx = "0abcdefgh".find(
-                                    pos.chars().nth(0).unwrap()
-                                ).unwrap_or(10) as u8;
-                            let yy = pos.chars().nth(1).unwrap()
-                                    .to_digit(10).unwrap() as u8;
-                            xy = bitboard::cell(xx, yy);
-                        },
-                        Err(msg) => panic!("error running ruversi... [{msg}]"),
-                    }
-                }
-            } else {
-                // think
-                let st = Instant::now();
-                let mut node = nodebb::NodeBB::root(depth);
-                let val = f(&self.ban, depth, &mut node, wei, &mut tt).unwrap();
-                let ft = st.elapsed();
-                if self.not_silent() {
-                    println!("  val:{val:+5.1} {node} {}msec", ft.as_millis());
-                }
-                let best = node.best.as_ref().unwrap();
-                xy = best.xypos();
-            }
-            // apply move
-            let ban = self.ban.r#move(xy).unwrap();
-            let rfen = self.ban.to_string();
-            let teban = self.ban.teban;
-            self.ban = ban;
-
-            // save to kifu
-            self.kifu.append(xy, teban, rfen);
-
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
-            }
-
-            tt.next();
-        }
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
-        Ok(())
+        let verbose = self.verbose;
+        self.start_with(&mut [sente, gote], &mut |ev| show(&verbose, ev))
     }
 
-    /// play a game against Edax via othello engine protocol.  
-    /// # Arguments  
-    /// - f : fn for searching.  
-    /// - depth : searching depth.  
-    /// - turnin : Edax's turn.  
-    /// # Returns  
-    /// () or Error message.
-    pub fn start_against_via_cassio(&mut self,
-            f : SearchFn, depth : u8, turnin : i8, cconf : &str)
-                -> Result<(), String> {
-        let mut tt = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let wei = unsafe{nodebb::WEIGHT.as_ref().unwrap()};
-        let er = edaxrunner::CassioRunner::from_config(
-                &std::path::PathBuf::from(cconf))?;
-        let mut cassio =
-            cassio::OthelloEngineProtocolServer::new1(er.run().unwrap());
-        cassio.setturn(bitboard::SENTE);
-        cassio.init().unwrap();
-        if self.is_verbose() {
-            println!("opponent:{}", cassio.get_version()?);
+    /// play a game.
+    ///
+    /// # Arguments
+    /// - `players` : [sente, gote] or [a player for both sides].
+    ///   see Action for what players can do other than moves.
+    /// - `listener` : called w/ every event in the game.
+    ///
+    /// # Returns
+    /// () or error message.
+    pub fn start_with(&mut self, players : &mut [&mut dyn player::Player],
+            listener : &mut dyn FnMut(&Event)) -> Result<(), String> {
+        for p in players.iter_mut() {
+            p.new_game(&self.ban)?;
         }
-        cassio.new_position()?;
-
         loop {
-            // show
-            if self.is_verbose() {println!("{}", self.ban);}
-            let xy;
-            if self.ban.teban == turnin {
-                // self.ban.put();
-                let movable = self.ban.genmove().unwrap();
-                if movable.is_empty() {
-                    if self.not_silent() {println!("auto pass.");}
-                    xy = bitboard::PASS;
-                } else if movable.len() == 1 {
-                    xy = movable[0];
-                } else {
-                    // launch edax
-                    let alpha = -64f32;
-                    let beta = 64f32;
-                    // let depth = depth;
-                    let precision = 50;
-                    match cassio.midgame_search(&self.ban.to_obf(), alpha, beta, depth, precision) {
-                        Ok(res) => {
-                            if self.is_verbose() {println!("{res}");}
-                            let elem = res.split(',').collect::<Vec<_>>();
-                            if elem.len() < 2 {panic!("lack of response.. {res}");}
-
-                            let mv = elem[1].split_whitespace().collect::<Vec<_>>();
-                            if mv.len() < 2 {panic!("lack of response.. {res}");}
-
-                            match mv[1] {
-                            "Pa" => {
-                                xy = bitboard::PASS;
-                            },
-                            "--" => {
-                                xy = bitboard::NONE as u8;
-                            },
-                            _ => {
-                                let xx = mv[1].chars().nth(0).unwrap()
-                                    .to_ascii_uppercase() as u8 - b'A' + 1;
-                                let yy = mv[1].chars().nth(1).unwrap() as u8 - b'0';
-                                xy = bitboard::cell(xx, yy);
-                            },
-                            }
-                        },
-                        Err(ermsg) => {
-                            panic!("error occured: {ermsg}!!");
-                        },
-                    }
-                }
+            listener(&Event::Turn(&self.ban));
+            let idx = if self.ban.teban == bitboard::SENTE {0} else {players.len() - 1};
+            let player = &mut players[idx];
+            let (xy, search) = match player.act(&self.ban, &self.kifu)? {
+                Action::Move(xy, search) => (xy, search),
+                Action::Undo => {
+                    self.undo()?;
+                    continue;
+                },
+                Action::Load(kifu) => {
+                    (self.ban, self.kifu) = GameBB::resume(kifu)?;
+                    continue;
+                },
+                Action::Flip => {
+                    players.swap(0, players.len() - 1);
+                    continue;
+                },
+                Action::Restart => {
+                    self.restart()?;
+                    continue;
+                },
+                Action::Resign => {
+                    let teban = self.ban.teban;
+                    self.kifu.set_info("resign",
+                        if teban == bitboard::SENTE {"black"} else {"white"});
+                    self.kifu.winneris(-64 * teban);
+                    break;
+                },
+            };
+            let legal = self.ban.legal_moves();
+            let ok = if xy == bitboard::PASS {
+                legal == 0
             } else {
-                // think
-                let st = Instant::now();
-                let mut node = nodebb::NodeBB::root(depth);
-                let val = f(&self.ban, depth, &mut node, wei, &mut tt).unwrap();
-                let ft = st.elapsed();
-                if self.not_silent() {
-                    println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-                }
-                let best = node.best.as_ref().unwrap();
-                xy = best.xypos();
-            }
-
-            if xy != bitboard::NONE as u8 {
-                // apply move
-                let ban = self.ban.r#move(xy).unwrap();
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.ban = ban;
-
-                // save to kifu
-                self.kifu.append(xy, teban, rfen);
-            }
-
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
+                (xy as usize) < bitboard::CELL_2D && legal & (bitboard::LSB_CELL << xy) != 0
+            };
+            if !ok {
+                let te = kifu::Te::new(xy, self.ban.teban, String::new());
+                return Err(format!("illegal move {} by {} on {}.",
+                    te.pos(), player.name(), self.ban));
             }
+            listener(&Event::Move(xy, search.as_ref()));
 
-            tt.next();
-        }
-        cassio.quit().unwrap();
-
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
-        Ok(())
-    }
-
-    #[allow(dead_code)]
-    pub fn start_with_2et(&mut self,
-        f : SearchFn, depth : u8, et1 : &weight::Weight, et2 : &weight::Weight)
-            -> Result<(), String> {
-        let mut tt1 = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let mut tt2 = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let wei = unsafe{nodebb::WEIGHT.as_ref().unwrap()};
-        loop {
-            // show
-            // self.ban.put();
-            if self.is_verbose() {println!("{}", self.ban);}
-            // switch weight
-            let tt;
-            if self.ban.teban == bitboard::SENTE {
-                tt = &mut tt1;
-                unsafe {
-                    nodebb::WEIGHT.as_mut().unwrap().copy(et1);
-                }
-            } else {
-                tt = &mut tt2;
-                unsafe {
-                    nodebb::WEIGHT.as_mut().unwrap().copy(et2);
-                }
-            }
-            // think
-            let st = Instant::now();
-            let mut node = nodebb::NodeBB::root(depth);
-            let val = f(&self.ban, depth, &mut node, wei, tt).unwrap();
-            // let (val, node) = node::Node::think(&self.ban, 7).unwrap();
-            // let (val, node) = node::Node::think_ab(&self.ban, 7).unwrap();
-            let ft = st.elapsed();
-            if self.not_silent() {
-                println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-            }
-            let best = node.best.unwrap();
-            let xy = best.xypos();
             // apply move
-            let ban = self.ban.r#move(xy).unwrap();
+            let ban = self.ban.r#move(xy)?;
             let rfen = self.ban.to_string();
             let teban = self.ban.teban;
             self.ban = ban;
 
             // save to kifu
-            self.kifu.append(xy, teban, rfen);
+            self.record(xy, teban, rfen, search.as_ref());
 
             // check finished
             if self.ban.is_passpass() {
@@ -793,205 +223,163 @@ impl GameBB {
                 self.kifu.append(bitboard::PASS, teban, rfen);
                 break;
             }
-
-            tt.next();
+        }
+        for p in players.iter_mut() {
+            p.end_game()?;
         }
         // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
+        if self.kifu.score.is_none() {
+            self.kifu.winneris(self.ban.count());
+        }
+        listener(&Event::End(&self.kifu, &self.ban));
         Ok(())
     }
+}
 
-    /// # Arguments
-    /// - et1 : SENTE
-    /// - et2 : GOTE
-    pub fn starto_with_2et(&mut self,
-        f : SearchFn, depth : u8, et1 : &weight::Weight, et2 : &weight::Weight)
-            -> Result<(), String> {
-        let mut tt1 = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let mut tt2 = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let wei = unsafe{nodebb::WEIGHT.as_ref().unwrap()};
-        loop {
-            // show
-            // self.ban.put();
-            if self.is_verbose() {println!("{}", self.ban);}
-            // switch weight and tt
-            let tt;
-            if self.ban.teban == bitboard::SENTE {
-                tt = &mut tt1;
-                unsafe {
-                    nodebb::WEIGHT.as_mut().unwrap().copy(et1);
-                }
-            } else {
-                tt = &mut tt2;
-                unsafe {
-                    nodebb::WEIGHT.as_mut().unwrap().copy(et2);
-                }
-            }
-            // think
-            let st = Instant::now();
-            let mut node = nodebb::NodeBB::root(depth);
-            let val = f(&self.ban, depth, &mut node, wei, tt).unwrap();
-            // let (val, node) = node::Node::think(&self.ban, 7).unwrap();
-            // let (val, node) = node::Node::think_ab(&self.ban, 7).unwrap();
-            let ft = st.elapsed();
-            if self.not_silent() {
-                println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-            }
-            let best = node.best.as_ref().unwrap();
-            let xy = best.xypos();
-            // apply move
-            let ban = self.ban.r#move(xy).unwrap();
-            let rfen = self.ban.to_string();
-            let teban = self.ban.teban;
-            self.ban = ban;
+/// what happens in a game. see GameBB::start_with().
+pub enum Event<'a> {
+    /// waiting for a move on the board.
+    Turn(&'a bitboard::BitBoard),
+    /// a move w/ the search result by this program if any.
+    Move(u8, Option<&'a Search>),
+    /// the game is over. the kifu and the final board.
+    End(&'a kifu::Kifu, &'a bitboard::BitBoard),
+}
 
-            // save to kifu
-            self.kifu.append(xy, teban, rfen);
+/// show an event on the console.
+pub fn show(verbose : &myoption::Verbose, ev : &Event) {
+    match ev {
+        Event::Turn(ban) => {
+            if verbose.is_verbose() {println!("{ban}");}
+        },
+        Event::Move(xy, search) => {
+            if verbose.is_silent() {return;}
 
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
-            }
+            if let Some((val, node, ft)) = search {
+                println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
+            } else if *xy == bitboard::PASS {
+                println!("auto pass.");
+            }
+        },
+        Event::End(kifu, ban) => {
+            if verbose.is_silent() {return;}
+
+            println!("{}", kifu.to_str());
+            let last = kifu.list.iter().rev().map(|te| te.xy())
+                .find(|xy| *xy != bitboard::PASS);
+            println!("{}", ban.render(last, 0));
+        },
+    }
+}
 
-            tt.next();
+#[test]
+fn test_start_with() {
+    /// plays the first legal move.
+    struct First;
+    impl player::Player for First {
+        fn name(&self) -> String {String::from("first")}
+        fn play(&mut self, ban : &bitboard::BitBoard) -> Result<(u8, Option<Search>), String> {
+            Ok((bitboard::MoveIter::new(ban.legal_moves()).next().unwrap_or(bitboard::PASS), None))
         }
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
-        Ok(())
     }
-
-    /// # Arguments
-    /// - et1 : SENTE
-    /// - et2 : GOTE
-    #[allow(dead_code)]
-    pub fn starto_with_2et_mt(&mut self,
-        f : SearchFn, depth : u8, et1 : &weight::Weight, et2 : &weight::Weight)
-            -> Result<(), String> {
-        let mut tt1 = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let mut tt2 = transptable::TranspositionTable::with_capacity(self.cachesize);
-        loop {
-            // show
-            // self.ban.put();
-            if self.is_verbose() {println!("{}", self.ban);}
-            // switch weight
-            let mut node = nodebb::NodeBB::root(depth);
-            let tt;
-            let wei = if self.ban.teban == bitboard::SENTE {
-                    tt = &mut tt1;
-                    et1
-                } else {
-                    tt = &mut tt2;
-                    et2
-                };
-            // think
-            let st = Instant::now();
-            let val = f(&self.ban, depth, &mut node, wei, tt).unwrap();
-            let ft = st.elapsed();
-            if self.not_silent() {
-                println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-            }
-            let best = node.best.as_ref().unwrap();
-            let xy = best.xypos();
-            // apply move
-            let ban = self.ban.r#move(xy).unwrap();
-            let rfen = self.ban.to_string();
-            let teban = self.ban.teban;
-            self.ban = ban;
-
-            // save to kifu
-            self.kifu.append(xy, teban, rfen);
-
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
-            }
-
-            tt.next();
+    /// plays an illegal move.
+    struct Cheat;
+    impl player::Player for Cheat {
+        fn name(&self) -> String {String::from("cheat")}
+        fn play(&mut self, _ban : &bitboard::BitBoard) -> Result<(u8, Option<Search>), String> {
+            Ok((0, None))
         }
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
-        Ok(())
     }
 
-    /// # Arguments
-    /// - et1 : SENTE
-    /// - et2 : GOTE
-    pub fn starto_with_2et_mt_tt(&mut self,
-        f : SearchFn, depth : u8, et1 : &weight::Weight, et2 : &weight::Weight)
-            -> Result<(), String> {
-        let mut tt1 = transptable::TranspositionTable::with_capacity(self.cachesize);
-        let mut tt2 = transptable::TranspositionTable::with_capacity(self.cachesize);
-        loop {
-            // show
-            // self.ban.put();
-            if self.is_verbose() {println!("{}", self.ban);}
-            // switch weight
-            let mut node = nodebb::NodeBB::root(depth);
-            let tt;
-            let wei = if self.ban.teban == bitboard::SENTE {
-                    tt = &mut tt1;
-                    et1
-                } else {
-                    tt = &mut tt2;
-                    et2
-                };
-            // think
-            let st = Instant::now();
-            let val = f(&self.ban, depth, &mut node, wei, tt).unwrap();
-            let ft = st.elapsed();
-            if self.not_silent() {
-                println!("val:{val:+5.1} {node} {}msec", ft.as_millis());
-            }
-            let best = node.best.as_ref().unwrap();
-            let xy = best.xypos();
-            // apply move
-            let ban = self.ban.r#move(xy).unwrap();
-            let rfen = self.ban.to_string();
-            let teban = self.ban.teban;
-            self.ban = ban;
-
-            // save to kifu
-            self.kifu.append(xy, teban, rfen);
-
-            // check finished
-            if self.ban.is_passpass() {
-                break;
-            }
-            if self.ban.is_full() {
-                let rfen = self.ban.to_string();
-                let teban = self.ban.teban;
-                self.kifu.append(bitboard::PASS, teban, rfen);
-                break;
+    let rfen = "aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w";
+    let mut w = weight::Weight::new();
+    w.init();
+    let mut engine = player::Engine::new(nodebb::NodeBB::think_ab_simple_gk_tt, 2, &w, 1024);
+    let mut g = GameBB::from(rfen);
+    g.set_recordval(true);
+    let (mut turns, mut moves, mut ends) = (0, 0, 0);
+    g.start_with(&mut [&mut First, &mut engine], &mut |ev| match ev {
+        Event::Turn(_) => turns += 1,
+        Event::Move(..) => moves += 1,
+        Event::End(..) => ends += 1,
+    }).unwrap();
+    assert_eq!((turns, ends), (moves, 1));
+    let txt = format!("{}{}", kifu::HEADER, g.kifu.to_str());
+    let kifu = kifu::check(&txt).unwrap();
+    // 探索した手だけ値と時間がある
+    assert!(kifu.list.iter().all(|te| (te.teban == bitboard::GOTE) == te.nodes.is_some()
+        || te.xy() == bitboard::PASS));
+    assert!(kifu.list.iter().any(|te| te.val.is_some()));
+
+    // 1人で両方
+    let mut g = GameBB::from(rfen);
+    g.start_with(&mut [&mut First], &mut |_| {}).unwrap();
+    assert!(g.kifu.score.is_some());
+
+    let mut g = GameBB::from(rfen);
+    let err = g.start_with(&mut [&mut First, &mut Cheat], &mut |_| {}).unwrap_err();
+    assert!(err.starts_with("illegal move a1 by cheat on "), "{err}");
+
+    // 打ちかけから続ける
+    let kifu = kifu::Kifu::from_moves("f5d6c3").unwrap();
+    let mut g = GameBB::from_kifu(kifu.copy()).unwrap();
+    assert_eq!(g.ban.teban, bitboard::GOTE);
+    g.start_with(&mut [&mut First, &mut First], &mut |_| {}).unwrap();
+    assert!(g.kifu.list.len() > 3);
+    assert_eq!(g.kifu.list[..3].iter().map(|te| te.to_str(0)).collect::<Vec<_>>(),
+        kifu.list.iter().map(|te| te.to_str(0)).collect::<Vec<_>>());
+    let txt = format!("{}{}", kifu::HEADER, g.kifu.to_str());
+    assert!(kifu::check(&txt).unwrap().score.is_some());
+    // 終わった対局は続けられない
+    assert_eq!(GameBB::from_kifu(g.kifu.copy()).err().unwrap(), "the game is over.");
+    let mut over = g.kifu.copy();
+    over.score = None;
+    assert!(GameBB::from_kifu(over).is_err());
+    assert_eq!(GameBB::from_kifu(kifu::Kifu::new()).err().unwrap(), "no moves in the kifu.");
+    let mut broken = kifu.copy();
+    broken.list[1].teban = bitboard::SENTE;
+    assert_eq!(GameBB::from_kifu(broken).err().unwrap(),
+        "mismatch error. move 2: turn is wrong.");
+
+    /// does actions in order and then plays the first legal move.
+    struct Script(Vec<Action>, usize);
+    impl player::Player for Script {
+        fn name(&self) -> String {String::from("script")}
+        fn play(&mut self, ban : &bitboard::BitBoard) -> Result<(u8, Option<Search>), String> {
+            First.play(ban)
+        }
+        fn act(&mut self, ban : &bitboard::BitBoard, _kifu : &kifu::Kifu)
+                -> Result<Action, String> {
+            self.1 += 1;
+            if self.0.is_empty() {
+                return self.play(ban).map(|(xy, s)| Action::Move(xy, s));
             }
-
-            tt.next();
+            Ok(self.0.remove(0))
         }
-        // check who won
-        self.kifu.winneris(self.ban.count());
-        if self.not_silent() {println!("{}", self.kifu.to_str());}
-        // show
-        if self.not_silent() {self.ban.put();}
-        Ok(())
     }
+    // 打って戻して読み込んで投了
+    let mv = |k : &str| kifu::Kifu::from_moves(k).unwrap().list.last().unwrap().xy();
+    let mut script = Script(vec![Action::Move(mv("f5"), None), Action::Undo,
+        Action::Load(kifu.copy()), Action::Resign], 0);
+    let mut g = GameBB::new();
+    g.start_with(&mut [&mut script, &mut First], &mut |_| {}).unwrap();
+    // 読み込んだ3手と白の1手
+    assert_eq!(g.kifu.list.len(), 4);
+    assert_eq!(g.kifu.info("resign"), Some("black"));
+    assert_eq!(g.kifu.score, Some(-64));
+    assert_eq!(script.1, 4);
+
+    // 戻す手が無い, 入れ替え
+    let mut script = Script(vec![Action::Undo], 0);
+    let mut g = GameBB::new();
+    let err = g.start_with(&mut [&mut script, &mut First], &mut |_| {}).err().unwrap();
+    assert_eq!(err, "nothing to undo.");
+    let mut script = Script(vec![Action::Move(mv("f5"), None), Action::Restart,
+        Action::Flip, Action::Resign], 0);
+    let mut g = GameBB::new();
+    g.start_with(&mut [&mut script, &mut First], &mut |_| {}).unwrap();
+    // やり直して入れ替えた後は白番でscriptが投了
+    assert_eq!(g.kifu.list.len(), 1);
+    assert_eq!(g.kifu.info("resign"), Some("white"));
+    assert_eq!(g.kifu.score, Some(64));
 }
@@ -4,12 +4,115 @@ pub const SENTEWIN : i8 = 1;
 pub const DRAW : i8 = 0;
 pub const GOTEWIN : i8 = -1;
 pub const STR_POSX : &str = "0abcdefgh";
-pub const HEADER : &str = "# reversi kifu revision 1.0\n";
+/// revision 2 adds "# key: value" lines after this header and
+/// optional "key=value" fields after the search value of every move.
+/// revision 1 kifus are read in the same way.
+///
+/// ```text
+/// # reversi kifu revision 2.0
+/// # black: ruversi
+/// # depth: 7
+/// 1 @@ f4 8/8/8/3Aa3/3aA3/8/8/8 b +1.50 nodes=1234 time=0.052 pv=f4E3f2
+/// ```
+///
+/// keys of information written by this program:
+/// - black, white : players.
+/// - blackeval, whiteeval : eval tables of the players.
+/// - depth, think : search depth and algorithm.
+/// - date : when the game started in UTC.
+/// - initpos : initial position in duels. "<equal file>#<index>".
+/// - version : version of this program.
+/// - analysis, blackloss, whiteloss : settings and results of --analyze.
+/// - resign : black or white who resigned the game.
+/// - blacklevel, whitelevel : --level and its seed.
+/// - seed : --seed.
+///
+/// --analyze also adds "best=", "bestval=", "playval=" and "mistake=1"
+/// to every move.
+pub const HEADER : &str = "# reversi kifu revision 2.0\n";
+
+/// what is wrong w/ a kifu.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorKind {
+    /// too few elements, unknown turn or unknown line.
+    Syntax,
+    /// invalid cell like "z9".
+    Position,
+    /// invalid number in fields or the result.
+    Number,
+    /// invalid RFEN.
+    Rfen,
+    /// RFEN or turn differs from the replayed board.
+    Mismatch,
+    /// illegal move, missing pass or moves after the game.
+    Illegal,
+    /// result differs from the final board.
+    Score,
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let txt = match self {
+            ErrorKind::Syntax => "syntax",
+            ErrorKind::Position => "position",
+            ErrorKind::Number => "number",
+            ErrorKind::Rfen => "rfen",
+            ErrorKind::Mismatch => "mismatch",
+            ErrorKind::Illegal => "illegal",
+            ErrorKind::Score => "score",
+        };
+        write!(f, "{txt}")
+    }
+}
+
+/// error in a kifu w/ the line number from 1.
+#[derive(Debug, PartialEq)]
+pub struct KifuError {
+    pub line : usize,
+    pub kind : ErrorKind,
+    pub msg : String,
+}
+
+impl std::fmt::Display for KifuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} error. {}", self.line, self.kind, self.msg)
+    }
+}
 
 pub struct Te {
     xy : u8,
     pub teban : i8,
     pub rfen : String,
+    /// root search value from black's view.
+    pub val : Option<f32>,
+    /// thinking time in seconds.
+    pub time : Option<f32>,
+    /// # of searched nodes.
+    pub nodes : Option<usize>,
+    /// principal variation. ex. "f4E3f2"
+    pub pv : Option<String>,
+    /// best move found by --analyze.
+    pub best : Option<u8>,
+    /// values of the best and the played moves from black's view by --analyze.
+    pub bestval : Option<f32>,
+    pub playval : Option<f32>,
+    /// the played move loses more than the threshold of --analyze.
+    pub mistake : bool,
+}
+
+/// "a1" ~ "h8" or "PS" to a cell index or PASS.
+pub fn xy_from(pos : &str) -> Option<u8> {
+    if pos.eq_ignore_ascii_case("PS") || pos.eq_ignore_ascii_case("PA") {
+        return Some(bitboard::PASS);
+    }
+
+    let mut chars = pos.chars();
+    let x = STR_POSX.find(chars.next()?.to_ascii_lowercase())?;
+    let y = chars.next()?.to_digit(10)? as usize;
+    if x == 0 || !(1..=8).contains(&y) || chars.next().is_some() {
+        return None;
+    }
+    Some((x - 1 + y * 8 - 8) as u8)
 }
 
 impl Te {
@@ -18,6 +121,14 @@ impl Te {
             xy,
             teban,
             rfen,
+            val : None,
+            time : None,
+            nodes : None,
+            pv : None,
+            best : None,
+            bestval : None,
+            playval : None,
+            mistake : false,
         }
     }
 
@@ -25,26 +136,64 @@ impl Te {
         if line.starts_with("#") {
             return None;
         }
+        Te::parse(line).ok()
+    }
+
+    /// parse a move line. see HEADER.
+    ///
+    /// # Returns
+    /// a move or (kind, message).
+    pub fn parse(line : &str) -> Result<Te, (ErrorKind, String)> {
         let elem = line.split_whitespace().collect::<Vec<&str>>();
-        // nth teban posxy rfen rfen-teban
-        if elem.len() != 5 {
-            return None;
+        // nth teban posxy rfen rfen-teban [val] [key=value ...]
+        if elem.len() < 5 {
+            return Err((ErrorKind::Syntax, format!("too few elements in \"{line}\".")));
+        }
+        if elem[0].parse::<usize>().is_err() {
+            return Err((ErrorKind::Number, format!("invalid move number \"{}\".", elem[0])));
         }
         let teban = match elem[1] {
             bitboard::STONE_SENTE => bitboard::SENTE,
             bitboard::STONE_GOTE => bitboard::GOTE,
-            _ => return None
-        };
-        let xy = if elem[2].eq_ignore_ascii_case("PS") {
-            bitboard::PASS
-        } else {
-            let c = elem[2].chars().nth(0).unwrap();
-            let x = STR_POSX.find(c)?;
-            let y = elem[2].chars().nth(1).unwrap().to_digit(10).unwrap() as usize;
-            (x - 1 + y * 8 - 8) as u8
+            t => return Err((ErrorKind::Syntax, format!("unknown turn \"{t}\"."))),
         };
+        let xy = xy_from(elem[2]).ok_or_else(||
+            (ErrorKind::Position, format!("invalid position \"{}\".", elem[2])))?;
         let rfen = format!("{} {}", elem[3], elem[4]);
-        Some(Te {xy, teban, rfen})
+        let mut te = Te::new(xy, teban, rfen);
+        let number = |v : &str| {
+            v.parse::<f32>().map_err(|_| (ErrorKind::Number, format!("invalid number \"{v}\".")))
+        };
+        for (i, e) in elem.iter().enumerate().skip(5) {
+            match e.split_once('=') {
+                Some(("val", v)) => {te.val = Some(number(v)?);},
+                Some(("nodes", v)) => {
+                    te.nodes = Some(v.parse::<usize>().map_err(|_|
+                        (ErrorKind::Number, format!("invalid number \"{v}\".")))?);
+                },
+                Some(("time", v)) => {te.time = Some(number(v)?);},
+                Some(("pv", v)) => {te.pv = Some(v.to_string());},
+                Some(("best", v)) => {
+                    te.best = Some(xy_from(v).ok_or_else(||
+                        (ErrorKind::Position, format!("invalid position \"{v}\".")))?);
+                },
+                Some(("bestval", v)) => {te.bestval = Some(number(v)?);},
+                Some(("playval", v)) => {te.playval = Some(number(v)?);},
+                Some(("mistake", v)) => {te.mistake = v != "0";},
+                // 知らないキーは読み飛ばす
+                Some(_) => {},
+                None if i == 5 => {te.val = Some(number(e)?);},
+                None => {
+                    return Err((ErrorKind::Syntax, format!("unknown field \"{e}\".")));
+                },
+            }
+        }
+        Ok(te)
+    }
+
+    /// cell index or PASS.
+    pub fn xy(&self) -> u8 {
+        self.xy
     }
 
     pub fn pos(&self) -> String {
@@ -58,8 +207,33 @@ impl Te {
     }
 
     pub fn to_str(&self, i : usize) -> String {
+        let mut val = match self.val {
+            Some(v) => format!(" {v:+.2}"),
+            None => String::new(),
+        };
+        if let Some(n) = self.nodes {
+            val += &format!(" nodes={n}");
+        }
+        if let Some(t) = self.time {
+            val += &format!(" time={t:.3}");
+        }
+        if let Some(pv) = self.pv.as_ref().filter(|pv| !pv.is_empty()) {
+            val += &format!(" pv={pv}");
+        }
+        if let Some(xy) = self.best {
+            val += &format!(" best={}", Te::new(xy, self.teban, String::new()).pos());
+        }
+        if let Some(v) = self.bestval {
+            val += &format!(" bestval={v:+.2}");
+        }
+        if let Some(v) = self.playval {
+            val += &format!(" playval={v:+.2}");
+        }
+        if self.mistake {
+            val += " mistake=1";
+        }
         format!(
-            "{} {} {} {}\n",
+            "{} {} {} {}{val}\n",
             i, match self.teban {
                 bitboard::SENTE => { bitboard::STONE_SENTE },
                 bitboard::GOTE => { bitboard::STONE_GOTE },
@@ -111,20 +285,55 @@ fn testte() {
     assert_eq!("rfen w", te.rfen);
     assert_eq!("h8", te.pos());
     assert_eq!("2 [] h8 rfen w\n", te.to_str(2));
+    assert_eq!(None, te.val);
+
+    let te = Te::from("3 @@ c4 rfen b -2.50");
+    assert!(te.is_some());
+    let te = te.unwrap();
+    assert_eq!(Some(-2.5), te.val);
+    assert_eq!("3 @@ c4 rfen b -2.50\n", te.to_str(3));
+
+    let te = Te::from("3 @@ c4 rfen b val");
+    assert!(te.is_none());
+
+    // revision 2
+    let te = Te::from("4 [] d3 rfen w -1.00 nodes=123 time=0.052 pv=d3E3 new=x").unwrap();
+    assert_eq!(Some(-1.0), te.val);
+    assert_eq!(Some(123), te.nodes);
+    assert_eq!(Some(0.052), te.time);
+    assert_eq!(Some("d3E3"), te.pv.as_deref());
+    assert_eq!("4 [] d3 rfen w -1.00 nodes=123 time=0.052 pv=d3E3\n", te.to_str(4));
+    let te = Te::from("5 @@ c4 rfen b time=1.5 val=2").unwrap();
+    assert_eq!(Some(2.0), te.val);
+    assert_eq!(None, te.nodes);
+    assert_eq!("5 @@ c4 rfen b +2.00 time=1.500\n", te.to_str(5));
+    assert!(Te::from("6 @@ c4 rfen b nodes=x").is_none());
+    // --analyze
+    let te = Te::from("7 [] d3 rfen w -1.00 best=c4 bestval=+2.00 playval=-1.00 mistake=1").unwrap();
+    assert_eq!(Some(26), te.best);
+    assert_eq!((Some(2.0), Some(-1.0), true), (te.bestval, te.playval, te.mistake));
+    assert_eq!("7 [] d3 rfen w -1.00 best=c4 bestval=+2.00 playval=-1.00 mistake=1\n",
+        te.to_str(7));
+    assert!(Te::from("8 @@ c4 rfen b best=z9").is_none());
+    assert!(Te::from("6 @@ c4 rfen b 1.0 2.0").is_none());
 }
 
 pub struct Kifu {
     pub list: Vec<Te>,
     pub score : Option<i8>,
+    /// information about the game. ex. ("black", name)
+    pub info : Vec<(String, String)>,
 }
 
 impl From<&Vec<&str>> for Kifu {
     fn from(lines : &Vec<&str>) -> Kifu {
-        let mut ret = Kifu {
-            list : Vec::<Te>::new(),
-            score : None,
-        };
+        let mut ret = Kifu::new();
         for &l in lines {
+            // # key: value
+            if let Some((key, val)) = l.strip_prefix("# ").and_then(|kv| kv.split_once(": ")) {
+                ret.set_info(key, val);
+                continue;
+            }
             let te = Te::from(l);
             if te.is_none() {
                 continue;
@@ -132,7 +341,7 @@ impl From<&Vec<&str>> for Kifu {
             ret.list.push(te.unwrap());
         }
         // score?
-        let result = lines.last().unwrap();
+        let result = lines.last().copied().unwrap_or_default();
         // println!("{:?}", result);
         let score = result.split(" ").collect::<Vec<&str>>();
         let score = score.last().unwrap().parse::<i8>().unwrap_or(0);
@@ -153,6 +362,7 @@ impl Kifu {
         Kifu {
             list : Vec::<Te>::new(),
             score : None,
+            info : Vec::new(),
         }
     }
 
@@ -161,6 +371,7 @@ impl Kifu {
         Kifu {
             list : Vec::<Te>::new(),
             score : Some(100),
+            info : Vec::new(),
         }
     }
 
@@ -168,15 +379,117 @@ impl Kifu {
     pub fn copy(&self) -> Kifu {
         let mut ret = Kifu::new();
         ret.score = self.score;
+        ret.info = self.info.clone();
         for te in self.list.iter() {
-            ret.append(te.xy, te.teban, te.rfen.clone());
+            ret.append_val(te.xy, te.teban, te.rfen.clone(), te.val);
+            let last = ret.list.last_mut().unwrap();
+            last.time = te.time;
+            last.nodes = te.nodes;
+            last.pv = te.pv.clone();
+            last.best = te.best;
+            last.bestval = te.bestval;
+            last.playval = te.playval;
+            last.mistake = te.mistake;
         }
         ret
     }
+
+    /// # Returns
+    /// value of `key` in info if any.
+    pub fn info(&self, key : &str) -> Option<&str> {
+        self.info.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// set or replace information about the game.
+    pub fn set_info(&mut self, key : &str, val : &str) {
+        match self.info.iter_mut().find(|(k, _)| k == key) {
+            Some(kv) => {kv.1 = val.to_string();},
+            None => {self.info.push((key.to_string(), val.to_string()));},
+        }
+    }
+
+    /// information as comment lines to be put after HEADER.
+    pub fn info_str(&self) -> String {
+        self.info.iter().map(|(k, v)| format!("# {k}: {v}\n")).collect()
+    }
+
+    /// record players, search depth and algorithm, date and version. see HEADER.
+    pub fn set_game_info(&mut self, black : &str, white : &str, depth : u8, think : &str) {
+        self.set_info("black", black);
+        self.set_info("white", white);
+        self.set_info("depth", &depth.to_string());
+        self.set_info("think", think);
+        self.set_info("date", &now());
+        self.set_info("version", env!("CARGO_PKG_VERSION"));
+        if let Some(seed) = rng::seed() {
+            self.set_info("seed", &seed.to_string());
+        }
+    }
+
+    /// write the kifu w/ HEADER and information into a file.
+    pub fn save(&self, path : &str) -> Result<(), String> {
+        std::fs::write(path, format!("{HEADER}{}{}", self.info_str(), self.to_str()))
+            .map_err(|e| format!("{path}: {e}"))
+    }
+
+    /// play `xy` on `ban` and record it.
+    /// a pass is inserted if the side to move has no legal moves.
+    ///
+    /// # Arguments
+    /// - `ban` : board before the move. it is updated.
+    /// - `xy` : cell index or PASS.
+    ///
+    /// # Returns
+    /// () or error message for an illegal move.
+    pub fn play(&mut self, ban : &mut bitboard::BitBoard, xy : u8) -> Result<(), String> {
+        if xy != bitboard::PASS && ban.legal_moves() == 0 && !ban.is_passpass() {
+            self.append(bitboard::PASS, ban.teban, ban.to_string());
+            *ban = ban.r#move(bitboard::PASS).unwrap();
+        }
+        let legal = if xy == bitboard::PASS {
+            ban.legal_moves() == 0
+        } else {
+            ban.legal_moves() & (bitboard::LSB_CELL << xy) != 0
+        };
+        if !legal || ban.is_passpass() {
+            let te = Te::new(xy, ban.teban, String::new());
+            return Err(format!("illegal move {} on {}.", te.pos(), ban));
+        }
+
+        self.append(xy, ban.teban, ban.to_string());
+        *ban = ban.r#move(xy).unwrap();
+        Ok(())
+    }
+
+    /// record passes at the end of the game in the same way as GameBB.
+    ///
+    /// # Arguments
+    /// - `ban` : board after the last move. it is updated.
+    ///
+    /// # Returns
+    /// true if the game is over.
+    pub fn finish(&mut self, ban : &mut bitboard::BitBoard) -> bool {
+        while !ban.is_full() && !ban.is_passpass() && ban.legal_moves() == 0 {
+            self.append(bitboard::PASS, ban.teban, ban.to_string());
+            *ban = ban.r#move(bitboard::PASS).unwrap();
+        }
+        if ban.is_full() {
+            self.append(bitboard::PASS, ban.teban, ban.to_string());
+            return true;
+        }
+        ban.is_passpass()
+    }
     pub fn append(&mut self, xy : u8, t : i8, rfen : String) {
         self.list.push(Te::new(xy, t, rfen));
     }
 
+    /// append a move w/ the root search value from black's view.
+    pub fn append_val(&mut self, xy : u8, t : i8, rfen : String, val : Option<f32>) {
+        let mut te = Te::new(xy, t, rfen);
+        te.val = val;
+        self.list.push(te);
+    }
+
     pub fn to_str(&self) -> String {
         let lines = self.list.iter().enumerate().map(
             |(i, a)| a.to_str(i + 1)).collect::<Vec<String>>();
@@ -221,6 +534,60 @@ impl Kifu {
         self.score = Some(n);
     }
 
+    /// replay moves from the first RFEN and check RFENs, turns, passes and the result.
+    ///
+    /// # Returns
+    /// () or (index of the move or the length for the result, kind, message).
+    pub fn validate(&self) -> Result<(), (usize, ErrorKind, String)> {
+        let Some(first) = self.list.first() else {return Ok(());};
+
+        let mut ban = bitboard::BitBoard::from(&first.rfen)
+            .map_err(|e| (0, ErrorKind::Rfen, format!("{}: {e}", first.rfen)))?;
+        let mut over = false;
+        for (i, te) in self.list.iter().enumerate() {
+            let n = i + 1;
+            if over {
+                return Err((i, ErrorKind::Illegal, format!("move {n} after the game.")));
+            }
+            if te.rfen != ban.to_string() {
+                return Err((i, ErrorKind::Mismatch,
+                    format!("move {n}: {} != {ban} replayed.", te.rfen)));
+            }
+            if te.teban != ban.teban {
+                return Err((i, ErrorKind::Mismatch, format!("move {n}: turn is wrong.")));
+            }
+            if te.xy == bitboard::PASS && ban.is_full() {
+                // 最後の局面
+                over = true;
+                continue;
+            }
+            let legal = ban.legal_moves();
+            if te.xy == bitboard::PASS && legal != 0 {
+                return Err((i, ErrorKind::Illegal, format!("move {n}: pass w/ legal moves.")));
+            }
+            if te.xy != bitboard::PASS && legal == 0 {
+                return Err((i, ErrorKind::Illegal, format!("move {n}: missing pass.")));
+            }
+            if te.xy != bitboard::PASS && legal & (bitboard::LSB_CELL << te.xy) == 0 {
+                return Err((i, ErrorKind::Illegal, format!("move {n}: {} is illegal.", te.pos())));
+            }
+            ban = ban.r#move(te.xy).unwrap();
+            over = ban.is_passpass();
+        }
+        if !over && !ban.is_full() {return Ok(());}
+
+        match self.score {
+            Some(score) if score != ban.count() => {
+                Err((self.list.len(), ErrorKind::Score,
+                    format!("result {score} != {} on the board.", ban.count())))
+            },
+            None => {
+                Err((self.list.len(), ErrorKind::Score, String::from("no result after the game.")))
+            },
+            _ => Ok(()),
+        }
+    }
+
     pub fn winner(&self) -> Option<i8> {
         let score = self.score?;
         if score.is_positive() {
@@ -233,6 +600,517 @@ impl Kifu {
     }
 }
 
+/// parse the result line written by Kifu::to_str().
+fn parse_result(line : &str) -> Result<Option<i8>, (ErrorKind, String)> {
+    if line == "on going..." {return Ok(None);}
+    if line == "DRAW." {return Ok(Some(0));}
+
+    let (winner, score) = line.rsplit_once(' ')
+        .ok_or_else(|| (ErrorKind::Syntax, format!("unknown line \"{line}\".")))?;
+    let score = score.parse::<i8>()
+        .map_err(|_| (ErrorKind::Number, format!("invalid score \"{score}\".")))?;
+    match winner {
+        "SENTE won." if score > 0 => Ok(Some(score)),
+        "GOTE won." if score < 0 => Ok(Some(score)),
+        "SENTE won." | "GOTE won." => {
+            Err((ErrorKind::Score, format!("\"{line}\" is inconsistent.")))
+        },
+        _ => Err((ErrorKind::Syntax, format!("unknown line \"{line}\"."))),
+    }
+}
+
+/// parse a kifu strictly.
+///
+/// # Arguments
+/// - `txt` : kifu.
+/// - `partial` : the result may be missing in an interrupted game.
+///
+/// # Returns
+/// (kifu, line numbers of moves, line number of the result) or error.
+fn parse_lines(txt : &str, partial : bool) -> Result<(Kifu, Vec<usize>, usize), KifuError> {
+    let mut kifu = Kifu::new();
+    let mut movelines = Vec::new();
+    let mut resultline = 0;
+    for (i, l) in txt.lines().enumerate() {
+        let err = |(kind, msg)| KifuError {line : i + 1, kind, msg};
+        let l = l.trim();
+        if l.is_empty() {continue;}
+
+        if resultline > 0 {
+            return Err(err((ErrorKind::Syntax, format!("\"{l}\" after the result."))));
+        }
+        if let Some(comment) = l.strip_prefix('#') {
+            if let Some((key, val)) = comment.strip_prefix(' ').and_then(|kv| kv.split_once(": ")) {
+                kifu.set_info(key, val);
+            }
+            continue;
+        }
+        if l.starts_with(|c : char| c.is_ascii_digit()) {
+            kifu.list.push(Te::parse(l).map_err(err)?);
+            movelines.push(i + 1);
+            continue;
+        }
+        kifu.score = parse_result(l).map_err(err)?;
+        resultline = i + 1;
+    }
+    if resultline == 0 && partial {
+        return Ok((kifu, movelines, txt.lines().count() + 1));
+    }
+    if resultline == 0 {
+        return Err(KifuError {
+            line : txt.lines().count() + 1, kind : ErrorKind::Syntax,
+            msg : String::from("no result."),
+        });
+    }
+    Ok((kifu, movelines, resultline))
+}
+
+/// parse a kifu strictly and replay it.
+///
+/// # Returns
+/// kifu or the first error.
+pub fn check(txt : &str) -> Result<Kifu, KifuError> {
+    check_lines(txt, false)
+}
+
+/// check() w/ a kifu which may lack the result.
+fn check_lines(txt : &str, partial : bool) -> Result<Kifu, KifuError> {
+    let (kifu, movelines, resultline) = parse_lines(txt, partial)?;
+    kifu.validate().map_err(|(idx, kind, msg)| {
+        KifuError {line : movelines.get(idx).copied().unwrap_or(resultline), kind, msg}
+    })?;
+    Ok(kifu)
+}
+
+/// read a game to be continued.
+///
+/// # Arguments
+/// - `path` : a kifu file which may lack the result, GGF or a transcript.
+///   the first game is read from GGF or transcripts.
+///
+/// # Returns
+/// kifu or error message.
+pub fn load(path : &str) -> Result<Kifu, String> {
+    let txt = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let body = txt.trim_start();
+    let game = if body.starts_with("(;") {
+        read_ggf(body).into_iter().next().unwrap()
+    } else if body.starts_with("# reversi kifu")
+            || body.lines().any(|l| l.starts_with(|c : char| c.is_ascii_digit())) {
+        return check_lines(&txt, true).map_err(|e| format!("{path}:{e}"));
+    } else {
+        Format::Moves.read(body).into_iter().next()
+            .unwrap_or_else(|| Err(String::from("no moves.")))
+    };
+    game.map_err(|e| format!("{path}: {e}"))
+}
+
+/// check a kifu file or all of them in a directory.
+/// an unreadable file is counted as invalid.
+///
+/// # Returns
+/// (# of kifus, # of invalid ones) or error message.
+pub fn checkdir(path : &str) -> Result<(usize, usize), String> {
+    let files = if std::path::Path::new(path).is_dir() {
+        dataset::listkifu(path)?.iter().map(|f| {
+            std::path::Path::new(path).join(f).to_str().unwrap().to_string()
+        }).collect()
+    } else {
+        vec![path.to_string()]
+    };
+    let mut invalid = 0;
+    for file in files.iter() {
+        let txt = match std::fs::read_to_string(file) {
+            Ok(txt) => txt,
+            Err(e) => {
+                println!("{file}: {e}");
+                invalid += 1;
+                continue;
+            },
+        };
+        if let Err(e) = check(&txt) {
+            println!("{file}:{e}");
+            invalid += 1;
+        }
+    }
+    Ok((files.len(), invalid))
+}
+
+/// the current date and time in UTC. "YYYY-MM-DD hh:mm:ss"
+pub fn now() -> String {
+    let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    date(secs)
+}
+
+/// seconds from the unix epoch to "YYYY-MM-DD hh:mm:ss".
+fn date(secs : u64) -> String {
+    let (days, sec) = ((secs / 86400) as i64, secs % 86400);
+    // days to the civil date. (H. Hinnant)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 {mp + 3} else {mp - 9};
+    let y = yoe + era * 400 + if m <= 2 {1} else {0};
+    format!("{y:04}-{m:02}-{d:02} {:02}:{:02}:{:02}", sec / 3600, sec / 60 % 60, sec % 60)
+}
+
+/// GGF keys for info.
+const GGF_INFO : [(&str, &str) ; 5] = [
+    ("PC", "place"), ("DT", "date"), ("PB", "black"), ("PW", "white"), ("TI", "time"),
+];
+
+/// GGF and transcripts use the standard initial position which is BitBoard::new() upside down.
+fn std_flip(xy : u8) -> u8 {
+    if xy == bitboard::PASS {xy} else {xy ^ 0x38}
+}
+
+/// "[...]" w/ escapes.
+fn ggf_escape(val : &str) -> String {
+    val.replace('\\', "\\\\").replace(']', "\\]")
+}
+
+/// split "KEY[value]KEY[value]..." into (KEY, value).
+fn ggf_tokens(txt : &str) -> Result<Vec<(String, String)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = txt.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek().is_none() {break;}
+
+        let mut key = String::new();
+        for c in chars.by_ref() {
+            if c == '[' {break;}
+            if !c.is_ascii_alphabetic() {
+                return Err(format!("invalid key \"{key}{c}\"."));
+            }
+            key.push(c);
+        }
+        let mut val = String::new();
+        let mut closed = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {val.push(chars.next().unwrap_or('\\'));},
+                ']' => {closed = true; break;},
+                _ => {val.push(c);},
+            }
+        }
+        if !closed {
+            return Err(format!("{key}[ is not closed."));
+        }
+        tokens.push((key, val));
+    }
+    Ok(tokens)
+}
+
+/// BO[8 <rows from the 1st> <turn>] to a board.
+fn ggf_board(val : &str) -> Result<bitboard::BitBoard, String> {
+    let elem = val.split_whitespace().collect::<Vec<_>>();
+    if elem.len() < 3 || elem[0] != "8" {
+        return Err(format!("BO[{val}] is not supported."));
+    }
+    let cells = elem[1..elem.len() - 1].concat();
+    if cells.len() != bitboard::CELL_2D {
+        return Err(format!("BO[{val}] has {} cells.", cells.len()));
+    }
+
+    let mut ban = bitboard::BitBoard::new();
+    ban.black = 0;
+    ban.white = 0;
+    for (i, c) in cells.chars().enumerate() {
+        let bit = bitboard::LSB_CELL << std_flip(i as u8);
+        match c {
+            '*' => {ban.black |= bit;},
+            'O' | 'o' => {ban.white |= bit;},
+            '-' => {},
+            _ => {return Err(format!("BO[{val}] has '{c}'."));},
+        }
+    }
+    ban.teban = match elem[elem.len() - 1] {
+        "*" => bitboard::SENTE,
+        "O" | "o" => bitboard::GOTE,
+        t => {return Err(format!("BO[{val}] has turn {t}."));},
+    };
+    Ok(ban)
+}
+
+impl Kifu {
+    /// read a game in GGF.
+    /// ex. (;GM[Othello]PB[a]PW[b]RE[+2.000]TY[8]BO[8 ... *]B[f5]W[d6//1.2]...;)
+    ///
+    /// # Returns
+    /// kifu or error message. the score is the difference of stones
+    /// if the game is over, RE[] otherwise.
+    pub fn from_ggf(txt : &str) -> Result<Kifu, String> {
+        let body = txt.trim().strip_prefix("(;").and_then(|t| t.strip_suffix(";)"))
+            .ok_or("not a GGF game.")?;
+        let mut kifu = Kifu::new();
+        let mut ban = bitboard::BitBoard::new();
+        let mut result = None;
+        let mut started = false;
+        for (key, val) in ggf_tokens(body)? {
+            match key.as_str() {
+                "GM" if !val.eq_ignore_ascii_case("othello") => {
+                    return Err(format!("GM[{val}] is not othello."));
+                },
+                "TY" if !val.starts_with('8') => {
+                    return Err(format!("TY[{val}] is not supported."));
+                },
+                "BO" => {
+                    if started {return Err(String::from("BO[] after moves."));}
+                    ban = ggf_board(&val)?;
+                },
+                "RE" => {
+                    // +18.000:r など
+                    let score = val.split(':').next().unwrap_or("");
+                    result = score.parse::<f32>().ok().map(|s| s.round() as i8);
+                },
+                "B" | "W" => {
+                    started = true;
+                    let mut elem = val.split('/');
+                    let pos = elem.next().unwrap_or("");
+                    let xy = xy_from(pos).ok_or(format!("{key}[{val}] is invalid."))?;
+                    let teban = if key == "B" {bitboard::SENTE} else {bitboard::GOTE};
+                    let turn = if ban.teban == teban || ban.legal_moves() == 0 {
+                        Ok(())
+                    } else {
+                        Err(String::from("not the turn."))
+                    };
+                    turn.and_then(|_| kifu.play(&mut ban, std_flip(xy)))
+                        .map_err(|e| format!("{key}[{val}]: {e}"))?;
+                    let te = kifu.list.last_mut().unwrap();
+                    // 評価値は打つ側から見た値
+                    te.val = elem.next().and_then(|v| v.parse::<f32>().ok())
+                        .map(|v| v * teban as f32);
+                    te.time = elem.next().and_then(ggf_seconds);
+                },
+                _ => {
+                    if let Some((_, name)) = GGF_INFO.iter().find(|(k, _)| *k == key) {
+                        kifu.set_info(name, &val);
+                    }
+                },
+            }
+        }
+        if kifu.finish(&mut ban) {
+            kifu.winneris(ban.count());
+        } else if let Some(score) = result {
+            kifu.winneris(score);
+        }
+        Ok(kifu)
+    }
+
+    /// write the game in GGF.
+    /// passes at the end of the game are not written.
+    pub fn to_ggf(&self) -> String {
+        let mut txt = String::from("(;GM[Othello]");
+        for (key, name) in GGF_INFO.iter() {
+            if let Some(val) = self.info(name) {
+                txt += &format!("{key}[{}]", ggf_escape(val));
+            }
+        }
+        match self.score {
+            Some(score) => {txt += &format!("RE[{:+.3}]", score as f32);},
+            None => {txt += "RE[?]";},
+        }
+        let ban = self.list.first()
+            .and_then(|te| bitboard::BitBoard::from(&te.rfen).ok())
+            .unwrap_or_default();
+        txt += "TY[8]BO[8";
+        for y in 0..bitboard::NUMCELL {
+            txt += " ";
+            for x in 0..bitboard::NUMCELL {
+                let bit = bitboard::LSB_CELL << std_flip((x + y * bitboard::NUMCELL) as u8);
+                txt += if ban.black & bit != 0 {"*"} else if ban.white & bit != 0 {"O"} else {"-"};
+            }
+        }
+        txt += if ban.teban == bitboard::SENTE {" *]"} else {" O]"};
+
+        let last = self.list.iter().rposition(|te| te.xy != bitboard::PASS);
+        for te in self.list.iter().take(last.map_or(0, |i| i + 1)) {
+            let key = if te.teban == bitboard::SENTE {"B"} else {"W"};
+            let pos = if te.xy == bitboard::PASS {
+                String::from("pa")
+            } else {
+                Te::new(std_flip(te.xy), te.teban, String::new()).pos()
+            };
+            let val = te.val.map_or(String::new(), |v| format!("{:.2}", v * te.teban as f32));
+            let time = te.time.map_or(String::new(), |t| format!("{t:.2}"));
+            if val.is_empty() && time.is_empty() {
+                txt += &format!("{key}[{pos}]");
+            } else {
+                txt += &format!("{key}[{pos}/{val}/{time}]");
+            }
+        }
+        txt + ";)"
+    }
+}
+
+/// "12.3" or "01:02.5" in GGF to seconds.
+fn ggf_seconds(txt : &str) -> Option<f32> {
+    txt.split(':').try_fold(0.0, |sum, t| t.parse::<f32>().ok().map(|t| sum * 60.0 + t))
+}
+
+impl Kifu {
+    /// play a transcript like "f5d6c3" in the standard coordinates on `ban`.
+    /// passes can be omitted or written as "ps" or "pa". spaces are ignored.
+    ///
+    /// # Arguments
+    /// - `ban` : board before the moves. it is updated.
+    /// - `txt` : transcript.
+    ///
+    /// # Returns
+    /// () or error message w/ the move number.
+    pub fn play_moves(&mut self, ban : &mut bitboard::BitBoard, txt : &str)
+            -> Result<(), String> {
+        let chars = txt.chars().filter(|c| !c.is_whitespace()).collect::<Vec<_>>();
+        for (i, mv) in chars.chunks(2).enumerate() {
+            let pos = mv.iter().collect::<String>();
+            let xy = xy_from(&pos).ok_or(format!("move {} {pos}: invalid move.", i + 1))?;
+            self.play(ban, std_flip(xy)).map_err(|e| format!("move {} {pos}: {e}", i + 1))?;
+        }
+        Ok(())
+    }
+
+    /// read a transcript from the initial position.
+    ///
+    /// # Returns
+    /// kifu or error message. the score is set if the game is over.
+    pub fn from_moves(txt : &str) -> Result<Kifu, String> {
+        let mut kifu = Kifu::new();
+        let mut ban = bitboard::BitBoard::new();
+        kifu.play_moves(&mut ban, txt)?;
+        if kifu.finish(&mut ban) {
+            kifu.winneris(ban.count());
+        }
+        Ok(kifu)
+    }
+
+    /// write moves as a transcript in the standard coordinates w/o passes.
+    ///
+    /// # Returns
+    /// transcript or error message if the game does not start from the initial position.
+    pub fn to_moves(&self) -> Result<String, String> {
+        if self.list.first().is_some_and(|te| te.rfen != bitboard::BitBoard::new().to_string()) {
+            return Err(String::from("not from the initial position."));
+        }
+
+        Ok(self.list.iter().filter(|te| te.xy != bitboard::PASS).map(|te| {
+            Te::new(std_flip(te.xy), te.teban, String::new()).pos()
+        }).collect())
+    }
+}
+
+/// read games in a GGF file.
+///
+/// # Returns
+/// list of kifus or error messages for every game.
+pub fn read_ggf(txt : &str) -> Vec<Result<Kifu, String>> {
+    txt.match_indices("(;").map(|(i, _)| {
+        let game = &txt[i..];
+        let end = game.find(";)").map_or(game.len(), |e| e + 2);
+        Kifu::from_ggf(&game[..end])
+    }).collect()
+}
+
+/// formats of game records other than kifu files.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    /// Generic Game Format. a game in "(;GM[Othello]...;)".
+    Ggf,
+    /// transcript like "f5d6c3" in every line.
+    Moves,
+}
+
+impl Format {
+    /// # Returns
+    /// kifus or error messages for every game.
+    fn read(&self, txt : &str) -> Vec<Result<Kifu, String>> {
+        match self {
+            Format::Ggf => read_ggf(txt),
+            Format::Moves => {
+                txt.lines().map(|l| l.trim())
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(Kifu::from_moves).collect()
+            },
+        }
+    }
+
+    fn write(&self, kifu : &Kifu) -> Result<String, String> {
+        match self {
+            Format::Ggf => Ok(kifu.to_ggf()),
+            Format::Moves => kifu.to_moves(),
+        }
+    }
+}
+
+/// import games in a file into `outdir` as kifu files.
+///
+/// # Arguments
+/// - `path` : a file in `format`.
+/// - `outdir` : directory for kifu files.
+/// - `format` : format of `path`.
+pub fn import(path : &str, outdir : &str, format : Format) -> Result<(), String> {
+    let txt = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    std::fs::create_dir_all(outdir).map_err(|e| format!("{outdir}: {e}"))?;
+    let stem = std::path::Path::new(path).file_stem()
+        .and_then(|s| s.to_str()).unwrap_or("games");
+    let (mut imported, mut invalid) = (0, 0);
+    for (i, kifu) in format.read(&txt).into_iter().enumerate() {
+        let kifu = match kifu {
+            Ok(kifu) => kifu,
+            Err(msg) => {
+                eprintln!("{path}: game {i}: {msg}");
+                invalid += 1;
+                continue;
+            },
+        };
+        let kifupath = std::path::Path::new(outdir).join(format!("kifu{stem}_{i:05}.txt"));
+        kifu.save(kifupath.to_str().unwrap())?;
+        imported += 1;
+    }
+    println!("{imported} games imported, {invalid} invalid.");
+    Ok(())
+}
+
+/// convert kifu files into another format.
+///
+/// # Arguments
+/// - `path` : a kifu file or a directory including them.
+/// - `format` : output format.
+///
+/// # Returns
+/// a game in a line or error message. games which can not be read or converted are skipped.
+pub fn export(path : &str, format : Format) -> Result<String, String> {
+    let isdir = std::path::Path::new(path).is_dir();
+    let files = if isdir {
+        dataset::listkifu(path)?.iter().map(|f| {
+            std::path::Path::new(path).join(f).to_str().unwrap().to_string()
+        }).collect()
+    } else {
+        vec![path.to_string()]
+    };
+    let mut txt = String::new();
+    for file in files.iter() {
+        // ディレクトリなら壊れた棋譜は飛ばす
+        let kifu = match dataset::readkifu(file) {
+            Ok(kifu) => kifu,
+            Err(msg) if isdir => {eprintln!("{msg}"); continue;},
+            Err(msg) => {return Err(msg);},
+        };
+        match format.write(&kifu) {
+            Ok(game) => {txt += &game; txt += "\n";},
+            Err(msg) => {eprintln!("{file}: {msg}");},
+        }
+    }
+    Ok(txt)
+}
+
 #[test]
 fn testkifu() {
     // new, from, append, to_str, winneris, winner
@@ -272,4 +1150,295 @@ fn testkifu() {
     for ((i, a), b) in kifu.list.iter().enumerate().zip(kifu2.list.iter()) {
         assert_eq!(a.to_str(i), b.to_str(i));
     }
+
+    // revision 2
+    let mut kifu = kifu.copy();
+    kifu.set_info("black", "ruversi 0.6");
+    kifu.set_info("depth", "7");
+    let mut kifu3 = Kifu::new();
+    kifu3.set_game_info("a", "b", 5, "ab");
+    assert_eq!(kifu3.info.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>(),
+        ["black", "white", "depth", "think", "date", "version"]);
+    kifu.list[0].nodes = Some(1234);
+    kifu.list[0].time = Some(0.5);
+    kifu.list[1].pv = Some(String::from("f6H6"));
+    let path = std::env::temp_dir().join(
+        format!("ruversi_test_kifu_{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    kifu.save(path).unwrap();
+    let content = std::fs::read_to_string(path).unwrap();
+    assert!(content.starts_with(&format!("{HEADER}# black: ruversi 0.6\n# depth: 7\n1 @@ h5 ")));
+    assert!(content.contains(" b nodes=1234 time=0.500\n"));
+    let kifu2 = dataset::readkifu(path).unwrap();
+    assert_eq!(kifu2.to_str(), kifu.to_str());
+    assert_eq!(kifu2.info("depth"), Some("7"));
+    assert_eq!(kifu2.list[1].pv.as_deref(), Some("f6H6"));
+    std::fs::remove_file(path).unwrap();
+    assert!(kifu.save("/nonexistent/kifu.txt").is_err());
+    assert_eq!(now().len(), "2024-01-02 03:04:05".len());
+    assert_eq!(date(0), "1970-01-01 00:00:00");
+    assert_eq!(date(951_782_400 + 86399), "2000-02-29 23:59:59");
+}
+
+#[test]
+fn test_ggf() {
+    assert_eq!(xy_from("a1"), Some(0));
+    assert_eq!(xy_from("H8"), Some(63));
+    assert_eq!(xy_from("ps"), Some(bitboard::PASS));
+    assert_eq!(xy_from("pa"), Some(bitboard::PASS));
+    assert_eq!(xy_from("i1"), None);
+    assert_eq!(xy_from("a9"), None);
+    assert_eq!(xy_from("a10"), None);
+
+    // 決まった手順で最後まで打つ
+    let mut kifu = Kifu::new();
+    let mut ban = bitboard::BitBoard::new();
+    let mut i = 3;
+    while !kifu.finish(&mut ban) {
+        let moves = ban.genmove().unwrap();
+        kifu.play(&mut ban, moves[i % moves.len()]).unwrap();
+        kifu.list.last_mut().unwrap().val = Some(i as f32);
+        i = (i * 7 + 3) % 101;
+    }
+    kifu.winneris(ban.count());
+    kifu.set_info("black", "a]b\\c");
+    kifu.set_info("white", "d");
+    kifu.list[0].time = Some(1.5);
+    let ggf = kifu.to_ggf();
+    assert!(ggf.starts_with("(;GM[Othello]PB[a\\]b\\\\c]PW[d]RE["));
+    assert!(ggf.contains("TY[8]BO[8 -------- -------- -------- ---O*--- ---*O--- -------- -------- -------- *]B[d3/3.00/1.50]"), "{ggf}");
+    let kifu2 = Kifu::from_ggf(&ggf).unwrap();
+    assert_eq!(kifu2.to_str(), kifu.to_str());
+    assert_eq!(kifu2.info_str(), kifu.info_str());
+    assert_eq!(kifu2.list[0].time, Some(1.5));
+    assert_eq!(kifu2.to_ggf(), ggf);
+
+    // 打ちかけの対局と評価値、時間
+    let ggf = "(;GM[Othello]PC[NJ]PB[a]PW[b]RE[+2.000:r]TY[8]\
+        BO[8 -------- -------- -------- ---O*--- ---*O--- -------- -------- -------- *]\
+        B[f5//1:02.5]W[d6/-1.5/3];)";
+    let kifu = Kifu::from_ggf(ggf).unwrap();
+    assert_eq!(kifu.list.len(), 2);
+    assert_eq!(kifu.list[0].pos(), "f4");
+    assert_eq!(kifu.list[1].pos(), "d3");
+    assert_eq!(kifu.list[0].time, Some(62.5));
+    assert_eq!(kifu.list[1].val, Some(1.5));
+    assert_eq!(kifu.score, Some(2));
+    assert_eq!(kifu.info("place"), Some("NJ"));
+    assert_eq!(kifu.info("white"), Some("b"));
+
+    // 白番から始まる局面
+    let ggf = "(;GM[Othello]RE[?]TY[8]\
+        BO[8 -------- -------- ---*---- ---**--- ---*O--- -------- -------- -------- O]\
+        W[c3];)";
+    let kifu = Kifu::from_ggf(ggf).unwrap();
+    assert_eq!(kifu.list[0].teban, bitboard::GOTE);
+    assert_eq!(kifu.score, None);
+    assert_eq!(kifu.to_ggf(), ggf);
+
+    for (ggf, msg) in [
+        ("(;GM[Go];)", "GM[Go] is not othello."),
+        ("(;GM[Othello]B[z9];)", "B[z9] is invalid."),
+        ("(;GM[Othello]W[d3];)", "W[d3]: not the turn."),
+        ("(;GM[Othello]B[a1];)", "B[a1]: illegal move a8 on 8/8/8/3Aa3/3aA3/8/8/8 b."),
+        ("(;GM[Othello]B[f5]BO[8 * *];)", "BO[] after moves."),
+        ("(;GM[Othello]PB[a;)", "PB[ is not closed."),
+        ("GM[Othello]", "not a GGF game."),
+    ] {
+        assert_eq!(Kifu::from_ggf(ggf).err().unwrap(), msg);
+    }
+
+    let games = read_ggf("1 (;GM[Othello]B[f5];)\n2 (;GM[Go];)\n(;GM[Othello]PB[x];)");
+    assert_eq!(games.len(), 3);
+    assert!(games[0].is_ok());
+    assert!(games[1].is_err());
+    assert_eq!(games[2].as_ref().unwrap().info("black"), Some("x"));
+
+    // kifuファイルのコメントから情報を読む
+    let txt = format!("{HEADER}# black: x\n# white: y z\n{}", kifu.to_str());
+    let kifu = Kifu::from(&txt.split('\n').collect::<Vec<_>>());
+    assert_eq!(kifu.info("black"), Some("x"));
+    assert_eq!(kifu.info("white"), Some("y z"));
+    assert_eq!(kifu.list.len(), 1);
+}
+
+#[test]
+fn test_moves() {
+    let kifu = Kifu::from_moves("f5d6c3 D3c4").unwrap();
+    assert_eq!(kifu.list.len(), 5);
+    assert_eq!(kifu.list[0].pos(), "f4");
+    assert_eq!(kifu.list[4].teban, bitboard::SENTE);
+    assert_eq!(kifu.score, None);
+    assert_eq!(kifu.to_moves().unwrap(), "f5d6c3d3c4");
+
+    // 最後まで打った対局は往復しても同じ
+    let mut kifu = Kifu::new();
+    let mut ban = bitboard::BitBoard::new();
+    let mut i = 5;
+    while !kifu.finish(&mut ban) {
+        let moves = ban.genmove().unwrap();
+        kifu.play(&mut ban, moves[i % moves.len()]).unwrap();
+        i = (i * 7 + 3) % 101;
+    }
+    kifu.winneris(ban.count());
+    let moves = kifu.to_moves().unwrap();
+    assert!(!moves.contains("ps"));
+    let kifu2 = Kifu::from_moves(&moves).unwrap();
+    assert_eq!(kifu2.to_str(), kifu.to_str());
+    assert!(Kifu::from_moves(" ").unwrap().list.is_empty());
+
+    // パスを省略した手順と書いた手順
+    let mut kifu = Kifu::new();
+    let mut ban = bitboard::BitBoard::from("8/8/8/8/8/8/8/Aa6 w").unwrap();
+    kifu.play_moves(&mut ban, "c1").unwrap();
+    assert_eq!(kifu.list.len(), 2);
+    assert_eq!(kifu.to_moves().err().unwrap(), "not from the initial position.");
+    assert_eq!(kifu.list[0].xy, bitboard::PASS);
+    let mut ban = bitboard::BitBoard::from("8/8/8/8/8/8/8/Aa6 w").unwrap();
+    let mut kifu = Kifu::new();
+    kifu.play_moves(&mut ban, "PSc1").unwrap();
+    assert_eq!(kifu.list.len(), 2);
+
+    assert_eq!(Kifu::from_moves("f5f5").err().unwrap(),
+        "move 2 f5: illegal move f4 on 8/8/8/3C2/3aA3/8/8/8 w.");
+    assert_eq!(Kifu::from_moves("f5z9").err().unwrap(), "move 2 z9: invalid move.");
+    assert_eq!(Kifu::from_moves("f5d").err().unwrap(), "move 2 d: invalid move.");
+}
+
+#[test]
+fn test_import_export() {
+    let dir = std::env::temp_dir().join(
+        format!("ruversi_test_import_{}", std::process::id()));
+    let dir = dir.to_str().unwrap();
+    let path = format!("{dir}.txt");
+    std::fs::write(&path, "# transcripts\nf5d6c3\n\nf5f5\nf5f6e6\n").unwrap();
+    import(&path, dir, Format::Moves).unwrap();
+    let files = dataset::listkifu(dir).unwrap();
+    assert_eq!(files.len(), 2);
+    let name = std::path::Path::new(&path).file_stem().unwrap().to_str().unwrap();
+    assert_eq!(files[1], format!("kifu{name}_00002.txt"));
+    assert_eq!(export(dir, Format::Moves).unwrap(), "f5d6c3\nf5f6e6\n");
+    let ggf = export(&format!("{dir}/{}", files[0]), Format::Ggf).unwrap();
+    assert!(ggf.ends_with("B[f5]W[d6]B[c3];)\n"), "{ggf}");
+
+    std::fs::write(&path, &ggf).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    import(&path, dir, Format::Ggf).unwrap();
+    assert_eq!(export(dir, Format::Moves).unwrap(), "f5d6c3\n");
+    std::fs::remove_dir_all(dir).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(export(dir, Format::Ggf).is_err());
+}
+
+
+#[test]
+fn test_check() {
+    // 壊れた行でpanicしない
+    assert!(Te::from("1 @@ z9 rfen b").is_none());
+    assert!(Te::from("1 @@ a rfen b").is_none());
+    assert_eq!(Te::parse("1 @@ z9 rfen b").err().unwrap().0, ErrorKind::Position);
+    assert_eq!(Te::parse("x @@ a1 rfen b").err().unwrap().0, ErrorKind::Number);
+    assert_eq!(Te::parse("1 ## a1 rfen b").err().unwrap().0, ErrorKind::Syntax);
+    assert_eq!(Kifu::from(&Vec::new()).list.len(), 0);
+
+    // 決まった手順で最後まで打つ
+    let mut kifu = Kifu::new();
+    let mut ban = bitboard::BitBoard::new();
+    let mut i = 7;
+    while !kifu.finish(&mut ban) {
+        let moves = ban.genmove().unwrap();
+        kifu.play(&mut ban, moves[i % moves.len()]).unwrap();
+        i = (i * 7 + 3) % 101;
+    }
+    kifu.winneris(ban.count());
+    kifu.set_info("black", "x");
+    let txt = format!("{HEADER}{}{}", kifu.info_str(), kifu.to_str());
+    let kifu2 = check(&txt).unwrap();
+    assert_eq!(kifu2.to_str(), kifu.to_str());
+    assert_eq!(kifu2.info("black"), Some("x"));
+    // 打ちかけも正しい
+    let ongoing = Kifu::from_moves("f5d6c3").unwrap();
+    assert!(check(&ongoing.to_str()).is_ok());
+
+    let lines = txt.lines().collect::<Vec<_>>();
+    // HEADER, info, 1手目
+    assert!(lines[2].starts_with("1 @@ "));
+    let edit = |n : usize, line : &str| {
+        let mut l = lines.clone();
+        l[n - 1] = line;
+        l.join("\n")
+    };
+    let err = check(&edit(3, "1 @@ z9 8/8/8/3Aa3/3aA3/8/8/8 b")).err().unwrap();
+    assert_eq!((err.line, err.kind), (3, ErrorKind::Position));
+    assert_eq!(err.to_string(), "3: position error. invalid position \"z9\".");
+    let err = check(&edit(3, "1 @@ a1 8/8/8/3Aa3/3aA3/8/8/8 b")).err().unwrap();
+    assert_eq!((err.line, err.kind), (3, ErrorKind::Illegal));
+    let err = check(&edit(3, "1 @@ d3 8/8/8/3Aa3/3aA3/8/8/8 w")).err().unwrap();
+    assert_eq!((err.line, err.kind), (3, ErrorKind::Mismatch));
+    let err = check(&edit(3, "1 @@ d3 8/8/8/3Aa3/3aA3/8/8/8 x")).err().unwrap();
+    assert_eq!((err.line, err.kind), (3, ErrorKind::Rfen));
+    let err = check(&edit(4, "2 [] a1 8/8/8/3Aa3/3aA3/8/8/8 w")).err().unwrap();
+    assert_eq!((err.line, err.kind), (4, ErrorKind::Mismatch));
+    let err = check(&edit(5, "3 @@ e3 rfen b x")).err().unwrap();
+    assert_eq!((err.line, err.kind), (5, ErrorKind::Number));
+    let n = lines.len();
+    let err = check(&edit(n, "SENTE won. 64")).err().unwrap();
+    assert_eq!((err.line, err.kind), (n, ErrorKind::Score));
+    let err = check(&edit(n, "GOTE won. 3")).err().unwrap();
+    assert_eq!((err.line, err.kind), (n, ErrorKind::Score));
+    let err = check(&edit(n, "on going...")).err().unwrap();
+    assert_eq!((err.line, err.kind), (n, ErrorKind::Score));
+    let err = check(&edit(n, "won.")).err().unwrap();
+    assert_eq!((err.line, err.kind), (n, ErrorKind::Syntax));
+    let err = check(&lines[..n - 1].join("\n")).err().unwrap();
+    assert_eq!((err.line, err.kind), (n, ErrorKind::Syntax));
+    let err = check(&format!("{txt}\n1 @@ a1 rfen b")).err().unwrap();
+    assert_eq!((err.line, err.kind), (n + 1, ErrorKind::Syntax));
+    // パスを抜くと手番が合わない
+    let ps = lines.iter().position(|l| l.contains(" PS ")).unwrap();
+    let mut l = lines.clone();
+    l.remove(ps);
+    let err = check(&l.join("\n")).err().unwrap();
+    assert_eq!(err.kind, ErrorKind::Mismatch);
+    // 読めないファイルも不正として数えて続ける
+    let dir = std::env::temp_dir().join(
+        format!("ruversi_test_checkdir_{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("kifu0unreadable")).unwrap();
+    std::fs::write(dir.join("kifu1.txt"), &txt).unwrap();
+    std::fs::write(dir.join("kifu2.txt"), edit(n, "won.")).unwrap();
+    assert_eq!(checkdir(dir.to_str().unwrap()), Ok((3, 2)));
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(checkdir(dir.to_str().unwrap()).is_ok_and(|(_, invalid)| invalid == 1));
+}
+
+#[test]
+fn test_load() {
+    let path = std::env::temp_dir().join(
+        format!("ruversi_test_load_{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    let ongoing = Kifu::from_moves("f5d6c3").unwrap();
+    let load_txt = |txt : &str| {
+        std::fs::write(path, txt).unwrap();
+        load(path)
+    };
+
+    // 結果の無い棋譜, GGF, 手順
+    let txt = format!("{HEADER}{}", ongoing.to_str());
+    let lines = txt.lines().filter(|l| *l != "on going...").collect::<Vec<_>>();
+    let kifu = load_txt(&lines.join("\n")).unwrap();
+    assert_eq!(kifu.to_str(), ongoing.to_str());
+    assert_eq!(load_txt(&txt).unwrap().to_str(), ongoing.to_str());
+    let kifu = load_txt(&ongoing.to_ggf()).unwrap();
+    assert_eq!(kifu.to_moves().unwrap(), "f5d6c3");
+    let kifu = load_txt("# transcript\n\nf5d6c3\nf5\n").unwrap();
+    assert_eq!(kifu.to_str(), ongoing.to_str());
+
+    let err = load_txt(&txt.replace(&format!("1 @@ {}", ongoing.list[0].pos()), "1 @@ a1")).err().unwrap();
+    assert_eq!(err, format!("{path}:2: illegal error. move 1: a1 is illegal."));
+    assert_eq!(load_txt("f5f5").err().unwrap(),
+        format!("{path}: move 2 f5: illegal move f4 on 8/8/8/3C2/3aA3/8/8/8 w."));
+    assert_eq!(load_txt("# nothing\n").err().unwrap(), format!("{path}: no moves."));
+    assert!(load_txt(&format!("{HEADER}{}", Kifu::new().to_str())).unwrap().list.is_empty());
+    std::fs::remove_file(path).unwrap();
+    assert!(load(path).is_err());
 }
@@ -0,0 +1,167 @@
+use super::*;
+
+use rand::{Rng, SeedableRng};
+use rand::distributions::{StandardNormal, WeightedIndex};
+
+/// strength of the engine for teaching, casual play and varied kifus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level {
+    /// max searching depth.
+    pub depth : u8,
+    /// search is not deepened if the total # of nodes is expected to exceed this.
+    /// a soft limit checked between depths. 0 : no limit.
+    pub nodes : usize,
+    /// standard deviation of noise added to root scores in discs.
+    pub noise : f32,
+    /// temperature of softmax over root scores in discs. 0 : the best move.
+    pub temperature : f32,
+}
+
+/// named levels from the weakest.
+pub const LEVELS : [(&str, Level) ; 5] = [
+    ("beginner", Level {depth : 1, nodes : 100, noise : 8.0, temperature : 4.0}),
+    ("novice", Level {depth : 2, nodes : 1000, noise : 4.0, temperature : 2.0}),
+    ("casual", Level {depth : 3, nodes : 10000, noise : 2.0, temperature : 1.0}),
+    ("intermediate", Level {depth : 5, nodes : 100000, noise : 1.0, temperature : 0.5}),
+    ("advanced", Level {depth : 7, nodes : 1000000, noise : 0.0, temperature : 0.2}),
+];
+
+impl Level {
+    /// # Returns
+    /// a level named `name` or None.
+    pub fn from(name : &str) -> Option<Level> {
+        LEVELS.iter().find(|(nm, _)| *nm == name).map(|(_, lv)| *lv)
+    }
+}
+
+/// # Returns
+/// index of a score chosen by softmax w/ `temperature` or the best one.
+fn softmax(scores : &[f32], temperature : f32, rng : &mut impl Rng) -> usize {
+    let best = scores.iter().enumerate()
+        .max_by(|a, b| a.1.partial_cmp(b.1).unwrap()).unwrap();
+    if temperature <= 0.0 {return best.0;}
+
+    let weights = scores.iter()
+        .map(|s| ((s - best.1) / temperature).exp()).collect::<Vec<_>>();
+    rng.sample(WeightedIndex::new(&weights).unwrap())
+}
+
+/// a level w/ its own random numbers.
+pub struct Strength {
+    level : Level,
+    rng : rand::rngs::StdRng,
+}
+
+impl Strength {
+    /// # Arguments
+    /// - `level` : level.
+    /// - `seed` : the same seed chooses the same moves.
+    pub fn new(level : Level, seed : u64) -> Strength {
+        Strength {
+            level,
+            rng : rand::rngs::StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// search every legal move deeper while the total # of nodes is within the limit.
+    /// the search is not deepened in the endgame not to play exact endgames.
+    ///
+    /// # Returns
+    /// ([(move, value from black's view, searched tree)], # of nodes, depth).
+    fn root_scores(&self, ban : &bitboard::BitBoard, wei : &weight::Weight,
+            tt : &mut transptable::TranspositionTable)
+            -> (Vec<(u8, f32, nodebb::NodeBB)>, usize, u8) {
+        let mut nodes = 0;
+        let mut scores = Vec::new();
+        let mut depth = 0;
+        let mut last = 0;
+        for d in 1..=self.level.depth.max(1) {
+            scores = nodebb::NodeBB::think_every_move(ban, d, wei, tt);
+            let n = scores.iter().map(|(_, _, nd)| nd.kyokumen).sum::<usize>();
+            nodes += n;
+            depth = d;
+            if self.level.nodes == 0 {continue;}
+
+            // 次の深さは今の深さの何倍かかるかで見積もる
+            let next = (n * n).checked_div(last).unwrap_or(
+                n * ban.legal_moves().count_ones() as usize);
+            if nodes + next > self.level.nodes {break;}
+            last = n;
+        }
+        (scores, nodes, depth)
+    }
+
+    /// choose a move on `ban` w/ legal moves.
+    ///
+    /// # Returns
+    /// (value of the chosen move from black's view, tree w/ the chosen move as the best).
+    pub fn search(&mut self, ban : &bitboard::BitBoard, wei : &weight::Weight,
+            tt : &mut transptable::TranspositionTable) -> (f32, nodebb::NodeBB) {
+        let (mut scores, nodes, depth) = self.root_scores(ban, wei, tt);
+        let fteban = ban.teban as f32;
+        let noisy = scores.iter().map(|(_, val, _)| {
+            let noise = self.rng.sample::<f64, _>(StandardNormal) as f32 * self.level.noise;
+            val * fteban + noise
+        }).collect::<Vec<_>>();
+        let idx = softmax(&noisy, self.level.temperature, &mut self.rng);
+        let (xy, val, nd) = scores.swap_remove(idx);
+
+        let mut root = nodebb::NodeBB::root(depth);
+        root.kyokumen = nodes;
+        root.hyoka = Some(val);
+        root.best = Some(nodebb::Best::new(val, xy));
+        root.child.push(nd);
+        (val, root)
+    }
+}
+
+#[test]
+fn test_level() {
+    assert_eq!(Level::from("casual").unwrap().depth, 3);
+    assert!(Level::from("god").is_none());
+    // 弱いほど浅くてばらつく
+    for w in LEVELS.windows(2) {
+        assert!(w[0].1.depth <= w[1].1.depth);
+        assert!(w[0].1.temperature > w[1].1.temperature);
+    }
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    assert_eq!(softmax(&[1.0, 3.0, 2.0], 0.0, &mut rng), 1);
+    // 差が大きいとほぼ最善
+    let n = (0..100).filter(|_| softmax(&[0.0, 30.0], 1.0, &mut rng) == 1).count();
+    assert_eq!(n, 100);
+    let n = (0..1000).filter(|_| softmax(&[0.0, 0.0], 1.0, &mut rng) == 1).count();
+    assert!((400..600).contains(&n), "{n}");
+
+    let mut w = weight::Weight::new();
+    w.init();
+    let ban = bitboard::BitBoard::new();
+    let moves = |seed| {
+        let mut st = Strength::new(Level::from("beginner").unwrap(), seed);
+        let mut tt = transptable::TranspositionTable::with_capacity(1024);
+        (0..10).map(|_| st.search(&ban, &w, &mut tt).1.best.unwrap().xypos())
+            .collect::<Vec<_>>()
+    };
+    // 同じseedなら同じ手
+    let m1 = moves(5);
+    assert_eq!(m1, moves(5));
+    assert!(m1.iter().all(|xy| ban.legal_moves() & (bitboard::LSB_CELL << xy) != 0));
+    assert!(m1.iter().any(|xy| *xy != m1[0]));
+
+    let mut st = Strength::new(Level::from("casual").unwrap(), 1);
+    let mut tt = transptable::TranspositionTable::with_capacity(1024);
+    let (val, node) = st.search(&ban, &w, &mut tt);
+    assert_eq!(node.hyoka, Some(val));
+    assert!(node.kyokumen > 4);
+    assert_eq!(node.best_order().len() % 2, 0);
+    assert!(!node.best_order().is_empty());
+
+    // 終盤でも読み切らずに節点数の上限に近いところで止める
+    let ban = bitboard::BitBoard::from(
+        "aAaAaAaA/AaAaAaAa/aCaC/AcAc/b5/BbBb/dD/Dd w").unwrap();
+    for (_, lv) in LEVELS.iter().take(3) {
+        let (_, nodes, depth) = Strength::new(*lv, 1).root_scores(&ban, &w, &mut tt);
+        assert!(depth <= lv.depth);
+        assert!(nodes <= lv.nodes || depth == 1, "{nodes} {depth}");
+    }
+}
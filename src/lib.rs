@@ -1,4 +1,5 @@
 use std::fs::File;
 use std::io::Write;
 pub mod bitboard;
+pub mod rng;
 pub mod weight;
@@ -5,20 +5,32 @@ use std::thread;
 use rand::Rng;
 use std::sync::{Arc, Mutex};
 
+mod analyze;
 mod cassio;
 mod bitboard;
+mod dataset;
 mod duelresult;
 mod edaxrunner;
+mod endgame;
+mod evalcheck;
 // mod extractrfen;
 mod game;
 mod gtprotocol;
 mod initialpos;
 mod myoption;
 mod nodebb;
+mod optimizer;
 mod kifu;
-// mod trainer;
+mod level;
+mod perft;
+mod player;
+mod rng;
+mod selfplay;
+mod trainer;
 mod transptable;
+mod tui;
 mod weight;
+mod wthor;
 
 
 /// global settings.
@@ -84,6 +96,11 @@ fn verbose(rfen : &str, depth : u8,
     let val = f(&ban, depth, &mut node, wei, &mut tt).unwrap();
     let ft = st.elapsed();
     println!("val:{val:.4?} {node} {}msec", ft.as_millis());
+    if let Some(method) = MYOPT.get().unwrap().explain {
+        if let Err(msg) = explain(&ban, wei, method) {
+            eprintln!("{msg}");
+        }
+    }
     if let Some(path) = treepath {
         if let Err(e) = node.dumptree(0, path) {
             eprintln!("{e}@{} {}", file!(), line!());
@@ -105,32 +122,69 @@ fn verbose(rfen : &str, depth : u8,
     }
 }
 
+/// show contributions of every stone to the evaluation.
+/// the table in --ev2 is also explained to compare when it is specified.
+/// # Arguments
+/// - ban : board to be explained.
+/// - wei : eval table.
+/// - method : how to attribute the value to cells.
+/// # Returns
+/// Ok(()) for success, otherwise Err(error message).
+fn explain(ban : &bitboard::BitBoard, wei : &weight::Weight,
+        method : weight::Attribution) -> Result<(), String> {
+    let mut list = vec![wei.explain(ban, method)];
+    let ev2 = &MYOPT.get().unwrap().evaltable2;
+    if !ev2.is_empty() {
+        let mut w2 = weight::Weight::new();
+        w2.read(ev2)?;
+        list.push(w2.explain(ban, method));
+    }
+
+    for (i, exp) in list.iter().enumerate() {
+        println!("ev{}:", i + 1);
+        print!("{}", exp.to_grid(ban));
+    }
+
+    if let Some(path) = &MYOPT.get().unwrap().explainjson {
+        let json = format!("[{}]\n",
+            list.iter().map(|exp| exp.to_json()).collect::<Vec<_>>().join(","));
+        let mut f = File::create(path).map_err(|e| e.to_string())?;
+        f.write_all(json.as_bytes()).map_err(|e| e.to_string())?;
+        println!("put explanation into {path}.");
+    }
+    Ok(())
+}
+
 fn genkifu_single(rfentbl : &[String], depth : u8, grp : &str, cachesz: usize) {
-    let think = MYOPT.get().unwrap().think.as_str();
-    let f = match think {
-        "" | "ab" => {
-            // nodebb::NodeBB::think_mtdf
-            nodebb::NodeBB::think_ab_simple_gk_tt
-            // nodebb::NodeBB::think_ab_simple
-        },
-        "all" => {
-            nodebb::NodeBB::think_simple_gk_tt
-        },
-        _ => { panic!("unknown thinking method.") }
-    };
+    let searchval = MYOPT.get().unwrap().searchval;
+    let verbose = MYOPT.get().unwrap().verbose;
+    let f = searchfn();
     for (idx, rfen) in rfentbl.iter().enumerate() {
         // prepare game
         let mut g = game::GameBB::from(rfen);
-        g.set_cachesize(cachesz);
-        // play
-        g.startgk(f, depth).unwrap();
-        let kifutxt = g.kifu.to_str();
+        g.set_recordval(searchval);
+        g.kifu.set_game_info("ruversi", "ruversi", depth, thinkname());
+        g.kifu.set_info("blackeval", &evalpath());
+        g.kifu.set_info("whiteeval", &evalpath());
+        // play. only the result is shown.
+        let mut listener = |ev : &game::Event| {
+            if let game::Event::End(..) = ev {game::show(&verbose, ev);}
+        };
+        let mut engine = player::Engine::new(f, depth, weight(), cachesz);
+        if levels().is_empty() {
+            g.start_with(&mut [&mut engine], &mut listener).unwrap();
+        } else {
+            // 対局ごとに別のseed
+            let seed = levelseed().wrapping_add(
+                grp.parse::<u64>().unwrap_or(0) * 1000000 + idx as u64 * 2);
+            let mut white = player::Engine::new(f, depth, weight(), cachesz);
+            setlevel(&mut engine, &mut g.kifu, 0, seed, bitboard::SENTE);
+            setlevel(&mut white, &mut g.kifu, 1, seed + 1, bitboard::GOTE);
+            g.start_with(&mut [&mut engine, &mut white], &mut listener).unwrap();
+        }
 
         // store kifu
-        let kifuname = format!("./kifu/kifu{grp}{idx:05}.txt");
-        let mut f = File::create(kifuname).unwrap();
-        let content = format!("{}{kifutxt}", kifu::HEADER);
-        f.write_all(content.as_bytes()).unwrap();
+        g.kifu.save(&format!("./kifu/kifu{grp}{idx:05}.txt")).unwrap();
     }
 }
 
@@ -153,6 +207,13 @@ fn genkifu_para(rfentbl : &[String], depth : u8, grp : &str, cachesz: usize) {
 /// # Arguments
 /// - n : None or Some(0 - 19). index in 20 group.
 fn gen_kifu(n : Option<usize>, depth : u8, cachesz : usize) {
+    // --rfen or --moves
+    let rfen = &MYOPT.get().unwrap().rfen;
+    if !rfen.is_empty() {
+        genkifu_single(std::slice::from_ref(rfen), depth, &format!("{:02}", n.unwrap_or(0)), cachesz);
+        return;
+    }
+
     let mut ip = initialpos::InitialPos::read(initialpos::INITIALPOSFILE).unwrap();
     ip.append(initialpos::INITIALPOSFILE7).unwrap();
     let rfentbl_src =
@@ -175,6 +236,29 @@ fn gen_kifu(n : Option<usize>, depth : u8, cachesz : usize) {
     // genkifu_single(rfentbl, depth, &format!("{grp:02}"), cachesz);
 }
 
+/// play a game between 2 eval tables.
+/// # Arguments
+/// - rfen : initial position.
+/// - initpos : "<equal file>#<index>".
+/// - black : (eval table, its path) for SENTE.
+/// - white : (eval table, its path) for GOTE.
+/// # Returns
+/// kifu w/ information about the game.
+fn duel_game(rfen : &str, initpos : &str, depth : u8,
+        black : (&weight::Weight, &str), white : (&weight::Weight, &str),
+        cachesz : usize, verbose : &myoption::Verbose) -> kifu::Kifu {
+    let mut g = game::GameBB::from(rfen);
+    g.set_verbose(verbose);
+    g.kifu.set_game_info("ruversi", "ruversi", depth, thinkname());
+    g.kifu.set_info("blackeval", black.1);
+    g.kifu.set_info("whiteeval", white.1);
+    g.kifu.set_info("initpos", initpos);
+    let mut sente = player::Engine::new(searchfn(), depth, black.0, cachesz);
+    let mut gote = player::Engine::new(searchfn(), depth, white.0, cachesz);
+    g.start(&mut sente, &mut gote).unwrap();
+    g.kifu
+}
+
 /// duel between 2 eval tables.
 /// # Arguments
 /// - ev1 : eval table 1.
@@ -202,105 +286,43 @@ fn duel_para(ev1 : &str, ev2 : &str, duellv : i8, depth : u8, cachesz : usize) {
     let rfentbl = &mut ip.rfens_all();
     let n = rfentbl.len() / 2;
     let rfen1 = rfentbl.drain(n..).collect::<Vec<String>>();
-    let think = MYOPT.get().unwrap().think.as_str();
 
-    let f = match think {
-        "" | "ab" => {
-            // nodebb::NodeBB::think_mtdf
-            nodebb::NodeBB::think_ab_simple_gk_tt
-            // nodebb::NodeBB::think_ab_simple
-        },
-        "all" => {
-            nodebb::NodeBB::think_simple_gk_tt
-        },
-        _ => { panic!("unknown thinking method.") }
-    };
+    let (ev3, ev4, eqfile2) = (ev1.to_string(), ev2.to_string(), eqfile.clone());
     let thrd = thread::spawn(move || {
-        for rfen in rfen1.iter() {
-            // prepare game
-            let mut g = game::GameBB::from(rfen);
-            g.set_cachesize(cachesz);
-            g.set_verbose(&verbose);
-            // play
-            match think {
-                "" | "ab" => {
-                    g.starto_with_2et_mt_tt(f, depth, &w3, &w4).unwrap()
-                },
-                "all" => {
-                    g.starto_with_2et(f, depth, &w3, &w4).unwrap()
-                },
-                _ => { panic!("unknown thinking method.") }
-            }
-            let result = g.kifu.winner().unwrap();
+        for (i, rfen) in rfen1.iter().enumerate() {
+            let initpos = format!("{eqfile2}#{}", n + i);
+            let kifu = duel_game(rfen, &initpos, depth, (&w3, &ev3), (&w4, &ev4),
+                cachesz, &verbose);
+            save_duel_kifu(&kifu, &format!("kifuev{:05}b", n + i));
             {
                 let mut dr = dresult2.lock().unwrap();
-                dr.sresult(result);
+                dr.sresult(kifu.winner().unwrap());
             }
 
-            // prepare game
-            let mut g = game::GameBB::from(rfen);
-            g.set_cachesize(cachesz);
-            g.set_verbose(&verbose);
-            // play
-            let think = MYOPT.get().unwrap().think.as_str();
-            match think {
-                "" | "ab" => {
-                    g.starto_with_2et_mt_tt(f, depth, &w4, &w3).unwrap()
-                },
-                "all" => {
-                    g.starto_with_2et(f, depth, &w4, &w3).unwrap()
-                },
-                _ => { panic!("unknown thinking method.") }
-            }
-            let result = g.kifu.winner().unwrap();
+            let kifu = duel_game(rfen, &initpos, depth, (&w4, &ev4), (&w3, &ev3),
+                cachesz, &verbose);
+            save_duel_kifu(&kifu, &format!("kifuev{:05}w", n + i));
             {
                 let mut dr = dresult2.lock().unwrap();
-                dr.gresult(result);
+                dr.gresult(kifu.winner().unwrap());
                 println!("{dr}");
             }
         }});
 
-    for rfen in rfentbl.iter() {
-        // prepare game
-        let mut g = game::GameBB::from(rfen);
-        g.set_cachesize(cachesz);
-        g.set_verbose(&verbose);
-        // play
-        let think = MYOPT.get().unwrap().think.as_str();
-        match think {
-            "" | "ab" => {
-                g.starto_with_2et_mt_tt(f, depth, &w2, &w1).unwrap()
-            },
-            "all" => {
-                g.starto_with_2et(f, depth, &w1, &w2).unwrap()
-            },
-            _ => { panic!("unknown thinking method.") }
-        }
-        let result = g.kifu.winner().unwrap();
+    for (i, rfen) in rfentbl.iter().enumerate() {
+        let initpos = format!("{eqfile}#{i}");
+        let kifu = duel_game(rfen, &initpos, depth, (&w1, ev1), (&w2, ev2), cachesz, &verbose);
+        save_duel_kifu(&kifu, &format!("kifuev{i:05}b"));
         {
             let mut dr = dresult.lock().unwrap();
-            dr.sresult(result);
+            dr.sresult(kifu.winner().unwrap());
         }
 
-        // prepare game
-        let mut g = game::GameBB::from(rfen);
-        g.set_cachesize(cachesz);
-        g.set_verbose(&verbose);
-        // play
-        let think = MYOPT.get().unwrap().think.as_str();
-        match think {
-            "" | "ab" => {
-                g.starto_with_2et_mt_tt(f, depth, &w2, &w1).unwrap()
-            },
-            "all" => {
-                g.starto_with_2et(f, depth, &w2, &w1).unwrap()
-            },
-            _ => { panic!("unknown thinking method.") }
-        }
-        let result = g.kifu.winner().unwrap();
+        let kifu = duel_game(rfen, &initpos, depth, (&w2, ev2), (&w1, ev1), cachesz, &verbose);
+        save_duel_kifu(&kifu, &format!("kifuev{i:05}w"));
         {
             let mut dr = dresult.lock().unwrap();
-            dr.gresult(result);
+            dr.gresult(kifu.winner().unwrap());
             println!("{dr}");
         }
     }
@@ -330,15 +352,36 @@ fn duel(ev1 : &str, ev2 : &str, duellv : i8, depth : u8, cachesz : usize) {
     let mut w2 = weight::Weight::new();
     w2.read(ev2).unwrap();
     let mut dr = duelresult::DuelResult::default();
-    let mut result;
 
     let verbose = MYOPT.get().unwrap().verbose;
     let eqfile = initialpos::equalfile(duellv);
     println!("equal file: {eqfile}");
     let ip = initialpos::InitialPos::read(&eqfile).unwrap();
     let rfentbl = &ip.rfens_all();
+    for (i, rfen) in rfentbl.iter().enumerate() {
+        let initpos = format!("{eqfile}#{i}");
+        let kifu = duel_game(rfen, &initpos, depth, (&w1, ev1), (&w2, ev2), cachesz, &verbose);
+        save_duel_kifu(&kifu, &format!("kifuev{i:05}b"));
+        dr.sresult(kifu.winner().unwrap());
+        let kifu = duel_game(rfen, &initpos, depth, (&w2, ev2), (&w1, ev1), cachesz, &verbose);
+        save_duel_kifu(&kifu, &format!("kifuev{i:05}w"));
+        dr.gresult(kifu.winner().unwrap());
+
+        println!("{dr}");
+    }
+    println!("ev1:{}", MYOPT.get().unwrap().evaltable1);
+    println!("ev2:{}", MYOPT.get().unwrap().evaltable2);
+}
+
+/// name of the search algorithm in --thinkab or --thinkall.
+fn thinkname() -> &'static str {
     let think = MYOPT.get().unwrap().think.as_str();
-    let f = match think {
+    if think.is_empty() {"ab"} else {think}
+}
+
+/// fn for searching in --thinkab or --thinkall.
+fn searchfn() -> game::SearchFn {
+    match MYOPT.get().unwrap().think.as_str() {
         "" | "ab" => {
             // nodebb::NodeBB::think_mtdf
             nodebb::NodeBB::think_ab_simple_gk_tt
@@ -348,31 +391,129 @@ fn duel(ev1 : &str, ev2 : &str, duellv : i8, depth : u8, cachesz : usize) {
             nodebb::NodeBB::think_simple_gk_tt
         },
         _ => { panic!("unknown thinking method.") }
+    }
+}
+
+/// the eval table read at the start.
+fn weight() -> &'static weight::Weight {
+    let src = unsafe{&*std::ptr::addr_of!(nodebb::WEIGHT)};
+    src.as_ref().unwrap()
+}
+
+/// play a game against another player w/ the eval table read at the start.
+/// # Arguments
+/// - g : game.
+/// - opponent : another player.
+/// - turn : opponent's turn.
+/// - depth : searching depth.
+/// - cachesz : size of the transposition table.
+/// - seed : seed for --level. None ignores --level.
+fn start_against(g : &mut game::GameBB, opponent : &mut dyn player::Player, turn : i8,
+        depth : u8, cachesz : usize, seed : Option<u64>) -> Result<(), String> {
+    let mut engine = player::Engine::new(searchfn(), depth, weight(), cachesz);
+    if let Some(seed) = seed {
+        setlevel(&mut engine, &mut g.kifu, 0, seed, -turn);
+    }
+    if turn == bitboard::SENTE {
+        g.start(opponent, &mut engine)
+    } else {
+        g.start(&mut engine, opponent)
+    }
+}
+
+/// levels by --level. [the engine] or [black, white] in --genkifu.
+fn levels() -> Vec<(&'static str, level::Level)> {
+    MYOPT.get().unwrap().level.split(',').filter(|nm| !nm.is_empty())
+        .map(|nm| (nm, level::Level::from(nm).unwrap())).collect()
+}
+
+/// seed for --level by --levelseed or a random one shown to reproduce games.
+fn levelseed() -> u64 {
+    static SEED : once_cell::sync::OnceCell<u64> = once_cell::sync::OnceCell::new();
+    *SEED.get_or_init(|| {
+        MYOPT.get().unwrap().levelseed.unwrap_or_else(|| {
+            let seed = rng::rng().gen::<u32>() as u64;
+            if !levels().is_empty() {println!("level seed: {seed}");}
+            seed
+        })
+    })
+}
+
+/// choose moves of `engine` by --level and record it in `kifu`.
+/// # Arguments
+/// - idx : index in levels(). the last one is used if it is out of range.
+/// - seed : seed for the level.
+/// - turn : engine's turn.
+fn setlevel(engine : &mut player::Engine, kifu : &mut kifu::Kifu, idx : usize, seed : u64,
+        turn : i8) {
+    let lvs = levels();
+    let Some((name, lv)) = lvs.get(idx.min(lvs.len().saturating_sub(1))) else {return;};
+    engine.set_strength(level::Strength::new(*lv, seed));
+    let key = if turn == bitboard::SENTE {"blacklevel"} else {"whitelevel"};
+    kifu.set_info(key, &format!("{name} seed {seed}"));
+}
+
+/// path of the eval table read at the start.
+fn evalpath() -> String {
+    let path = &MYOPT.get().unwrap().evaltable1;
+    if !path.is_empty() {
+        path.clone()
+    } else if std::path::Path::new("data/evaltable.txt").exists() {
+        String::from("data/evaltable.txt")
+    } else {
+        String::from("RANDOM")
+    }
+}
+
+/// record a game against an external program in the kifu header.
+/// # Arguments
+/// - kifu : game record.
+/// - opponent : name of the opponent.
+/// - turn : opponent's turn.
+/// - depth : searching depth of ruversi.
+/// - initpos : "<equal file>#<index>".
+fn set_duel_info(kifu : &mut kifu::Kifu, opponent : &str, turn : i8, depth : u8,
+        initpos : &str) {
+    let (black, white, key) = if turn == bitboard::SENTE {
+        (opponent, "ruversi", "whiteeval")
+    } else {
+        ("ruversi", opponent, "blackeval")
     };
-    for rfen in rfentbl.iter() {
-        // prepare game
-        let mut g = game::GameBB::from(rfen);
-        g.set_cachesize(cachesz);
-        g.set_verbose(&verbose);
-        // play
-        g.starto_with_2et(f, depth, &w1, &w2).unwrap();
-        let dresult = g.kifu.winner();
-        result = dresult.unwrap();
-        dr.sresult(result);
-        // prepare game
-        let mut g = game::GameBB::from(rfen);
-        g.set_cachesize(cachesz);
-        g.set_verbose(&verbose);
-        // play
-        g.starto_with_2et(f, depth, &w2, &w1).unwrap();
-        let dresult = g.kifu.winner();
-        result = dresult.unwrap();
-        dr.gresult(result);
+    kifu.set_game_info(black, white, depth, thinkname());
+    kifu.set_info(key, &evalpath());
+    kifu.set_info("initpos", initpos);
+}
 
-        println!("{dr}");
+/// put a duel game into --kifudir/duel/ as a kifu and show its path
+/// so that the duel log can be matched w/ the kifu.
+/// # Arguments
+/// - kifu : game record.
+/// - name : file name w/o ".txt". ex. "kifuedax00012w" for ruversi or ev1 as white
+///   on the 12th position.
+fn save_duel_kifu(kifu : &kifu::Kifu, name : &str) {
+    let dir = std::path::Path::new(&MYOPT.get().unwrap().kifudir).join("duel");
+    let path = dir.join(format!("{name}.txt"));
+    let path = path.to_str().unwrap();
+    let res = std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {e}", dir.display()))
+        .and_then(|_| kifu.save(path));
+    match res {
+        Ok(()) => println!("kifu: {path}"),
+        Err(msg) => eprintln!("failed to save a kifu. {msg}"),
+    }
+}
+
+/// append a game against an external program to the file of --ggf in GGF.
+/// # Arguments
+/// - kifu : game record.
+fn save_ggf(kifu : &kifu::Kifu) {
+    let path = &MYOPT.get().unwrap().ggf;
+    if path.is_empty() {return;}
+
+    let res = std::fs::OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut f| writeln!(f, "{}", kifu.to_ggf()));
+    if let Err(e) = res {
+        eprintln!("failed to write {path}. ({e})");
     }
-    println!("ev1:{}", MYOPT.get().unwrap().evaltable1);
-    println!("ev2:{}", MYOPT.get().unwrap().evaltable2);
 }
 
 /// duel between 2 eval tables.
@@ -390,40 +531,34 @@ fn duel_vs_edax(duellv : i8, depth : u8, cachesz : usize) {
 
     let econf = std::path::PathBuf::from(
             MYOPT.get().unwrap().edaxconfig.as_str());
-    let think = MYOPT.get().unwrap().think.as_str();
     let verbose = MYOPT.get().unwrap().verbose;
     let eqfile = initialpos::equalfile(duellv);
     println!("equal file: {eqfile}");
     let ip = initialpos::InitialPos::read(&eqfile).unwrap();
-    let f = match think {
-        "" | "ab" => {
-            // nodebb::NodeBB::think_mtdf
-            nodebb::NodeBB::think_ab_simple_gk_tt
-            // nodebb::NodeBB::think_ab_simple
-        },
-        "all" => {
-            nodebb::NodeBB::think_simple_gk_tt
-        },
-        _ => { panic!("unknown thinking method.") }
-    };
     let rfentbl = &ip.rfens_all();
-    for rfen in rfentbl.iter() {
+    for (i, rfen) in rfentbl.iter().enumerate() {
         let turn = bitboard::SENTE;
         // prepare game
         let mut g = game::GameBB::from(rfen);
-        g.set_cachesize(cachesz);
         g.set_verbose(&verbose);
+        set_duel_info(&mut g.kifu, "edax", turn, depth, &format!("{eqfile}#{i}"));
         // play
-        g.starto_against_edax(f, depth, turn, &econf).unwrap();
+        let er = edaxrunner::EdaxRunner::from_config(&econf).unwrap();
+        start_against(&mut g, &mut player::Edax::new(er), turn, depth, cachesz, None).unwrap();
+        save_ggf(&g.kifu);
+        save_duel_kifu(&g.kifu, &format!("kifuedax{i:05}w"));
         dresult = g.kifu.winner();
         result = dresult.unwrap();
         dr.sresult(result);
         let turn = bitboard::GOTE;
         // prepare game
         let mut g = game::GameBB::from(rfen);
-        g.set_cachesize(cachesz);
         g.set_verbose(&verbose);
-        g.starto_against_edax(f, depth, turn, &econf).unwrap();
+        set_duel_info(&mut g.kifu, "edax", turn, depth, &format!("{eqfile}#{i}"));
+        let er = edaxrunner::EdaxRunner::from_config(&econf).unwrap();
+        start_against(&mut g, &mut player::Edax::new(er), turn, depth, cachesz, None).unwrap();
+        save_ggf(&g.kifu);
+        save_duel_kifu(&g.kifu, &format!("kifuedax{i:05}b"));
         dresult = g.kifu.winner();
         result = dresult.unwrap();
         dr.gresult(result);
@@ -447,31 +582,22 @@ fn duel_vs_cassio(duellv : i8, depth : u8, cachesz : usize) {
 
     let econf = MYOPT.get().unwrap().edaxconfig.as_str();
     // println!("econf:{econf}");
-    let think = MYOPT.get().unwrap().think.as_str();
     let verbose = MYOPT.get().unwrap().verbose;
     let eqfile = initialpos::equalfile(duellv);
     println!("equal file: {eqfile}");
     let ip = initialpos::InitialPos::read(&eqfile).unwrap();
-    let f = match think {
-        "" | "ab" => {
-            // nodebb::NodeBB::think_mtdf
-            nodebb::NodeBB::think_ab_simple_gk_tt
-            // nodebb::NodeBB::think_ab_simple
-        },
-        "all" => {
-            nodebb::NodeBB::think_simple_gk_tt
-        },
-        _ => { panic!("unknown thinking method.") }
-    };
     let rfentbl = &ip.rfens_all();
-    for rfen in rfentbl.iter() {
+    for (i, rfen) in rfentbl.iter().enumerate() {
         let turn = bitboard::SENTE;
         // prepare game
         let mut g = game::GameBB::from(rfen);
-        g.set_cachesize(cachesz);
         g.set_verbose(&verbose);
+        set_duel_info(&mut g.kifu, "cassio", turn, depth, &format!("{eqfile}#{i}"));
         // play
-        g.start_against_via_cassio(f, depth, turn, econf).unwrap();
+        let mut cassio = player::Cassio::new(econf, depth, verbose).unwrap();
+        start_against(&mut g, &mut cassio, turn, depth, cachesz, None).unwrap();
+        save_ggf(&g.kifu);
+        save_duel_kifu(&g.kifu, &format!("kifucassio{i:05}w"));
         dresult = g.kifu.winner();
         result = dresult.unwrap();
         dr.sresult(result);
@@ -479,10 +605,13 @@ fn duel_vs_cassio(duellv : i8, depth : u8, cachesz : usize) {
         let turn = bitboard::GOTE;
         // prepare game
         let mut g = game::GameBB::from(rfen);
-        g.set_cachesize(cachesz);
         g.set_verbose(&verbose);
+        set_duel_info(&mut g.kifu, "cassio", turn, depth, &format!("{eqfile}#{i}"));
         // play
-        g.start_against_via_cassio(f, depth, turn, econf).unwrap();
+        let mut cassio = player::Cassio::new(econf, depth, verbose).unwrap();
+        start_against(&mut g, &mut cassio, turn, depth, cachesz, None).unwrap();
+        save_ggf(&g.kifu);
+        save_duel_kifu(&g.kifu, &format!("kifucassio{i:05}b"));
         dresult = g.kifu.winner();
         result = dresult.unwrap();
         dr.gresult(result);
@@ -505,29 +634,23 @@ fn duel_vs_ruversi(duellv : i8, depth : u8, cachesz : usize) {
     let mut result;
 
     let econf = MYOPT.get().unwrap().edaxconfig.as_str();
-    let think = MYOPT.get().unwrap().think.as_str();
     let verbose = MYOPT.get().unwrap().verbose;
     let eqfile = initialpos::equalfile(duellv);
     println!("equal file: {eqfile}");
     let ip = initialpos::InitialPos::read(&eqfile).unwrap();
     let rfentbl = &ip.rfens_all();
-    let f = match think {
-        "" | "ab" => {
-            // nodebb::NodeBB::think_mtdf
-            nodebb::NodeBB::think_ab_simple_gk_tt
-        },
-        "all" => {
-            nodebb::NodeBB::think_simple_gk_tt
-        },
-        _ => { panic!("unknown thinking method.") }
-        };
-    for rfen in rfentbl.iter() {
+    for (i, rfen) in rfentbl.iter().enumerate() {
         let turn = bitboard::SENTE;
         // prepare game
         let mut g = game::GameBB::from(rfen);
-        g.set_cachesize(cachesz);
         g.set_verbose(&verbose);
-        g.starto_against_ruversi(f, depth, turn, econf).unwrap();
+        set_duel_info(&mut g.kifu, "ruversi", turn, depth, &format!("{eqfile}#{i}"));
+        let mut rr = edaxrunner::RuversiRunner::from_config(
+                &std::path::PathBuf::from(econf)).unwrap();
+        rr.set_verbose(verbose.is_verbose());
+        start_against(&mut g, &mut player::Ruversi::new(rr), turn, depth, cachesz, None).unwrap();
+        save_ggf(&g.kifu);
+        save_duel_kifu(&g.kifu, &format!("kifuruversi{i:05}w"));
         dresult = g.kifu.winner();
         result = dresult.unwrap();
         dr.sresult(result);
@@ -535,10 +658,15 @@ fn duel_vs_ruversi(duellv : i8, depth : u8, cachesz : usize) {
         let turn = bitboard::GOTE;
         // prepare game
         let mut g = game::GameBB::from(rfen);
-        g.set_cachesize(cachesz);
         g.set_verbose(&verbose);
+        set_duel_info(&mut g.kifu, "ruversi", turn, depth, &format!("{eqfile}#{i}"));
         // play
-        g.starto_against_ruversi(f, depth, turn, econf).unwrap();
+        let mut rr = edaxrunner::RuversiRunner::from_config(
+                &std::path::PathBuf::from(econf)).unwrap();
+        rr.set_verbose(verbose.is_verbose());
+        start_against(&mut g, &mut player::Ruversi::new(rr), turn, depth, cachesz, None).unwrap();
+        save_ggf(&g.kifu);
+        save_duel_kifu(&g.kifu, &format!("kifuruversi{i:05}b"));
         dresult = g.kifu.winner();
         result = dresult.unwrap();
         dr.gresult(result);
@@ -561,28 +689,147 @@ fn readeval(path: &str) {
     }
 }
 
+/// train the eval table w/ kifus in kifu/.
+/// the eval table is saved as kifu/newevaltable.txt.
+fn learn() -> Result<(), String> {
+    let opt = MYOPT.get().unwrap();
+    let eta = opt.eta.unwrap_or(trainer::DEFAULT_ETA);
+    let repeat = opt.repeat.unwrap_or(trainer::DEFAULT_REPEAT);
+    let mut tr = trainer::Trainer::new(eta, repeat, "kifu/");
+    tr.read_opt_out(&opt.outtrain)?;
+    tr.set_progress(&opt.progress);
+    tr.set_target(opt.target);
+    tr.set_validation(opt.valsplit);
+    tr.set_schedule(opt.lrsched);
+    tr.set_optimizer(optimizer::Optimizer::new(opt.optimizer, opt.wdecay, opt.layerlr));
+    tr.set_patience(opt.patience);
+    if !opt.checkpoint.is_empty() {
+        tr.set_checkpoint(&opt.checkpoint, opt.resume);
+    }
+    tr.set_log("kifu/trainlog.csv");
+    if opt.trmode == myoption::TrainingMode::MiniBatch {
+        tr.set_minibatch(opt.minibsize);
+    }
+
+    let mut wei = weight::Weight::new();
+    wei.copy(weight());
+    let st = Instant::now();
+    let mse = tr.learn(&mut wei)?;
+    println!("mse:{mse}");
+    if tr.need_summay() {
+        println!("{}", tr.fmt_result());
+    }
+    if tr.need_time() {
+        println!("processing time: {}msec", st.elapsed().as_millis());
+    }
+    if tr.need_save() {
+        wei.writev11("kifu/newevaltable.txt")?;
+    }
+    Ok(())
+}
+
+/// solver for --exact.
+fn oracle() -> Result<endgame::Oracle, String> {
+    let opt = MYOPT.get().unwrap();
+    if opt.oracle != "edax" {
+        return Ok(endgame::Oracle::Internal);
+    }
+
+    if opt.edaxconfig.is_empty() {
+        return Ok(endgame::Oracle::Edax(edaxrunner::EdaxRunner::new()));
+    }
+    let econf = std::path::PathBuf::from(opt.edaxconfig.as_str());
+    Ok(endgame::Oracle::Edax(edaxrunner::EdaxRunner::from_config(&econf)?))
+}
+
+/// repeat self-play, training and duel in --history.
+fn selfplay_loop(depth : u8, cachesz : usize) -> Result<(), String> {
+    let opt = MYOPT.get().unwrap();
+    let sp = selfplay::SelfPlay {
+        history : opt.history.clone(),
+        generations : opt.selfplay,
+        games : opt.games,
+        depth,
+        cachesz,
+        search : searchfn(),
+        think : thinkname().to_string(),
+        opening : levels().first().map(|(nm, lv)| (nm.to_string(), *lv))
+            .unwrap_or_else(|| (selfplay::OPENING_LEVEL.to_string(),
+                level::Level::from(selfplay::OPENING_LEVEL).unwrap())),
+        duellv : opt.duellv,
+        gate : opt.gate,
+        eta : opt.eta.unwrap_or(trainer::DEFAULT_ETA),
+        repeat : opt.repeat.unwrap_or(trainer::DEFAULT_REPEAT),
+        minibsize : if opt.trmode == myoption::TrainingMode::MiniBatch {
+            opt.minibsize
+        } else {
+            1
+        },
+        target : opt.target,
+    };
+    sp.run(weight())
+}
+
+/// a game from --continue, --rfen or --moves if specified,
+/// otherwise from the initial position.
+fn newgame() -> game::GameBB {
+    let opt = MYOPT.get().unwrap();
+    if !opt.continued.is_empty() {
+        return kifu::load(&opt.continued).and_then(game::GameBB::from_kifu)
+            .unwrap_or_else(|msg| {
+                eprintln!("failed to continue {}. {msg}", opt.continued);
+                std::process::exit(1);
+            });
+    }
+    let rfen = &opt.rfen;
+    if rfen.is_empty() {game::GameBB::new()} else {game::GameBB::from(rfen)}
+}
+
+/// put the whole game continued from --continue into <path>_continued.txt.
+fn savecontinued(g : &game::GameBB) {
+    let path = &MYOPT.get().unwrap().continued;
+    if path.is_empty() {return;}
+
+    let p = std::path::Path::new(path);
+    let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("kifu");
+    let out = p.with_file_name(format!("{stem}_continued.txt"));
+    let out = out.to_str().unwrap();
+    match g.kifu.save(out) {
+        Ok(()) => println!("put the game into {out}."),
+        Err(msg) => eprintln!("{msg}"),
+    }
+}
+
 /// play a game ruversi vs you.
 /// # Arguments
 /// - depth : depth to think.
 /// - turnh : your turn.
 fn play(depth : u8, turnh: i8, cachesz : usize) {
+    let verbose = MYOPT.get().unwrap().verbose;
     // prepare game
-    let mut g = game::GameBB::new();
-    g.set_cachesize(cachesz);
+    let mut g = newgame();
+    g.set_verbose(&verbose);
     // play
-    let think = MYOPT.get().unwrap().think.as_str();
-    let f = match think {
-        "" | "ab" => {
-            // nodebb::NodeBB::think_mtdf
-            nodebb::NodeBB::think_ab_simple_gk_tt
-            // nodebb::NodeBB::think_ab_simple
-        },
-        "all" => {
-            nodebb::NodeBB::think_simple_gk_tt
-        },
-        _ => { panic!("unknown thinking method.") }
-    };
-    g.starto_against_stdin(f, depth, turnh).unwrap();
+    let mut human = player::Human::new(verbose);
+    human.set_engine(player::Engine::new(searchfn(), depth, weight(), cachesz));
+    start_against(&mut g, &mut human, turnh, depth, cachesz, Some(levelseed())).unwrap();
+    savecontinued(&g);
+}
+
+/// play games ruversi vs you on the terminal UI.
+/// # Arguments
+/// - depth : depth to think.
+/// - turnh : your turn.
+fn tui_play(depth : u8, turnh: i8, cachesz : usize) {
+    let mut g = newgame();
+    let mut engine = player::Engine::new(searchfn(), depth, weight(), cachesz);
+    setlevel(&mut engine, &mut g.kifu, 0, levelseed(), -turnh);
+    let helper = player::Engine::new(searchfn(), depth, weight(), cachesz);
+    if let Err(msg) = tui::run(&mut g, &mut engine, helper, turnh) {
+        eprintln!("{msg}");
+        std::process::exit(1);
+    }
+    savecontinued(&g);
 }
 
 /// play a game ruversi vs Edax.
@@ -590,25 +837,17 @@ fn play(depth : u8, turnh: i8, cachesz : usize) {
 /// - depth : depth to think.
 /// - turnh : Edax's turn.
 fn edax(depth : u8, turnh: i8, cachesz : usize) {
+    let verbose = MYOPT.get().unwrap().verbose;
     // prepare game
-    let mut g = game::GameBB::new();
-    g.set_cachesize(cachesz);
+    let mut g = newgame();
+    g.set_verbose(&verbose);
     // play
     let econf = std::path::PathBuf::from(
             MYOPT.get().unwrap().edaxconfig.as_str());
-    let think = MYOPT.get().unwrap().think.as_str();
-    let f = match think {
-        "" | "ab" => {
-            // nodebb::NodeBB::think_mtdf
-            nodebb::NodeBB::think_ab_simple_gk_tt
-            // nodebb::NodeBB::think_ab_simple
-        },
-        "all" => {
-            nodebb::NodeBB::think_simple_gk_tt
-        },
-        _ => { panic!("unknown thinking method.") }
-    };
-    g.starto_against_edax(f, depth, turnh, &econf).unwrap();
+    let er = edaxrunner::EdaxRunner::from_config(&econf).unwrap();
+    start_against(&mut g, &mut player::Edax::new(er), turnh, depth, cachesz, Some(levelseed()))
+        .unwrap();
+    savecontinued(&g);
 }
 
 /// play a game ruversi vs another ruversi.
@@ -618,24 +857,16 @@ fn edax(depth : u8, turnh: i8, cachesz : usize) {
 fn vs_ruversi(depth : u8, turnh: i8, cachesz : usize) {
     let verbose = MYOPT.get().unwrap().verbose;
     // prepare game
-    let mut g = game::GameBB::new();
-    g.set_cachesize(cachesz);
+    let mut g = newgame();
     g.set_verbose(&verbose);
     // play
     let econf = MYOPT.get().unwrap().edaxconfig.as_str();
-    let think = MYOPT.get().unwrap().think.as_str();
-    let f = match think {
-        "" | "ab" => {
-            // nodebb::NodeBB::think_mtdf
-            nodebb::NodeBB::think_ab_simple_gk_tt
-            // nodebb::NodeBB::think_ab_simple
-        },
-        "all" => {
-            nodebb::NodeBB::think_simple_gk_tt
-        },
-        _ => { panic!("unknown thinking method.") }
-    };
-    g.starto_against_ruversi(f, depth, turnh, econf).unwrap();
+    let mut rr = edaxrunner::RuversiRunner::from_config(
+            &std::path::PathBuf::from(econf)).unwrap();
+    rr.set_verbose(verbose.is_verbose());
+    start_against(&mut g, &mut player::Ruversi::new(rr), turnh, depth, cachesz, Some(levelseed()))
+        .unwrap();
+    savecontinued(&g);
 }
 
 /// show command options and exit(1).
@@ -817,6 +1048,9 @@ fn main() {
             Ok(mo) => {mo},
             Err(msg) => {panic!("{msg}")},
         }).unwrap();
+    if let Some(seed) = MYOPT.get().unwrap().seed {
+        rng::set_seed(seed);
+    }
 
     let mode = &MYOPT.get().unwrap().mode;
     if *mode == myoption::Mode::Help {
@@ -828,9 +1062,122 @@ fn main() {
     if *mode == myoption::Mode::Oep {
         oep();
     }
+    if *mode == myoption::Mode::Perft {
+        let opt = MYOPT.get().unwrap();
+        if let Err(msg) = perft::run(&opt.rfen, opt.perft, opt.divide) {
+            eprintln!("{msg}");
+        }
+        std::process::exit(0);
+    }
+    if *mode == myoption::Mode::ExportDataset {
+        let opt = MYOPT.get().unwrap();
+        let mut ds = dataset::Dataset::new(opt.augment);
+        ds.set_dedup(true);
+        ds.set_target(opt.target);
+        let res = oracle().and_then(|oracle| {
+            let exact = if opt.exact > 0 {Some((oracle, opt.exact))} else {None};
+            dataset::run(&mut ds, &opt.kifudir, &opt.dataset,
+                opt.dsformat, &opt.dsfields, opt.shardsize, exact)
+        });
+        if let Err(msg) = res {
+            eprintln!("{msg}");
+        }
+        std::process::exit(0);
+    }
+    if *mode == myoption::Mode::Label {
+        let opt = MYOPT.get().unwrap();
+        let res = if opt.exact == 0 {
+            Err(String::from("please specify --exact <empties>."))
+        } else {
+            oracle().and_then(|oracle| dataset::relabel(&opt.label, oracle, opt.exact))
+        };
+        if let Err(msg) = res {
+            eprintln!("{msg}");
+        }
+        std::process::exit(0);
+    }
+
+    if *mode == myoption::Mode::Wthor {
+        let opt = MYOPT.get().unwrap();
+        if let Err(msg) = wthor::run(&opt.wthor, &opt.kifudir, &opt.wtfilter) {
+            eprintln!("{msg}");
+        }
+        std::process::exit(0);
+    }
+    if *mode == myoption::Mode::FromGgf || *mode == myoption::Mode::FromMoves {
+        let opt = MYOPT.get().unwrap();
+        let res = if *mode == myoption::Mode::FromGgf {
+            kifu::import(&opt.ggf, &opt.kifudir, kifu::Format::Ggf)
+        } else {
+            kifu::import(&opt.transcript, &opt.kifudir, kifu::Format::Moves)
+        };
+        if let Err(msg) = res {
+            eprintln!("{msg}");
+        }
+        std::process::exit(0);
+    }
+    if *mode == myoption::Mode::ToGgf || *mode == myoption::Mode::ToMoves {
+        let opt = MYOPT.get().unwrap();
+        let res = if *mode == myoption::Mode::ToGgf {
+            kifu::export(&opt.ggf, kifu::Format::Ggf)
+        } else {
+            kifu::export(&opt.transcript, kifu::Format::Moves)
+        };
+        match res {
+            Ok(txt) => print!("{txt}"),
+            Err(msg) => eprintln!("{msg}"),
+        }
+        std::process::exit(0);
+    }
+    if *mode == myoption::Mode::CheckKifu {
+        // 不正な棋譜があればCIなどで分かるように失敗で終わる
+        let code = match kifu::checkdir(&MYOPT.get().unwrap().checkkifu) {
+            Ok((n, invalid)) => {
+                println!("{n} kifus, {invalid} invalid.");
+                if invalid > 0 {1} else {0}
+            },
+            Err(msg) => {
+                eprintln!("{msg}");
+                2
+            },
+        };
+        std::process::exit(code);
+    }
+    if *mode == myoption::Mode::EvalCheck {
+        let opt = MYOPT.get().unwrap();
+        let mut tables = vec![if opt.evaltable1.is_empty() {
+            "data/evaltable.txt"
+        } else {
+            opt.evaltable1.as_str()
+        }];
+        if !opt.evaltable2.is_empty() {
+            tables.push(&opt.evaltable2);
+        }
+        if let Err(msg) = evalcheck::run(&opt.evalcheck, &tables, opt.evaldepth,
+                opt.cachesize_actual()) {
+            eprintln!("{msg}");
+        }
+        std::process::exit(0);
+    }
+    if *mode == myoption::Mode::Analyze {
+        let opt = MYOPT.get().unwrap();
+        let table = if opt.evaltable1.is_empty() {
+            "data/evaltable.txt"
+        } else {
+            opt.evaltable1.as_str()
+        };
+        if let Err(msg) = analyze::run(&opt.analyze, table, opt.depth, opt.exact,
+                opt.mistake, opt.cachesize_actual()) {
+            eprintln!("{msg}");
+        }
+        std::process::exit(0);
+    }
 
     println!("Hello, reversi world!");
     println!("mode:{mode:?}");
+    if let Some(seed) = rng::seed() {
+        println!("seed:{seed}");
+    }
 
     nodebb::init_weight();
 
@@ -859,7 +1206,14 @@ fn main() {
         gen_kifu(n, depth, cachesz);
     }
     if *mode == myoption::Mode::Learn {
-        eprintln!("learning was deprecated. please use tigerdenversi instead.");
+        if let Err(msg) = learn() {
+            eprintln!("{msg}");
+        }
+    }
+    if *mode == myoption::Mode::SelfPlay {
+        if let Err(msg) = selfplay_loop(depth, cachesz) {
+            eprintln!("{msg}");
+        }
     }
     if *mode == myoption::Mode::Duel {
         let ev1 = &MYOPT.get().unwrap().evaltable1;
@@ -887,11 +1241,11 @@ fn main() {
         let opp = &MYOPT.get().unwrap().opponent;
         match opp {
             myoption::Opponent::Cui => {
-                play(
+                let f = if MYOPT.get().unwrap().tui {tui_play} else {play};
+                f(
                     depth,
                     if turn == bitboard::NONE {
-                        let mut rng = rand::thread_rng();
-                        if rng.gen::<bool>() {bitboard::SENTE} else {bitboard::GOTE}
+                        if rng::rng().gen::<bool>() {bitboard::SENTE} else {bitboard::GOTE}
                     } else {
                         turn
                     }, cachesz);
@@ -900,8 +1254,7 @@ fn main() {
                 edax(
                     depth,
                     if turn == bitboard::NONE {
-                        let mut rng = rand::thread_rng();
-                        if rng.gen::<bool>() {bitboard::SENTE} else {bitboard::GOTE}
+                        if rng::rng().gen::<bool>() {bitboard::SENTE} else {bitboard::GOTE}
                     } else {
                         turn
                     }, cachesz);
@@ -910,8 +1263,7 @@ fn main() {
                 vs_ruversi(
                     depth,
                     if turn == bitboard::NONE {
-                        let mut rng = rand::thread_rng();
-                        if rng.gen::<bool>() {bitboard::SENTE} else {bitboard::GOTE}
+                        if rng::rng().gen::<bool>() {bitboard::SENTE} else {bitboard::GOTE}
                     } else {
                         turn
                     }, cachesz);
@@ -14,6 +14,18 @@ pub enum Mode {
   Help,
   InitPos,
   Equal,
+  Perft,
+  ExportDataset,
+  SelfPlay,
+  Label,
+  EvalCheck,
+  Wthor,
+  FromGgf,
+  ToGgf,
+  FromMoves,
+  ToMoves,
+  CheckKifu,
+  Analyze,
 }
 
 #[derive(Debug, PartialEq)]
@@ -51,27 +63,68 @@ impl Verbose {
 /// See 'options:' section in Readme.md.
 #[derive(Debug)]
 pub struct MyOption {
+    pub analyze : String,
+    pub augment : bool,
+    pub checkkifu : String,
+    pub checkpoint : String,
     pub children : bool,
+    pub continued : String,
+    pub dataset : String,
     pub depth : u8,
+    pub divide : bool,
+    pub dsfields : String,  // fixedstones,moveno,stage
+    pub dsformat : dataset::Format,
     pub eta : Option<f32>,
     pub duellv : i8,
+    pub exact : u32,
     pub edaxconfig : String,
+    pub evalcheck : String,
+    pub evaldepth : u8,
     pub evaltable1 : String,
     pub evaltable2 : String,
+    pub explain : Option<weight::Attribution>,
+    pub explainjson : Option<String>,
+    pub games : usize,
+    pub ggf : String,
+    pub gate : selfplay::Gate,
+    pub history : String,
     pub initpos : String,
+    pub kifudir : String,
+    pub label : String,
+    pub layerlr : [f32 ; weight::N_LAYER],
+    pub level : String,  // "casual" or "novice,advanced"
+    pub levelseed : Option<u64>,
+    pub lrsched : trainer::Schedule,
     pub minibsize : usize,
+    pub mistake : f32,
     pub mode : Mode,
     pub n : Option<usize>,
     pub opponent : Opponent,
+    pub optimizer : optimizer::Method,
+    pub oracle : String,  // "internal", "edax"
     pub outtrain : String,  // progress,exrfens,summary
+    pub patience : usize,
+    pub perft : u8,
     pub progress : Vec<u32>,
     pub repeat : Option<usize>,
+    pub resume : bool,
     pub rfen : String,
+    pub searchval : bool,
+    pub seed : Option<u64>,
+    pub selfplay : usize,
+    pub shardsize : usize,
+    pub target : dataset::Target,
     pub think : String,  // "all", "ab"
+    pub transcript : String,
     pub treedump : Option<String>,
     pub trmode : TrainingMode,
+    pub tui : bool,
     pub turn : i8,  // SENTE, GOTE
+    pub valsplit : f32,
     pub verbose : Verbose,
+    pub wdecay : f32,
+    pub wtfilter : wthor::Filter,
+    pub wthor : String,
     cachesize : i32,
 }
 
@@ -84,46 +137,128 @@ impl MyOption {
     /// # Return value
     /// instance of MyOptions.<br>
     /// default:<br>
+    /// - analyze: ""
+    /// - augment: false
     /// - cachesize : 1024
+    /// - checkkifu: ""
+    /// - checkpoint: ""
     /// - children: false
+    /// - continued: ""
+    /// - dataset: ""
     /// - depth: 7
+    /// - divide: false
+    /// - dsfields: ""
+    /// - dsformat: Binary
     /// - eta: None
     /// - duellv: 5
+    /// - exact: 0
+    /// - evalcheck: ""
+    /// - evaldepth: 0
     /// - evaltable1: ""
     /// - evaltable2: ""
+    /// - explain: None
+    /// - explainjson: None
+    /// - games: 100
+    /// - ggf: ""
+    /// - gate: Sprt(0, 10)
+    /// - history: "history/"
     /// - initpos: ""
+    /// - kifudir: "kifu/"
+    /// - label: ""
+    /// - layerlr: [1, 1, 1]
+    /// - level: ""
+    /// - levelseed: None
+    /// - lrsched: Constant
+    /// - mistake: 4
     /// - mode: Mode::None
     /// - n: None
     /// - opponent: Opponent::None
+    /// - optimizer: Sgd(0)
+    /// - oracle: "internal"
     /// - outtrain: ""
+    /// - patience: 0
+    /// - perft: 0
     /// - progress: []
     /// - repeat: None
+    /// - resume: false
     /// - rfen: ""
+    /// - searchval: false
+    /// - seed: None
+    /// - selfplay: 0
+    /// - shardsize: 1000000
+    /// - target: Result
     /// - think: ""
+    /// - transcript: ""
     /// - trmode: OneByOne
+    /// - tui: false
+    /// - valsplit: 0
     /// - verbose: Normal
+    /// - wdecay: 0
+    /// - wtfilter: nothing
+    /// - wthor: ""
     pub fn new(args: Vec<String>) -> Result<MyOption, String> {
         let mut opt = MyOption {
+            analyze : String::new(),
+            augment : false,
+            checkkifu : String::new(),
+            checkpoint : String::new(),
             children : false,
+            continued : String::new(),
+            dataset : String::new(),
             depth : 7,
+            divide : false,
+            dsfields : String::new(),
+            dsformat : dataset::Format::Binary,
             eta : None,
             duellv : 5,
+            exact : 0,
             edaxconfig : String::new(),
+            evalcheck : String::new(),
+            evaldepth : 0,
             evaltable1 : String::new(),
             evaltable2 : String::new(),
+            explain : None,
+            explainjson : None,
+            games : 100,
+            ggf : String::new(),
+            gate : selfplay::Gate::Sprt(0.0, 10.0),
+            history : String::from("history/"),
             initpos: String::new(),
+            kifudir : String::from("kifu/"),
+            label : String::new(),
+            layerlr : [1.0 ; weight::N_LAYER],
+            level : String::new(),
+            levelseed : None,
+            lrsched : trainer::Schedule::Constant,
             mode : Mode::None,
             n : None,
             opponent: Opponent::None,
+            optimizer : optimizer::Method::Sgd(0.0),
+            oracle : String::from("internal"),
             outtrain: String::new(),
+            patience : 0,
+            perft : 0,
             progress: Vec::new(),
             repeat : None,
+            resume : false,
             rfen : String::new(),
+            searchval : false,
+            seed : None,
+            selfplay : 0,
+            shardsize : 1000000,
+            target : dataset::Target::Result,
             think : String::new(),
+            transcript : String::new(),
+            tui : false,
             turn : bitboard::NONE,
+            valsplit : 0.0,
             trmode : TrainingMode::OneByOne,
             minibsize : 128,
+            mistake : 4.0,
             verbose : Verbose::Normal,
+            wdecay : 0.0,
+            wtfilter : wthor::Filter::default(),
+            wthor : String::new(),
             treedump : None,
             cachesize : 1024,
         };
@@ -180,13 +315,77 @@ impl MyOption {
                     if opt.opponent == Opponent::None {
                         opt.opponent = Opponent::Cui;
                     }
+                } else if e == "--tui" {
+                    opt.mode = Mode::Play;
+                    opt.tui = true;
+                    if opt.opponent == Opponent::None {
+                        opt.opponent = Opponent::Cui;
+                    }
                 } else if e == "--rfen" {
-                    opt.mode = Mode::Rfen;
+                    if opt.mode != Mode::Perft {
+                        opt.mode = Mode::Rfen;
+                    }
+                    old = e;
+                } else if e == "--moves" {
+                    if opt.mode == Mode::None {
+                        opt.mode = Mode::Rfen;
+                    }
+                    old = e;
+                } else if e == "--perft" {
+                    opt.mode = Mode::Perft;
+                    old = e;
+                } else if e == "--divide" {
+                    opt.divide = true;
+                } else if e == "--export-dataset" {
+                    opt.mode = Mode::ExportDataset;
+                    old = e;
+                } else if e == "--selfplay" {
+                    opt.mode = Mode::SelfPlay;
+                    old = e;
+                } else if e == "--label" {
+                    opt.mode = Mode::Label;
+                    old = e;
+                } else if e == "--evalcheck" {
+                    opt.mode = Mode::EvalCheck;
+                    old = e;
+                } else if e == "--fromggf" {
+                    opt.mode = Mode::FromGgf;
+                    old = e;
+                } else if e == "--toggf" {
+                    opt.mode = Mode::ToGgf;
+                    old = e;
+                } else if e == "--checkkifu" {
+                    opt.mode = Mode::CheckKifu;
                     old = e;
+                } else if e == "--analyze" {
+                    opt.mode = Mode::Analyze;
+                    old = e;
+                } else if e == "--frommoves" {
+                    opt.mode = Mode::FromMoves;
+                    old = e;
+                } else if e == "--tomoves" {
+                    opt.mode = Mode::ToMoves;
+                    old = e;
+                } else if e == "--wthor" {
+                    opt.mode = Mode::Wthor;
+                    old = e;
+                } else if e == "--augment" {
+                    opt.augment = true;
+                } else if e == "--searchval" {
+                    opt.searchval = true;
+                } else if e == "--resume" {
+                    opt.resume = true;
                 } else if [
                         "--depth", "--Edconf", "--eta", "--ev1", "--ev2",
                         "--progress", "--Ruconf", "--repeat", "--trainout",
-                        "--cachesize",
+                        "--cachesize", "--explain", "--explainjson",
+                        "--kifudir", "--dsformat", "--dsfields", "--shardsize",
+                        "--games", "--gate", "--history", "--duellv",
+                        "--target", "--exact", "--oracle",
+                        "--valsplit", "--lrsched", "--patience", "--checkpoint",
+                        "--optimizer", "--wdecay", "--layerlr", "--evaldepth",
+                        "--wtfilter", "--ggf", "--mistake", "--continue",
+                        "--level", "--levelseed", "--seed",
                     ].contains(&e.as_str()) {
                     old = e;
                 } else if e == "--help" || e == "-h" {
@@ -292,6 +491,39 @@ impl MyOption {
             } else if old == "--rfen" {
                 opt.rfen = e;
                 old.clear();
+            } else if old == "--checkkifu" {
+                opt.checkkifu = e;
+                old.clear();
+            } else if old == "--analyze" {
+                opt.analyze = e;
+                old.clear();
+            } else if old == "--continue" {
+                opt.continued = e;
+                old.clear();
+            } else if old == "--frommoves" || old == "--tomoves" {
+                opt.transcript = e;
+                old.clear();
+            } else if old == "--moves" {
+                let mut ban = bitboard::BitBoard::new();
+                if let Err(msg) = kifu::Kifu::new().play_moves(&mut ban, &e) {
+                    return Err(format!("failed read {old} {e}. ({msg})"));
+                }
+                opt.rfen = ban.to_string();
+                old.clear();
+            } else if old == "--explain" {
+                match weight::Attribution::from(&e) {
+                    Some(method) => {opt.explain = Some(method);},
+                    None => {
+                        return Err(format!("invalid option: {old} {e}"));
+                    }
+                }
+                old.clear();
+            } else if old == "--explainjson" {
+                if opt.explain.is_none() {
+                    opt.explain = Some(weight::Attribution::Occlusion);
+                }
+                opt.explainjson = Some(e);
+                old.clear();
             } else if old == "--depth" {
                 match e.parse::<i32>() {
                     Ok(dep) => {
@@ -306,6 +538,240 @@ impl MyOption {
                     }
                 }
                 old.clear();
+            } else if old == "--perft" {
+                match e.parse::<u8>() {
+                    Ok(dep) => {
+                        if dep == 0 || dep > 60 {
+                            return Err(format!("perft depth {dep} is invalid number."));
+                        }
+                        opt.perft = dep;
+                    },
+                    Err(err) => {
+                        return Err(format!("failed read {old} {e}. ({err})"));
+                    }
+                }
+                old.clear();
+            } else if old == "--export-dataset" {
+                opt.dataset = e;
+                old.clear();
+            } else if old == "--kifudir" {
+                opt.kifudir = e;
+                old.clear();
+            } else if old == "--dsformat" {
+                match dataset::Format::from(&e) {
+                    Some(fmt) => {opt.dsformat = fmt;},
+                    None => {
+                        return Err(format!("invalid option: {old} {e}"));
+                    }
+                }
+                old.clear();
+            } else if old == "--dsfields" {
+                opt.dsfields = e;
+                old.clear();
+            } else if old == "--shardsize" {
+                match e.parse::<usize>() {
+                    Ok(sz) => {
+                        if sz == 0 {
+                            return Err(format!("shardsize {sz} is invalid number."));
+                        }
+                        opt.shardsize = sz;
+                    },
+                    Err(err) => {
+                        return Err(format!("failed read {old} {e}. ({err})"));
+                    }
+                }
+                old.clear();
+            } else if old == "--selfplay" || old == "--games" {
+                match e.parse::<usize>() {
+                    Ok(n) => {
+                        if n == 0 {
+                            return Err(format!("{old} {n} is invalid number."));
+                        }
+                        if old == "--selfplay" {
+                            opt.selfplay = n;
+                        } else {
+                            opt.games = n;
+                        }
+                    },
+                    Err(err) => {
+                        return Err(format!("failed read {old} {e}. ({err})"));
+                    }
+                }
+                old.clear();
+            } else if old == "--gate" {
+                match selfplay::Gate::from(&e) {
+                    Some(gate) => {opt.gate = gate;},
+                    None => {
+                        return Err(format!("invalid option: {old} {e}"));
+                    }
+                }
+                old.clear();
+            } else if old == "--label" {
+                opt.label = e;
+                old.clear();
+            } else if old == "--fromggf" || old == "--toggf" || old == "--ggf" {
+                opt.ggf = e;
+                old.clear();
+            } else if old == "--wthor" {
+                opt.wthor = e;
+                old.clear();
+            } else if old == "--wtfilter" {
+                match wthor::Filter::from(&e) {
+                    Some(filter) => {opt.wtfilter = filter;},
+                    None => {
+                        return Err(format!("invalid option: {old} {e}"));
+                    }
+                }
+                old.clear();
+            } else if old == "--evalcheck" {
+                opt.evalcheck = e;
+                old.clear();
+            } else if old == "--evaldepth" {
+                match e.parse::<u8>() {
+                    Ok(depth) => {opt.evaldepth = depth;},
+                    Err(err) => {
+                        return Err(format!("failed read {old} {e}. ({err})"));
+                    }
+                }
+                old.clear();
+            } else if old == "--exact" {
+                match e.parse::<u32>() {
+                    Ok(n) => {
+                        if n == 0 || n > 60 {
+                            return Err(format!("exact {n} is invalid number."));
+                        }
+                        opt.exact = n;
+                    },
+                    Err(err) => {
+                        return Err(format!("failed read {old} {e}. ({err})"));
+                    }
+                }
+                old.clear();
+            } else if old == "--oracle" {
+                let oracle = e.to_ascii_lowercase();
+                if oracle != "internal" && oracle != "edax" {
+                    return Err(format!("invalid option: {old} {e}"));
+                }
+                opt.oracle = oracle;
+                old.clear();
+            } else if old == "--level" {
+                let names = e.split(',').collect::<Vec<_>>();
+                if names.len() > 2 {
+                    return Err(format!("too many levels in {old} {e}."));
+                }
+                if let Some(nm) = names.iter().find(|nm| level::Level::from(nm).is_none()) {
+                    let all = level::LEVELS.iter().map(|(nm, _)| *nm).collect::<Vec<_>>();
+                    return Err(format!("unknown level \"{nm}\". {}", all.join(", ")));
+                }
+                opt.level = e;
+                old.clear();
+            } else if old == "--levelseed" || old == "--seed" {
+                match e.parse::<u64>() {
+                    Ok(seed) if old == "--seed" => {opt.seed = Some(seed);},
+                    Ok(seed) => {opt.levelseed = Some(seed);},
+                    Err(err) => {
+                        return Err(format!("failed read {old} {e}. ({err})"));
+                    }
+                }
+                old.clear();
+            } else if old == "--mistake" {
+                match e.parse::<f32>() {
+                    Ok(discs) => {
+                        if discs.is_nan() || discs < 0.0 {
+                            return Err(format!("mistake {discs} is invalid number."));
+                        }
+                        opt.mistake = discs;
+                    },
+                    Err(err) => {
+                        return Err(format!("failed read {old} {e}. ({err})"));
+                    }
+                }
+                old.clear();
+            } else if old == "--valsplit" {
+                match e.parse::<f32>() {
+                    Ok(ratio) => {
+                        if !(0.0..1.0).contains(&ratio) {
+                            return Err(format!("valsplit {ratio} is invalid number."));
+                        }
+                        opt.valsplit = ratio;
+                    },
+                    Err(err) => {
+                        return Err(format!("failed read {old} {e}. ({err})"));
+                    }
+                }
+                old.clear();
+            } else if old == "--lrsched" {
+                match trainer::Schedule::from(&e) {
+                    Some(sched) => {opt.lrsched = sched;},
+                    None => {
+                        return Err(format!("invalid option: {old} {e}"));
+                    }
+                }
+                old.clear();
+            } else if old == "--patience" {
+                match e.parse::<usize>() {
+                    Ok(n) => {opt.patience = n;},
+                    Err(err) => {
+                        return Err(format!("failed read {old} {e}. ({err})"));
+                    }
+                }
+                old.clear();
+            } else if old == "--optimizer" {
+                match optimizer::Method::from(&e) {
+                    Some(method) => {opt.optimizer = method;},
+                    None => {
+                        return Err(format!("invalid option: {old} {e}"));
+                    }
+                }
+                old.clear();
+            } else if old == "--wdecay" {
+                match e.parse::<f32>() {
+                    Ok(decay) => {
+                        if decay < 0.0 {
+                            return Err(format!("wdecay {decay} is invalid number."));
+                        }
+                        opt.wdecay = decay;
+                    },
+                    Err(err) => {
+                        return Err(format!("failed read {old} {e}. ({err})"));
+                    }
+                }
+                old.clear();
+            } else if old == "--layerlr" {
+                match optimizer::parse_layerlr(&e) {
+                    Some(lr) => {opt.layerlr = lr;},
+                    None => {
+                        return Err(format!("invalid option: {old} {e}"));
+                    }
+                }
+                old.clear();
+            } else if old == "--checkpoint" {
+                opt.checkpoint = e;
+                old.clear();
+            } else if old == "--target" {
+                match dataset::Target::from(&e) {
+                    Some(target) => {opt.target = target;},
+                    None => {
+                        return Err(format!("invalid option: {old} {e}"));
+                    }
+                }
+                old.clear();
+            } else if old == "--history" {
+                opt.history = e;
+                old.clear();
+            } else if old == "--duellv" {
+                match e.parse::<i8>() {
+                    Ok(lv) => {
+                        if !(1..=14).contains(&lv) {
+                            return Err(format!("duel level {lv} is invalid number."));
+                        }
+                        opt.duellv = lv;
+                    },
+                    Err(err) => {
+                        return Err(format!("failed read {old} {e}. ({err})"));
+                    }
+                }
+                old.clear();
             } else if old == "--initpos" {
                 opt.initpos = e;
                 old.clear();
@@ -358,9 +824,23 @@ pub fn showhelp(msg : &str) {
   --playw    play a game agaist you. your turn is white(GOTE).
   you can use w/ --Edax to make ruversi white.
   --rfen <rfen>  think from rfen for debug. don't forget \"\" not to be recognized as 2 part.
+  --moves <moves>  think from the position after moves like f5d6c3 instead of rfen.
   --gtp      go text protocol mode.
   --oep      othello engine protocol mode.
-  --learn    [deprecated]set lerning mode. default.
+  --learn    set lerning mode.
+  --perft <depth>  count leaf nodes <depth> plies ahead. a pass is counted as a ply.
+  --export-dataset <dir>  export positions in kifus into <dir> as shards and manifest.json.
+  --selfplay <generations>  repeat self-play, training and duel against the best eval table.
+  --label <dir>  label positions in a dataset exported by --export-dataset w/ exact scores.
+  --evalcheck <positions>  measure errors of eval tables on positions w/ true scores.
+  --wthor <path>  import games in a WTHOR file(.wtb) or files in a directory as kifus.
+  --fromggf <path>  import games in a GGF file into --kifudir(default kifu/) as kifus.
+  --toggf <path>  put a kifu file or kifu files in a directory in GGF.
+  --frommoves <path>  import transcripts like f5d6c3 in every line of a file as kifus.
+  --tomoves <path>  put a kifu file or kifu files in a directory as transcripts.
+  --checkkifu <path>  check a kifu file or kifu files in a directory by replaying them.
+                      exits w/ 1 if some are invalid or 2 on error.
+  --analyze <kifu>  search every position in a kifu and flag mistakes.
 
   Common:
     --thinkab   use alpha-beta pruning. default.
@@ -368,6 +848,8 @@ pub fn showhelp(msg : &str) {
     --depth x   searching depth. default 7.
     --silent    reduce console outputs.
     --cachesize sz  # of cache table size in kilo. default 1024.
+    --seed <seed>   seed for every random number like random eval tables, turns, shuffles and --level.
+        runs w/ the same seed are reproduced. it is shown and recorded in kifus.
   Duel:
     --ev1 <path>  a file for board evaluation.
     --ev2 <path>  a file for board evaluation.
@@ -375,29 +857,131 @@ pub fn showhelp(msg : &str) {
     --Edconf <path>  a file for edax(or ruversi) path configuration.
     --Ruversi  play against another Ruversi, not against Edax.
     --Cassio   play against edax via othello engine protocol.
+    --ggf <path>  append games to <path> in GGF.
   GenKifu:
+    --moves <moves>  generate a kifu from the position after moves.
     -Nx  initial board group x for generating kifu. 0~99.
         all of the initial board positions will be used when this option is not specified.
+    --searchval  record root search values in kifus for --target.
+    --level <black>[,<white>]  strength of black and white. see Play.
+    --levelseed <seed>  seed for --level. every game uses its own seed from it.
   Play:
+    commands on your turn : undo, hint, moves, eval, save <file>, load <file>, flip, resign, help.
+    --tui      play on a full screen terminal UI w/ the cursor, evaluation, PV, moves and clocks.
+    --level <level>  strength of this program instead of --depth.
+        beginner, novice, casual, intermediate or advanced.
+    --levelseed <seed>  seed for --level. the same seed chooses the same moves.
+        a random seed is shown when it is not specified.
+    --rfen <rfen>, --moves <moves>  start from a position instead of the initial position.
     --Edax     play against Edax instead of you. please use with --play(bw).
     --Edconf <path>  a file for edax path configuration.
+    --continue <path>  continue a game in a kifu, GGF or transcript file.
+        the whole game is put into <path>_continued.txt.
   Rfen:
     --treedump <path>  put search tree into a file w/ PlantUML format.
     --children         also think from every children rfen.
-  [deprecated]Learn:
-    --repeat <number>  [deprecated]number of learning. default 10000.
-    --eta <ratio>      [deprecated]learning ratio. default 0.1.
-    --trainout <options>  [deprecated]output control.
+    --explain <method>  show contributions of every stone to the evaluation.
+        occlusion : difference from the value without the stone.
+        gradient  : gradient x input.
+        also explained by --ev2 when it is specified.
+    --explainjson <path>  put the contributions into a file w/ JSON format.
+        occlusion is used when --explain is not specified.
+  Perft:
+    --rfen <rfen>  start from rfen instead of the initial position.
+    --moves <moves>  start from the position after moves.
+    --divide       show the count of every move.
+  ExportDataset:
+    --kifudir <dir>     directory including kifu files. default kifu/.
+    --dsformat <format> format of shards. bin or csv. default bin.
+    --dsfields <fields> COMMA separated optional fields. default nothing.
+        fixedstones : # of fixed stones of black and white.
+        moveno      : move number in the kifu.
+        stage       : progress of weights. 0~2.
+        target      : training target. see --target in Learn.
+    --augment           also export rotated, mirrored and color flipped positions.
+    --shardsize <number>  max # of positions in a shard. default 1000000.
+    --exact <empties>   label positions w/ <empties> or less empty cells w/ exact scores.
+    symmetric positions are exported only once.
+  Label:
+    --exact <empties>   positions w/ <empties> or less empty cells are solved. 1~60.
+    --oracle <solver>   internal or edax. default internal.
+        edax is configured by --Edconf.
+    solved positions are cached in <dir>/solved.txt and reused when it is run again.
+  EvalCheck:
+    <positions> is a dataset directory exported by --export-dataset w/ its targets or
+    a text file w/ \"<rfen> <score from black's view>\" in every line.
+    MSE, MAE and sign accuracy for all, every stage and every 10 empty cells
+    of --ev1(default data/evaltable.txt) and --ev2 are shown side by side.
+    --evaldepth <depth>  search depth. default 0(static evaluation).
+  Analyze:
+    the best move, its value and the value of the played move are added to every move
+    in <kifu> and the annotated kifu is put into <kifu>_analyzed.txt.
+    total loss and mistakes of black and white are shown.
+    --depth x            searching depth. default 7.
+    --exact <empties>    positions w/ <empties> or less empty cells are solved.
+    --mistake <discs>    moves losing more than <discs> are mistakes. default 4.
+  Wthor:
+    moves are validated by replaying and kifus are put into --kifudir(default kifu/).
+    player names are read from .jou in the same directory.
+    --wtfilter <filters>  COMMA separated conditions of games to be imported.
+        year:<year>[:<to>]    year of games.
+        player:<name>         a part of the name of black or white.
+        score:<min>:<max>     final score from black's view.
+  SelfPlay:
+    the best eval table(--ev1 or data/evaltable.txt) plays games against itself,
+    a candidate is trained w/ the kifus and promoted if it passes the gate in a duel.
+    options for Learn are used for training.
+    --games <number>   # of self-play games in a generation. default 100.
+    --level <level>    level of the first 4 moves of each color in a self-play game
+                       to vary games. default casual. seeded by --seed.
+    --duellv <number>  equal positions for the duel. 1 ~ default 5 ~ 14.
+    --gate <gate>      criterion to promote a candidate. default sprt.
+        elo[:<min>]             lower bound of 95% interval of R > min. default 0.
+        sprt[:<elo0>:<elo1>]    SPRT accepts elo1 against elo0. default 0:10.
+    --history <dir>    directory for generations. default history/.
+        best.txt, history.csv and genNNN/(kifu/, candidate.txt, duel.txt).
+        the loop is resumed if the directory exists.
+  Learn:
+    train the eval table(--ev1 or data/evaltable.txt) w/ kifus in kifu/.
+    the result is saved as kifu/newevaltable.txt.
+    --repeat <number>  number of epochs. default 100.
+    --eta <ratio>      learning ratio. default 0.001.
+    --trainout <options>  output control.
         exrfens  : put RFENs in 2nd moves in every kifus.
         nosave   : skip saving weights.
         progress : show progress
         summary  : show input summary.
         time     : show processing time.
         default: progress,summary,time
-    --progress <numbers>  [deprecated]storing weight after some iterations as newevaltable.rN.txt.
+    --progress <numbers>  storing weight after some epochs as newevaltable.rN.txt.
         default: nothing.
-    --onebyone  [deprecated]train w/o minibatch. minibatch=1 in other words. default.
-    --minibatch <number>  [deprecated]train w/ minibatch.
+    --target <target>  training target of positions. default result.
+        result          final score of the game.
+        search          search value recorded by --searchval.
+        blend[:<w>]     (1 - w) * result + w * search. default 0.5.
+        td[:<lambda>]   TD(lambda) return over search values. default 0.7.
+        result is used for positions w/o search values.
+    --valsplit <ratio>  ratio of kifus for validation. 0 ~ 1. default 0.
+        MSE, MAE and sign accuracy of every stage are measured in every epoch.
+        the log is put into kifu/trainlog.csv.
+    --lrsched <schedule>  learning rate schedule. default const.
+        const              same rate.
+        step:<n>:<gamma>   multiply gamma every n epochs.
+        exp:<gamma>        multiply gamma every epoch.
+        cos                cosine annealing to 0.
+    --patience <number>  stop when the loss(validation if any) does not improve
+        in <number> epochs and restore the best weights. default 0(never).
+    --optimizer <method>  how to update weights. default sgd.
+        sgd[:<momentum>]          SGD w/ momentum. default 0.
+        adam[:<beta1>:<beta2>]    Adam. default 0.9:0.999.
+        adamw[:<beta1>:<beta2>]   Adam w/ decoupled weight decay.
+    --wdecay <lambda>  weight decay. L2 for sgd and adam. default 0.
+    --layerlr <in>:<hidden>:<out>  multipliers of the learning rate
+        for every layer. default 1:1:1.
+    --checkpoint <dir>  store weights, optimizer and state in <dir> after every epoch.
+    --resume    resume from --checkpoint.
+    --onebyone  train w/o minibatch. minibatch=1 in other words. default.
+    --minibatch <number>  train w/ minibatch.
         size of minibatch. default 128.
 ");
 }
@@ -451,6 +1035,47 @@ mod tests {
         assert_eq!(opt.verbose, Verbose::Normal);
         // treedump のデフォルト値は None
         assert_eq!(opt.treedump, None);
+        assert_eq!(opt.explain, None);
+        assert_eq!(opt.explainjson, None);
+        assert_eq!(opt.perft, 0);
+        assert!(!opt.divide);
+        assert!(!opt.augment);
+        assert_eq!(opt.dataset, "");
+        assert_eq!(opt.dsfields, "");
+        assert_eq!(opt.dsformat, dataset::Format::Binary);
+        assert_eq!(opt.kifudir, "kifu/");
+        assert_eq!(opt.shardsize, 1000000);
+        assert_eq!(opt.selfplay, 0);
+        assert_eq!(opt.games, 100);
+        assert_eq!(opt.gate, selfplay::Gate::Sprt(0.0, 10.0));
+        assert_eq!(opt.history, "history/");
+        assert!(!opt.searchval);
+        assert_eq!(opt.seed, None);
+        assert_eq!(opt.exact, 0);
+        assert_eq!(opt.valsplit, 0.0);
+        assert_eq!(opt.lrsched, trainer::Schedule::Constant);
+        assert_eq!(opt.patience, 0);
+        assert_eq!(opt.checkpoint, "");
+        assert!(!opt.resume);
+        assert_eq!(opt.optimizer, optimizer::Method::Sgd(0.0));
+        assert_eq!(opt.wdecay, 0.0);
+        assert_eq!(opt.layerlr, [1.0 ; weight::N_LAYER]);
+        assert_eq!(opt.evalcheck, "");
+        assert_eq!(opt.evaldepth, 0);
+        assert_eq!(opt.wthor, "");
+        assert_eq!(opt.ggf, "");
+        assert_eq!(opt.transcript, "");
+        assert_eq!(opt.checkkifu, "");
+        assert_eq!(opt.analyze, "");
+        assert_eq!(opt.continued, "");
+        assert!(!opt.tui);
+        assert_eq!(opt.mistake, 4.0);
+        assert_eq!(opt.wtfilter, wthor::Filter::default());
+        assert_eq!(opt.label, "");
+        assert_eq!(opt.level, "");
+        assert_eq!(opt.levelseed, None);
+        assert_eq!(opt.oracle, "internal");
+        assert_eq!(opt.target, dataset::Target::Result);
         assert_eq!(opt.cachesize, 1024);
     }
 
@@ -552,4 +1177,294 @@ mod tests {
         let err = MyOption::new(args).unwrap_err();
         assert_eq!(err, "failed read --cachesize A. (invalid digit found in string)");
     }
+
+    #[test]
+    fn test_explain() {
+        let args = vec![
+            "prog".to_string(), "--explain".to_string(), "gradient".to_string()];
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.explain, Some(weight::Attribution::GradientInput));
+        assert_eq!(opt.explainjson, None);
+        let args = vec![
+            "prog".to_string(), "--explainjson".to_string(), "a.json".to_string()];
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.explain, Some(weight::Attribution::Occlusion));
+        assert_eq!(opt.explainjson, Some("a.json".to_string()));
+        let args = vec![
+            "prog".to_string(), "--explain".to_string(), "lime".to_string()];
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "invalid option: --explain lime");
+    }
+
+    #[test]
+    fn test_perft() {
+        let args = vec![
+            "prog".to_string(), "--perft".to_string(), "5".to_string(),
+            "--rfen".to_string(), "8/8/8/3Aa3/3aA3/8/8/8 b".to_string(),
+            "--divide".to_string()];
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::Perft);
+        assert_eq!(opt.perft, 5);
+        assert_eq!(opt.rfen, "8/8/8/3Aa3/3aA3/8/8/8 b");
+        assert!(opt.divide);
+        let args = vec![
+            "prog".to_string(), "--rfen".to_string(), "8/8/8/3Aa3/3aA3/8/8/8 b".to_string(),
+            "--perft".to_string(), "5".to_string()];
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::Perft);
+        let args = vec![
+            "prog".to_string(), "--perft".to_string(), "0".to_string()];
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "perft depth 0 is invalid number.");
+    }
+
+    #[test]
+    fn test_export_dataset() {
+        let args = [
+            "prog", "--export-dataset", "out", "--kifudir", "kifu2/",
+            "--dsformat", "CSV", "--dsfields", "moveno,stage", "--augment",
+            "--shardsize", "1000"].iter().map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::ExportDataset);
+        assert_eq!(opt.dataset, "out");
+        assert_eq!(opt.kifudir, "kifu2/");
+        assert_eq!(opt.dsformat, dataset::Format::Csv);
+        assert_eq!(opt.dsfields, "moveno,stage");
+        assert!(opt.augment);
+        assert_eq!(opt.shardsize, 1000);
+        let args = ["prog", "--dsformat", "json"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "invalid option: --dsformat json");
+        let args = ["prog", "--shardsize", "0"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "shardsize 0 is invalid number.");
+    }
+
+    #[test]
+    fn test_selfplay() {
+        let args = [
+            "prog", "--selfplay", "3", "--games", "20", "--gate", "elo:5",
+            "--history", "hist", "--duellv", "2"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::SelfPlay);
+        assert_eq!(opt.selfplay, 3);
+        assert_eq!(opt.games, 20);
+        assert_eq!(opt.gate, selfplay::Gate::Elo(5.0));
+        assert_eq!(opt.history, "hist");
+        assert_eq!(opt.duellv, 2);
+        let args = ["prog", "--selfplay", "0"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "--selfplay 0 is invalid number.");
+        let args = ["prog", "--gate", "sprt:1"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "invalid option: --gate sprt:1");
+        let args = ["prog", "--duellv", "15"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "duel level 15 is invalid number.");
+    }
+
+    #[test]
+    fn test_target() {
+        let args = ["prog", "--genkifu", "--searchval"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::GenKifu);
+        assert!(opt.searchval);
+        let args = ["prog", "--learn", "--target", "td:0.5"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.target, dataset::Target::Td(0.5));
+        let args = ["prog", "--target", "blend:2"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "invalid option: --target blend:2");
+    }
+
+    #[test]
+    fn test_label() {
+        let args = ["prog", "--label", "ds/", "--exact", "14", "--oracle", "Edax"]
+            .iter().map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::Label);
+        assert_eq!(opt.label, "ds/");
+        assert_eq!(opt.exact, 14);
+        assert_eq!(opt.oracle, "edax");
+        let args = ["prog", "--exact", "0"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "exact 0 is invalid number.");
+        let args = ["prog", "--oracle", "wzebra"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "invalid option: --oracle wzebra");
+    }
+
+    #[test]
+    fn test_validation() {
+        let args = ["prog", "--learn", "--valsplit", "0.1", "--lrsched", "step:10:0.5",
+                "--patience", "5", "--checkpoint", "cp/", "--resume"]
+            .iter().map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.valsplit, 0.1);
+        assert_eq!(opt.lrsched, trainer::Schedule::Step(10, 0.5));
+        assert_eq!(opt.patience, 5);
+        assert_eq!(opt.checkpoint, "cp/");
+        assert!(opt.resume);
+        let args = ["prog", "--valsplit", "1"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "valsplit 1 is invalid number.");
+        let args = ["prog", "--lrsched", "warmup"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "invalid option: --lrsched warmup");
+    }
+
+    #[test]
+    fn test_optimizer() {
+        let args = ["prog", "--learn", "--optimizer", "adamw", "--wdecay", "0.01",
+                "--layerlr", "1:0.5:0.25"]
+            .iter().map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.optimizer, optimizer::Method::AdamW(0.9, 0.999));
+        assert_eq!(opt.wdecay, 0.01);
+        assert_eq!(opt.layerlr, [1.0, 0.5, 0.25]);
+        let args = ["prog", "--optimizer", "sgd:0.9"].iter()
+            .map(|a| a.to_string()).collect();
+        assert_eq!(MyOption::new(args).unwrap().optimizer, optimizer::Method::Sgd(0.9));
+        let args = ["prog", "--optimizer", "lion"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "invalid option: --optimizer lion");
+        let args = ["prog", "--wdecay", "-1"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "wdecay -1 is invalid number.");
+        let args = ["prog", "--layerlr", "1:1"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "invalid option: --layerlr 1:1");
+    }
+
+    #[test]
+    fn test_evalcheck() {
+        let args = ["prog", "--evalcheck", "pos.txt", "--evaldepth", "3"]
+            .iter().map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::EvalCheck);
+        assert_eq!(opt.evalcheck, "pos.txt");
+        assert_eq!(opt.evaldepth, 3);
+        let args = ["prog", "--evaldepth", "x"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert!(err.starts_with("failed read --evaldepth x."));
+    }
+
+    #[test]
+    fn test_wthor() {
+        let args = ["prog", "--wthor", "WTH_2001.wtb", "--wtfilter", "year:2001,score:-4:4",
+                "--kifudir", "wthor/"]
+            .iter().map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::Wthor);
+        assert_eq!(opt.wthor, "WTH_2001.wtb");
+        assert_eq!(opt.wtfilter, wthor::Filter::from("score:-4:4,year:2001").unwrap());
+        assert_eq!(opt.kifudir, "wthor/");
+        let args = ["prog", "--wtfilter", "elo:2000"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "invalid option: --wtfilter elo:2000");
+    }
+
+    #[test]
+    fn test_ggf() {
+        let args = ["prog", "--fromggf", "games.ggf", "--kifudir", "ggf/"]
+            .iter().map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::FromGgf);
+        assert_eq!(opt.ggf, "games.ggf");
+        assert_eq!(opt.kifudir, "ggf/");
+        let args = ["prog", "--toggf", "kifu/"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::ToGgf);
+        assert_eq!(opt.ggf, "kifu/");
+    }
+
+    #[test]
+    fn test_moves() {
+        let args = ["prog", "--moves", "f5d6"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::Rfen);
+        assert_eq!(opt.rfen, "8/8/3a4/3aB2/3aA3/8/8/8 b");
+        let args = ["prog", "--playb", "--moves", "f5"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::Play);
+        assert_eq!(opt.rfen, "8/8/8/3C2/3aA3/8/8/8 w");
+        let args = ["prog", "--tomoves", "kifu/"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::ToMoves);
+        assert_eq!(opt.transcript, "kifu/");
+        let args = ["prog", "--frommoves", "games.txt"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::FromMoves);
+        assert_eq!(opt.transcript, "games.txt");
+        let args = ["prog", "--checkkifu", "kifu/"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::CheckKifu);
+        assert_eq!(opt.checkkifu, "kifu/");
+        let args = ["prog", "--analyze", "kifu1.txt", "--exact", "14", "--mistake", "2.5"]
+            .iter().map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::Analyze);
+        assert_eq!(opt.analyze, "kifu1.txt");
+        assert_eq!((opt.exact, opt.mistake), (14, 2.5));
+        let args = ["prog", "--playw", "--continue", "kifu1.txt"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!(opt.mode, Mode::Play);
+        assert_eq!(opt.continued, "kifu1.txt");
+        let args = ["prog", "--genkifu", "--level", "novice,advanced", "--levelseed", "3"]
+            .iter().map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!((opt.level.as_str(), opt.levelseed), ("novice,advanced", Some(3)));
+        let args = ["prog", "--duel", "--seed", "42"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!((opt.seed, opt.levelseed), (Some(42), None));
+        let args = ["prog", "--seed", "-1"].iter()
+            .map(|a| a.to_string()).collect();
+        assert!(MyOption::new(args).unwrap_err().starts_with("failed read --seed -1."));
+        let args = ["prog", "--level", "god"].iter()
+            .map(|a| a.to_string()).collect();
+        assert_eq!(MyOption::new(args).unwrap_err(), "unknown level \"god\". \
+            beginner, novice, casual, intermediate, advanced");
+        let args = ["prog", "--level", "casual,casual,casual"].iter()
+            .map(|a| a.to_string()).collect();
+        assert!(MyOption::new(args).is_err());
+        let args = ["prog", "--tui", "--playw"].iter()
+            .map(|a| a.to_string()).collect();
+        let opt = MyOption::new(args).unwrap();
+        assert_eq!((opt.mode, opt.opponent, opt.turn), (Mode::Play, Opponent::Cui, bitboard::GOTE));
+        assert!(opt.tui);
+        let args = ["prog", "--mistake", "-1"].iter()
+            .map(|a| a.to_string()).collect();
+        assert_eq!(MyOption::new(args).unwrap_err(), "mistake -1 is invalid number.");
+        let args = ["prog", "--genkifu", "--moves", "f5f5"].iter()
+            .map(|a| a.to_string()).collect();
+        let err = MyOption::new(args).unwrap_err();
+        assert_eq!(err, "failed read --moves f5f5. \
+            (move 2 f5: illegal move f4 on 8/8/8/3C2/3aA3/8/8/8 w.)");
+    }
 }
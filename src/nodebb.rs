@@ -109,8 +109,9 @@ impl NodeBB {
                 > other.best.as_ref().unwrap().hyoka * other.teban as f32
     }
 
+    /// static evaluation from black's view.
     #[cfg(target_arch="x86_64")]
-    fn evaluate(ban : &bitboard::BitBoard, wei : &weight::Weight) -> f32 {
+    pub fn evaluate(ban : &bitboard::BitBoard, wei : &weight::Weight) -> f32 {
         if ban.is_full() || ban.is_passpass() {
             return ban.countf32();
         }
@@ -125,7 +126,7 @@ impl NodeBB {
     }
 
     #[cfg(target_arch="aarch64")]
-    fn evaluate(ban : &bitboard::BitBoard, wei : &weight::Weight) -> f32 {
+    pub fn evaluate(ban : &bitboard::BitBoard, wei : &weight::Weight) -> f32 {
         if ban.is_full() || ban.is_passpass() {
             return ban.countf32();
         }
@@ -277,6 +278,66 @@ impl NodeBB {
         node.hyoka
     }
 
+    /// search every legal move on `ban` w/ `depth - 1` plies after it.
+    /// every move is searched w/ the full window and w/o deepening in the endgame
+    /// so that values of moves are comparable.
+    ///
+    /// # Returns
+    /// [(move, value from black's view, searched tree)]
+    pub fn think_every_move(ban : &bitboard::BitBoard, depth : u8,
+            wei : &weight::Weight, tt : &mut transptable::TranspositionTable)
+            -> Vec<(u8, f32, NodeBB)> {
+        let alpha : f32 = -123456.7;
+        let beta : f32 = 123456.7;
+        bitboard::MoveIter::new(ban.legal_moves()).map(|mv| {
+            let child = ban.r#move(mv).unwrap();
+            let mut nd = NodeBB::new(mv, depth.max(1) - 1, ban.teban);
+            let val = NodeBB::think_internal_ab_failsoft(
+                &mut nd, &child, alpha, beta, wei, tt) * child.teban as f32;
+            nd.hyoka = Some(val);
+            (mv, val, nd)
+        }).collect()
+    }
+
+    /// think_ab_simple_gk_tt()と同じ探索を盤をコピーせずに行う。
+    #[allow(dead_code)]
+    pub fn think_ab_inplace_gk_tt(ban : &bitboard::BitBoard, depth : u8, nd : &mut NodeBB,
+            wei : &weight::Weight, tt : &mut transptable::TranspositionTable)
+            -> Option<f32> {
+        if depth == 0 {
+            return None;
+        }
+        if ban.is_passpass() {
+            return None;
+        }
+        // no more empty cells
+        let _moves = ban.genmove()?;
+
+        let node = nd;
+
+        let yomikiri = 13;
+        let yose = 18;
+        let nblank = ban.nblank();
+        node.depth =
+            if nblank < yomikiri {
+                yomikiri as u8
+            } else if nblank <= yose {
+                depth + 2
+            } else {
+                depth
+            };
+
+        let alpha : f32 = -123456.7;
+        let beta : f32 = 123456.7;
+        let mut ban = ban.clone();
+        let val = NodeBB::think_internal_ab_inplace(
+                    node, &mut ban, alpha, beta, wei, tt);
+        let val = val * ban.teban as f32;
+        node.hyoka = Some(val);
+
+        node.hyoka
+    }
+
     #[allow(dead_code)]
     pub fn think_mtdf(ban : &bitboard::BitBoard, depth : u8, node : &mut NodeBB,
             wei : &weight::Weight, tt : &mut transptable::TranspositionTable) -> Option<f32> {
@@ -339,6 +400,12 @@ impl NodeBB {
         if node.depth == 0 {
             return NodeBB::evalwtt(ban, wei, tt);
         }
+        // 読み切れる局面では確定石で結果の範囲が窓の外なら読まない
+        if node.depth as u32 >= ban.nblank() {
+            let (lower, upper) = ban.stable_bounds();
+            if upper as f32 <= alpha {return upper as f32;}
+            if lower as f32 >= beta {return lower as f32;}
+        }
 
         let mut newalpha = alpha;
         let depth = node.depth;
@@ -420,6 +487,111 @@ impl NodeBB {
         // newalpha  // fail-hard
     }
 
+    /// think_internal_ab_failsoft()の盤をコピーしない版。
+    /// 戻るときには`ban`は呼ばれたときと同じに戻っている。
+    pub fn think_internal_ab_inplace(node:&mut NodeBB, ban : &mut bitboard::BitBoard, alpha : f32, beta : f32,
+            wei : &weight::Weight, tt : &mut transptable::TranspositionTable) -> f32 {
+        if ban.is_full() || ban.is_passpass() {
+            return ban.countf32() * ban.teban as f32;
+        }
+        #[cfg(feature="mate1")]
+        if ban.is_last1() {
+            let (val, xy) = ban.move_mate1();
+            if node.best.is_none() {
+                node.best = Some(Best::new(val * ban.teban as f32, xy));
+            }
+            return val * ban.teban as f32;
+        }
+        #[cfg(feature="withtt")]
+        if let Some(tt_val) = tt.check_available(ban, node.depth) {
+            return tt_val;
+        }
+        if node.depth == 0 {
+            return NodeBB::evalwtt(ban, wei, tt);
+        }
+        // 読み切れる局面では確定石で結果の範囲が窓の外なら読まない
+        if node.depth as u32 >= ban.nblank() {
+            let (lower, upper) = ban.stable_bounds();
+            if upper as f32 <= alpha {return upper as f32;}
+            if lower as f32 >= beta {return lower as f32;}
+        }
+
+        let mut newalpha = alpha;
+        let depth = node.depth;
+        let teban = ban.teban;
+        let moves = ban.genmove();
+
+        // no more empty cells
+        if moves.is_none() {
+            panic!("moves.is_none() nblank == 0 should work!");
+        }
+        let mut moves = moves.unwrap();
+        if moves.len() > 1 {
+            // shallow search for move ordering.
+            let fteban = teban as f32;
+            let mut aval = moves.iter().enumerate().map(|(i, &mv)| {
+                const D : u8 = 6;
+                let undo = ban.do_move(mv).unwrap();
+                let val = if depth < D {  // depth:1
+                    -NodeBB::evalwtt(ban, wei, tt)
+                } else {  // depth:2
+                    match ban.genmove() {
+                        None => {
+                            ban.countf32() * fteban
+                        },
+                        Some(mvs) => {
+                            -mvs.iter().map(|&mv| {
+                                    let undo2 = ban.do_move(mv).unwrap();
+                                    let val = -NodeBB::evalwtt(ban, wei, tt);
+                                    ban.undo_move(&undo2);
+                                    val
+                                }
+                            ).reduce(f32::max).unwrap()
+                        },
+                    }
+                };
+                ban.undo_move(&undo);
+                (i, val)
+            }).collect::<Vec<_>>();
+            aval.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+            moves = aval.iter().map(|(i, _val)| moves[*i]).collect::<Vec<_>>();
+        }
+        node.child.reserve(moves.len());
+        let mut maxval = -9999.0;
+        for mv in moves {
+            let undo = ban.do_move(mv).unwrap();
+            let ch = if let Some(nd)
+                = node.child.iter_mut().find(|n| n.xy == mv) {
+                nd
+            } else {
+                node.child.push(NodeBB::new(mv, depth - 1, teban));
+                node.child.last_mut().unwrap()
+            };
+            let val = -NodeBB::think_internal_ab_inplace(
+                        ch, ban, -beta, -newalpha, wei, tt);
+            ch.hyoka = Some(val);
+            node.kyokumen += ch.kyokumen;
+            if cfg!(feature="withtt") {
+                tt.set(ban, -val, depth - 1);
+            }
+            ban.undo_move(&undo);
+            if newalpha < val {
+                newalpha = val;
+                node.best = Some(Best::new(val, mv));
+            } else if node.best.is_none() {
+                node.best = Some(Best::new(val, mv));
+            }
+            if newalpha >= beta {
+                // cut
+                return newalpha;
+            }
+            if maxval < val {
+                maxval = val;
+            }
+        }
+        maxval  // fail-soft
+    }
+
     #[allow(dead_code)]
     pub fn think_internal_pvs_tt(node:&mut NodeBB, ban : &bitboard::BitBoard, alpha : f32, beta : f32,
             wei : &weight::Weight, tt : &mut transptable::TranspositionTable) -> f32 {
@@ -433,6 +605,12 @@ impl NodeBB {
         if node.depth == 0 {
             return NodeBB::evalwtt(ban, wei, tt);
         }
+        // 読み切れる局面では確定石で結果の範囲が窓の外なら読まない
+        if node.depth as u32 >= ban.nblank() {
+            let (lower, upper) = ban.stable_bounds();
+            if upper as f32 <= alpha {return upper as f32;}
+            if lower as f32 >= beta {return lower as f32;}
+        }
 
         let mut newalpha = alpha;
         let depth = node.depth;
@@ -703,3 +881,30 @@ fn test_nodebb() {
     node.child.push(node34);
     assert_eq!(node.dumpv(), "val:Some(99.9), 9876 nodes. A2g8B1h7");
 }
+
+#[test]
+fn test_think_ab_inplace() {
+    let mut wei = weight::Weight::new();
+    wei.init();
+    let tbl = [
+        ("8/8/8/3Aa3/3aA3/8/8/8 b", 5),
+        ("8/8/2A5/2Ab3/2Ba3/8/8/8 w", 4),
+        ("2A1A1A1/3c2/Ac1bA/3c2/2cAa1/1a1Aa2A/A3a3/4A3 b", 3),
+        ("2Ad1/1BdA1/Aa1dA/BbA3/Bd2/Abd1/3c2/8 b", 3),
+        ("aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w", 3),
+        ("h/aFa/aC1Ba/aFa/aFa/aFa/aFa/h w", 3),
+    ];
+    for (rfen, depth) in tbl {
+        let ban = bitboard::BitBoard::from(rfen).unwrap();
+        let mut tt = transptable::TranspositionTable::with_capacity(1024);
+        let mut node = NodeBB::root(depth);
+        let val = NodeBB::think_ab_simple_gk_tt(&ban, depth, &mut node, &wei, &mut tt);
+        let mut tt2 = transptable::TranspositionTable::with_capacity(1024);
+        let mut node2 = NodeBB::root(depth);
+        let val2 = NodeBB::think_ab_inplace_gk_tt(&ban, depth, &mut node2, &wei, &mut tt2);
+        assert_eq!(val, val2, "{rfen}");
+        assert_eq!(node.kyokumen, node2.kyokumen, "{rfen}");
+        assert_eq!(node.best_order(), node2.best_order(), "{rfen}");
+        assert_eq!(node.best.unwrap().xypos(), node2.best.unwrap().xypos(), "{rfen}");
+    }
+}
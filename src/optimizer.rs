@@ -0,0 +1,289 @@
+use super::*;
+
+/// file name of the optimizer state in a checkpoint.
+pub const STATEFILE : &str = "optimizer.txt";
+const STATE_HEADER : &str = "# ruversi optimizer state";
+const EPS : f32 = 1e-8;
+
+/// how to update weights w/ gradients.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Method {
+    /// SGD w/ momentum. 0 is plain SGD.
+    Sgd(f32),
+    /// Adam(beta1, beta2). weight decay is added to gradients as L2.
+    Adam(f32, f32),
+    /// AdamW(beta1, beta2). weight decay is decoupled from gradients.
+    AdamW(f32, f32),
+}
+
+impl std::fmt::Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Method::Sgd(mom) => write!(f, "sgd:{mom}"),
+            Method::Adam(b1, b2) => write!(f, "adam:{b1}:{b2}"),
+            Method::AdamW(b1, b2) => write!(f, "adamw:{b1}:{b2}"),
+        }
+    }
+}
+
+impl Method {
+    /// - sgd[:<momentum>]
+    /// - adam[:<beta1>:<beta2>]
+    /// - adamw[:<beta1>:<beta2>]
+    pub fn from(txt : &str) -> Option<Method> {
+        let lower = txt.to_ascii_lowercase();
+        let elem = lower.split(':').collect::<Vec<_>>();
+        let ratio = |r : &str| r.parse::<f32>().ok().filter(|r| (0.0..1.0).contains(r));
+        match elem.as_slice() {
+            ["sgd"] => Some(Method::Sgd(0.0)),
+            ["sgd", mom] => Some(Method::Sgd(ratio(mom)?)),
+            ["adam"] => Some(Method::Adam(0.9, 0.999)),
+            ["adam", b1, b2] => Some(Method::Adam(ratio(b1)?, ratio(b2)?)),
+            ["adamw"] => Some(Method::AdamW(0.9, 0.999)),
+            ["adamw", b1, b2] => Some(Method::AdamW(ratio(b1)?, ratio(b2)?)),
+            _ => None,
+        }
+    }
+}
+
+/// per layer multipliers of the learning rate.
+///
+/// # Arguments
+/// - `txt` : <input>:<hidden>:<output>
+pub fn parse_layerlr(txt : &str) -> Option<[f32 ; weight::N_LAYER]> {
+    let elem = txt.split(':').collect::<Vec<_>>();
+    if elem.len() != weight::N_LAYER {return None;}
+
+    let mut lr = [0f32 ; weight::N_LAYER];
+    for (l, e) in lr.iter_mut().zip(elem.iter()) {
+        *l = e.parse::<f32>().ok().filter(|l| *l >= 0.0)?;
+    }
+    Some(lr)
+}
+
+/// updates weights and keeps moments of gradients.
+#[derive(Clone)]
+pub struct Optimizer {
+    method : Method,
+    decay : f32,  // 重み減衰
+    layerlr : [f32 ; weight::N_LAYER],  // 層毎の学習率の倍率
+    step : u64,  // 更新回数
+    m : Vec<f32>,  // 1次のモーメント。SGDでは速度
+    v : Vec<f32>,  // 2次のモーメント
+}
+
+impl Optimizer {
+    /// # Arguments
+    /// - `method` : update rule.
+    /// - `decay` : weight decay. L2 for SGD and Adam, decoupled for AdamW.
+    /// - `layerlr` : multipliers of the learning rate for input, hidden and output layers.
+    pub fn new(method : Method, decay : f32, layerlr : [f32 ; weight::N_LAYER]) -> Optimizer {
+        Optimizer {
+            method,
+            decay,
+            layerlr,
+            step : 0,
+            m : Vec::new(),
+            v : Vec::new(),
+        }
+    }
+
+    /// plain SGD. same as weight::Weight::update().
+    pub fn sgd() -> Optimizer {
+        Optimizer::new(Method::Sgd(0.0), 0.0, [1.0 ; weight::N_LAYER])
+    }
+
+    /// # of updates so far.
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    /// update `weight` by one step.
+    ///
+    /// # Arguments
+    /// - `weight` : weights to be updated.
+    /// - `grad` : mean gradients of a minibatch.
+    /// - `eta` : learning rate.
+    pub fn update(&mut self, weight : &mut weight::Weight, grad : &[f32], eta : f32) {
+        if self.m.is_empty() {
+            self.m = vec![0f32 ; weight::N_WEIGHT_ALL];
+            if !matches!(self.method, Method::Sgd(_)) {
+                self.v = vec![0f32 ; weight::N_WEIGHT_ALL];
+            }
+        }
+        self.step += 1;
+
+        let params = weight.params();
+        let mut delta = vec![0f32 ; weight::N_WEIGHT_ALL];
+        for (i, d) in delta.iter_mut().enumerate() {
+            let Some(layer) = weight::Weight::layer_of(i) else {continue;};
+
+            let lr = eta * self.layerlr[layer];
+            let w = params[i];
+            match self.method {
+                Method::Sgd(mom) => {
+                    let g = grad[i] + self.decay * w;
+                    self.m[i] = mom * self.m[i] + g;
+                    *d = lr * self.m[i];
+                },
+                Method::Adam(b1, b2) | Method::AdamW(b1, b2) => {
+                    let decoupled = matches!(self.method, Method::AdamW(..));
+                    let g = if decoupled {grad[i]} else {grad[i] + self.decay * w};
+                    self.m[i] = b1 * self.m[i] + (1.0 - b1) * g;
+                    self.v[i] = b2 * self.v[i] + (1.0 - b2) * g * g;
+                    let mhat = self.m[i] / (1.0 - b1.powi(self.step as i32));
+                    let vhat = self.v[i] / (1.0 - b2.powi(self.step as i32));
+                    *d = lr * mhat / (vhat.sqrt() + EPS);
+                    if decoupled {
+                        *d += lr * self.decay * w;
+                    }
+                },
+            }
+        }
+        weight.update(&delta, 1.0);
+    }
+
+    /// serialize the state.
+    ///
+    /// format:
+    /// ```text
+    /// # ruversi optimizer state
+    /// method,adam:0.9:0.999
+    /// step,123
+    /// m,0.1,0.2,...
+    /// v,0.01,0.02,...
+    /// ```
+    pub fn to_str(&self) -> String {
+        let join = |v : &[f32]| {
+            v.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(",")
+        };
+        format!("{STATE_HEADER}\nmethod,{}\nstep,{}\nm,{}\nv,{}\n",
+            self.method, self.step, join(&self.m), join(&self.v))
+    }
+
+    /// restore the state serialized by to_str().
+    /// decay and layerlr are not changed.
+    ///
+    /// # Returns
+    /// error message if the state is broken or for another method.
+    pub fn read_state(&mut self, txt : &str) -> Result<(), String> {
+        let parse = |v : &str| {
+            if v.is_empty() {return Ok(Vec::new());}
+
+            let v = v.split(',').map(|a| a.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+            if v.len() != weight::N_WEIGHT_ALL {
+                return Err(format!("{} moments for {} weights.",
+                    v.len(), weight::N_WEIGHT_ALL));
+            }
+            Ok(v)
+        };
+
+        let mut st = Optimizer::new(self.method, self.decay, self.layerlr);
+        for line in txt.lines() {
+            if line.starts_with('#') || line.is_empty() {continue;}
+
+            let (key, val) = line.split_once(',')
+                .ok_or(format!("invalid line \"{line}\"."))?;
+            match key {
+                "method" if Method::from(val) != Some(self.method) => {
+                    return Err(format!("optimizer {val} != {}.", self.method));
+                },
+                "step" => {
+                    st.step = val.parse().map_err(|e| format!("step {val}: {e}"))?;
+                },
+                "m" => {st.m = parse(val)?;},
+                "v" => {st.v = parse(val)?;},
+                _ => {},
+            }
+        }
+        if !st.m.is_empty() && !matches!(self.method, Method::Sgd(_))
+                && st.v.len() != st.m.len() {
+            return Err("no 2nd moments.".to_string());
+        }
+        *self = st;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_method() {
+    assert_eq!(Method::from("sgd"), Some(Method::Sgd(0.0)));
+    assert_eq!(Method::from("SGD:0.9"), Some(Method::Sgd(0.9)));
+    assert_eq!(Method::from("adam"), Some(Method::Adam(0.9, 0.999)));
+    assert_eq!(Method::from("adamw:0.8:0.99"), Some(Method::AdamW(0.8, 0.99)));
+    assert_eq!(Method::from("sgd:1"), None);
+    assert_eq!(Method::from("adam:0.9"), None);
+    assert_eq!(Method::from("rmsprop"), None);
+    for m in [Method::Sgd(0.5), Method::Adam(0.9, 0.999), Method::AdamW(0.8, 0.99)] {
+        assert_eq!(Method::from(&m.to_string()), Some(m));
+    }
+    assert_eq!(parse_layerlr("1:0.5:0.1"), Some([1.0, 0.5, 0.1]));
+    assert_eq!(parse_layerlr("1:0.5"), None);
+    assert_eq!(parse_layerlr("1:-1:1"), None);
+}
+
+#[test]
+fn test_optimizer() {
+    let mut w = weight::Weight::new();
+    w.init();
+    // パディングの勾配は0
+    let grad = w.params().iter().enumerate().map(|(i, a)| {
+        if weight::Weight::layer_of(i).is_none() {return 0.0;}
+
+        a * 0.5 + if *a >= 0.0 {0.01} else {-0.01}
+    }).collect::<Vec<f32>>();
+
+    // 素のSGDはWeight::update()と同じ
+    let mut w1 = weight::Weight::new();
+    w1.copy(&w);
+    w1.update(&grad, 0.1);
+    let mut w2 = weight::Weight::new();
+    w2.copy(&w);
+    Optimizer::sgd().update(&mut w2, &grad, 0.1);
+    assert!(w1.params() == w2.params());
+
+    // 層毎の学習率0なら変わらない
+    let mut w3 = weight::Weight::new();
+    w3.copy(&w);
+    let mut opt = Optimizer::new(Method::Adam(0.9, 0.999), 0.0, [0.0, 1.0, 0.0]);
+    opt.update(&mut w3, &grad, 0.1);
+    for (i, (a, b)) in w.params().iter().zip(w3.params().iter()).enumerate() {
+        match weight::Weight::layer_of(i) {
+            Some(1) => {
+                // Adamの最初の一歩は学習率分だけ動く
+                assert!(((a - b).abs() - 0.1).abs() < 1e-3, "{i} {a} {b}");
+            },
+            _ => {assert_eq!(a, b);},
+        }
+    }
+
+    // 勾配0でもAdamWは減衰する
+    let zero = vec![0f32 ; weight::N_WEIGHT_ALL];
+    let mut w4 = weight::Weight::new();
+    w4.copy(&w);
+    let mut opt = Optimizer::new(Method::AdamW(0.9, 0.999), 0.1, [1.0 ; weight::N_LAYER]);
+    opt.update(&mut w4, &zero, 0.1);
+    for (i, (a, b)) in w.params().iter().zip(w4.params().iter()).enumerate() {
+        if weight::Weight::layer_of(i).is_none() {continue;}
+
+        assert!((a * 0.99 - b).abs() < 1e-6, "{i} {a} {b}");
+    }
+
+    // 状態を書き出して読み戻すと同じように更新される
+    let mut opt = Optimizer::new(Method::Sgd(0.9), 0.01, [1.0 ; weight::N_LAYER]);
+    let mut w5 = weight::Weight::new();
+    w5.copy(&w);
+    opt.update(&mut w5, &grad, 0.01);
+    let mut opt2 = Optimizer::new(Method::Sgd(0.9), 0.01, [1.0 ; weight::N_LAYER]);
+    opt2.read_state(&opt.to_str()).unwrap();
+    assert_eq!(opt2.step(), 1);
+    let mut w6 = weight::Weight::new();
+    w6.copy(&w5);
+    opt.update(&mut w5, &grad, 0.01);
+    opt2.update(&mut w6, &grad, 0.01);
+    assert!(w5.params() == w6.params());
+    let mut adam = Optimizer::new(Method::Adam(0.9, 0.999), 0.0, [1.0 ; weight::N_LAYER]);
+    assert!(adam.read_state(&opt.to_str()).is_err());
+    assert!(adam.read_state("method,adam:0.9:0.999\nm,1,2\n").is_err());
+}
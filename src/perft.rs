@@ -0,0 +1,140 @@
+use super::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// 終局していたらtrue。どちらも打てない。
+fn is_gameover(ban : &bitboard::BitBoard, moves : &[u8]) -> bool {
+    if moves[0] != bitboard::PASS {return false;}
+
+    let passed = ban.r#move(bitboard::PASS).unwrap();
+    passed.genmove().is_none_or(|mvs| mvs[0] == bitboard::PASS)
+}
+
+/// count leaf nodes `depth` plies ahead.
+/// a pass is counted as a ply.
+/// a game which ends before `depth` is counted as a leaf.
+/// # Arguments
+/// - ban : board to start from.
+/// - depth : number of plies.
+/// # Returns
+/// number of leaf nodes.
+pub fn perft(ban : &bitboard::BitBoard, depth : u8) -> u64 {
+    if depth == 0 {return 1;}
+
+    let moves = match ban.genmove() {
+        None => {return 1;},  // no empty cells.
+        Some(mvs) => mvs,
+    };
+    if is_gameover(ban, &moves) {return 1;}
+
+    if depth == 1 {return moves.len() as u64;}
+
+    moves.iter().map(|&mv| {
+        perft(&ban.r#move(mv).unwrap(), depth - 1)
+    }).sum()
+}
+
+/// count leaf nodes for every move from `ban`.
+/// moves are shared by `nthreads` threads.
+/// # Arguments
+/// - ban : board to start from.
+/// - depth : number of plies including the first move.
+/// - nthreads : number of threads.
+/// # Returns
+/// list of (move, number of leaf nodes). empty if the game is over.
+pub fn divide(ban : &bitboard::BitBoard, depth : u8, nthreads : usize)
+        -> Vec<(u8, u64)> {
+    if depth == 0 {return Vec::new();}
+
+    let moves = match ban.genmove() {
+        None => {return Vec::new();},
+        Some(mvs) => mvs,
+    };
+    if is_gameover(ban, &moves) {return Vec::new();}
+
+    let next = AtomicUsize::new(0);
+    let mut result = vec![(0u8, 0u64) ; moves.len()];
+    let counts = Mutex::new(&mut result);
+    thread::scope(|s| {
+        for _i in 0..nthreads.clamp(1, moves.len()) {
+            s.spawn(|| {
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    if idx >= moves.len() {break;}
+
+                    let mv = moves[idx];
+                    let cnt = perft(&ban.r#move(mv).unwrap(), depth - 1);
+                    counts.lock().unwrap()[idx] = (mv, cnt);
+                }
+            });
+        }
+    });
+    result
+}
+
+/// a move in text. ex. f5, pass
+fn movetxt(mv : u8) -> String {
+    if mv == bitboard::PASS {
+        return String::from("pass");
+    }
+
+    let (x, y) = bitboard::cell2xy(mv);
+    postxt(x, y)
+}
+
+/// run perft and show the result.
+/// # Arguments
+/// - rfen : RFEN to start from. initial position if empty.
+/// - depth : number of plies.
+/// - show_divide : show the count of every move.
+/// # Returns
+/// Ok(()) for success, otherwise Err(error message).
+pub fn run(rfen : &str, depth : u8, show_divide : bool) -> Result<(), String> {
+    let ban = if rfen.is_empty() {
+        bitboard::BitBoard::new()
+    } else {
+        bitboard::BitBoard::from(rfen)?
+    };
+    ban.put();
+
+    let nthreads = thread::available_parallelism().map_or(1, |n| n.get());
+    let st = Instant::now();
+    let moves = divide(&ban, depth, nthreads);
+    let total = if moves.is_empty() {
+        perft(&ban, depth)
+    } else {
+        moves.iter().map(|(_mv, cnt)| cnt).sum()
+    };
+    let ft = st.elapsed();
+    if show_divide {
+        for (mv, cnt) in moves.iter() {
+            println!("{}: {cnt}", movetxt(*mv));
+        }
+    }
+    println!("perft({depth}) = {total} {}msec", ft.as_millis());
+    Ok(())
+}
+
+#[test]
+fn test_perft() {
+    let ban = bitboard::BitBoard::new();
+    let expected = [1, 4, 12, 56, 244, 1396, 8200, 55092, 390216];
+    for (depth, &cnt) in expected.iter().enumerate() {
+        assert_eq!(perft(&ban, depth as u8), cnt, "depth:{depth}");
+    }
+    let div = divide(&ban, 6, 2);
+    assert_eq!(div.len(), 4);
+    assert!(div.iter().all(|&(_mv, cnt)| cnt == 8200 / 4));
+    assert_eq!(
+        div.iter().map(|&(mv, _cnt)| movetxt(mv)).collect::<Vec<_>>(),
+        ["e3", "f4", "c5", "d6"]);
+
+    // 白はパスするしかない
+    let ban = bitboard::BitBoard::from("H/H/H/H/H/H/H/Fa1 w").unwrap();
+    assert_eq!(perft(&ban, 1), 1);
+    assert_eq!(perft(&ban, 3), 1);
+    assert_eq!(divide(&ban, 2, 2), [(bitboard::PASS, 1)]);
+    // 終局
+    let ban = bitboard::BitBoard::from("H/H/H/H/H/H/H/G1 w").unwrap();
+    assert_eq!(perft(&ban, 3), 1);
+    assert!(divide(&ban, 3, 2).is_empty());
+}
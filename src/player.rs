@@ -0,0 +1,419 @@
+use super::*;
+
+use std::io::{self, Write};
+
+/// someone who chooses moves in a game run by GameBB::start_with().
+pub trait Player {
+    /// name for messages.
+    fn name(&self) -> String;
+
+    /// called before the first move of a game.
+    fn new_game(&mut self, _ban : &bitboard::BitBoard) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// choose a move on `ban`.
+    ///
+    /// # Returns
+    /// (cell index or PASS, search result by this program if any) or error message.
+    fn play(&mut self, ban : &bitboard::BitBoard)
+        -> Result<(u8, Option<game::Search>), String>;
+
+    /// do something on `ban`. a move by play() if not overridden.
+    ///
+    /// # Arguments
+    /// - `ban` : board to be played.
+    /// - `kifu` : moves so far.
+    fn act(&mut self, ban : &bitboard::BitBoard, _kifu : &kifu::Kifu)
+            -> Result<game::Action, String> {
+        self.play(ban).map(|(xy, search)| game::Action::Move(xy, search))
+    }
+
+    /// called after the game is over.
+    fn end_game(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// # Returns
+/// the only legal move or PASS if there is no choice.
+fn forced(ban : &bitboard::BitBoard) -> Option<u8> {
+    let moves = ban.legal_moves();
+    match moves.count_ones() {
+        0 => Some(bitboard::PASS),
+        1 => bitboard::MoveIter::new(moves).next(),
+        _ => None,
+    }
+}
+
+/// "f5" from another program to a cell index.
+fn parse_pos(pos : &str, name : &str) -> Result<u8, String> {
+    kifu::xy_from(pos).ok_or_else(|| format!("invalid move \"{pos}\" from {name}."))
+}
+
+/// this program w/ its own eval table and transposition table.
+pub struct Engine<'a> {
+    f : game::SearchFn,
+    depth : u8,
+    wei : &'a weight::Weight,
+    tt : transptable::TranspositionTable,
+    strength : Option<level::Strength>,
+    /// # of moves chosen by `strength` in a game. None : every move.
+    strength_moves : Option<usize>,
+    /// # of moves played in this game.
+    nmoves : usize,
+}
+
+impl<'a> Engine<'a> {
+    /// # Arguments
+    /// - `f` : fn for searching.
+    /// - `depth` : searching depth.
+    /// - `wei` : eval table.
+    /// - `cachesz` : size of the transposition table.
+    pub fn new(f : game::SearchFn, depth : u8, wei : &'a weight::Weight, cachesz : usize)
+            -> Engine<'a> {
+        Engine {
+            f,
+            depth,
+            wei,
+            tt : transptable::TranspositionTable::with_capacity(cachesz),
+            strength : None,
+            strength_moves : None,
+            nmoves : 0,
+        }
+    }
+
+    /// choose moves by `strength` instead of the best move w/ `f` and `depth`.
+    pub fn set_strength(&mut self, strength : level::Strength) {
+        self.strength = Some(strength);
+        self.strength_moves = None;
+    }
+
+    /// choose the first `nmoves` moves in a game by `strength`
+    /// and the rest w/ `f` and `depth`.
+    pub fn set_opening(&mut self, strength : level::Strength, nmoves : usize) {
+        self.strength = Some(strength);
+        self.strength_moves = Some(nmoves);
+    }
+
+    /// # Returns
+    /// static evaluation of `ban` from black's view.
+    pub fn eval(&self, ban : &bitboard::BitBoard) -> f32 {
+        nodebb::NodeBB::evaluate(ban, self.wei)
+    }
+}
+
+impl Player for Engine<'_> {
+    fn name(&self) -> String {
+        String::from("ruversi")
+    }
+
+    fn new_game(&mut self, _ban : &bitboard::BitBoard) -> Result<(), String> {
+        self.nmoves = 0;
+        Ok(())
+    }
+
+    fn play(&mut self, ban : &bitboard::BitBoard)
+            -> Result<(u8, Option<game::Search>), String> {
+        let st = Instant::now();
+        self.nmoves += 1;
+        // パスは普通に探索する
+        let bystrength = ban.legal_moves() != 0
+            && self.strength_moves.is_none_or(|n| self.nmoves <= n);
+        if let Some(strength) = self.strength.as_mut().filter(|_| bystrength) {
+            let (val, node) = strength.search(ban, self.wei, &mut self.tt);
+            let xy = node.best.as_ref().unwrap().xypos();
+            self.tt.next();
+            return Ok((xy, Some((val, node, st.elapsed()))));
+        }
+        let mut node = nodebb::NodeBB::root(self.depth);
+        let val = (self.f)(ban, self.depth, &mut node, self.wei, &mut self.tt)
+            .ok_or_else(|| format!("failed to search {ban}."))?;
+        let ft = st.elapsed();
+        let xy = node.best.as_ref().ok_or_else(|| format!("no move on {ban}."))?.xypos();
+        self.tt.next();
+        Ok((xy, Some((val, node, ft))))
+    }
+}
+
+/// commands of Human.
+const HUMAN_HELP : &str = "\
+  a1 ~ h8      put a stone.
+  undo         take back your last move and the moves after that.
+  hint         show the move and the value by the engine.
+  moves        show legal moves on the board.
+  eval         show the evaluation of the board by the engine.
+  save <file>  put the game into a kifu file.
+  load <file>  continue a game in a kifu, GGF or transcript file.
+  flip         swap sides w/ the opponent.
+  resign       give up the game.
+  help         show this.";
+
+/// you via the standard input.
+pub struct Human<'a> {
+    verbose : myoption::Verbose,
+    engine : Option<Engine<'a>>,
+}
+
+impl<'a> Human<'a> {
+    pub fn new(verbose : myoption::Verbose) -> Human<'a> {
+        Human { verbose, engine : None }
+    }
+
+    /// use `engine` for hint and eval.
+    pub fn set_engine(&mut self, engine : Engine<'a>) {
+        self.engine = Some(engine);
+    }
+
+    /// show `ban` w/ the last move in `kifu` and `marks`.
+    fn show(&self, ban : &bitboard::BitBoard, kifu : &kifu::Kifu, marks : u64) {
+        let last = kifu.list.last().map(|te| te.xy()).filter(|xy| *xy != bitboard::PASS);
+        println!("{}", ban.render(last, marks));
+    }
+
+    /// run a command other than a move.
+    ///
+    /// # Returns
+    /// an action for the game if any.
+    fn command(&mut self, cmd : &str, arg : &str, ban : &bitboard::BitBoard,
+            kifu : &kifu::Kifu) -> Option<game::Action> {
+        match cmd {
+            "undo" => {
+                if kifu.list.iter().any(|te| te.teban == ban.teban && te.xy() != bitboard::PASS) {
+                    return Some(game::Action::Undo);
+                }
+                println!("nothing to undo.");
+            },
+            "hint" | "eval" => {
+                let Some(engine) = self.engine.as_mut() else {
+                    println!("no engine for {cmd}.");
+                    return None;
+                };
+                if cmd == "eval" {
+                    println!("eval:{:+.1} discs:{:+}", engine.eval(ban), ban.count());
+                    return None;
+                }
+                match engine.play(ban) {
+                    Ok((xy, Some((val, node, _)))) => {
+                        let te = kifu::Te::new(xy, ban.teban, String::new());
+                        println!("hint:{} val:{val:+.1} pv:{}", te.pos(), node.best_order());
+                    },
+                    Ok(_) => {},
+                    Err(msg) => println!("{msg}"),
+                }
+            },
+            "moves" => {
+                self.show(ban, kifu, ban.legal_moves());
+                let moves = bitboard::MoveIter::new(ban.legal_moves())
+                    .map(|xy| kifu::Te::new(xy, ban.teban, String::new()).pos())
+                    .collect::<Vec<_>>();
+                println!("legal moves: {}", moves.join(" "));
+            },
+            "save" if !arg.is_empty() => {
+                match kifu.save(arg) {
+                    Ok(()) => println!("put the game into {arg}."),
+                    Err(msg) => println!("{msg}"),
+                }
+            },
+            "load" if !arg.is_empty() => {
+                let loaded = kifu::load(arg).and_then(|k| {
+                    game::GameBB::from_kifu(k.copy()).map(|_| k)
+                });
+                match loaded {
+                    Ok(k) => return Some(game::Action::Load(k)),
+                    Err(msg) => println!("{msg}"),
+                }
+            },
+            "save" | "load" => println!("usage: {cmd} <file>"),
+            "flip" => return Some(game::Action::Flip),
+            "resign" => return Some(game::Action::Resign),
+            "help" | "?" => println!("{HUMAN_HELP}"),
+            _ => println!("unknown command : {cmd}. type help for commands."),
+        }
+        None
+    }
+}
+
+impl Player for Human<'_> {
+    fn name(&self) -> String {
+        String::from("you")
+    }
+
+    fn play(&mut self, ban : &bitboard::BitBoard)
+            -> Result<(u8, Option<game::Search>), String> {
+        match self.act(ban, &kifu::Kifu::new())? {
+            game::Action::Move(xy, search) => Ok((xy, search)),
+            _ => Err(String::from("only moves are allowed here.")),
+        }
+    }
+
+    fn act(&mut self, ban : &bitboard::BitBoard, kifu : &kifu::Kifu)
+            -> Result<game::Action, String> {
+        if ban.legal_moves() == 0 {
+            return Ok(game::Action::Move(bitboard::PASS, None));
+        }
+        if !self.verbose.is_silent() {self.show(ban, kifu, 0);}
+
+        loop {
+            print!("your turn[a1 ~ h8, help]:");
+            io::stdout().flush().unwrap();
+            let mut txt = String::new();
+            if io::stdin().read_line(&mut txt).map_err(|e| e.to_string())? == 0 {
+                return Err(String::from("no input from stdin."));
+            }
+            let mut words = txt.split_whitespace();
+            let Some(cmd) = words.next() else {continue;};
+            let arg = words.next().unwrap_or("");
+            match kifu::xy_from(cmd) {
+                Some(xy) if xy != bitboard::PASS
+                        && ban.legal_moves() & (bitboard::LSB_CELL << xy) != 0 => {
+                    return Ok(game::Action::Move(xy, None));
+                },
+                Some(_) => println!("{cmd} is not allowed."),
+                None if cmd.chars().nth(1).is_some_and(|c| c.is_ascii_digit()) => {
+                    println!("invalid position : {cmd}");
+                },
+                None => {
+                    let cmd = cmd.to_ascii_lowercase();
+                    if let Some(act) = self.command(&cmd, arg, ban, kifu) {
+                        return Ok(act);
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Edax as a subprocess.
+pub struct Edax {
+    er : edaxrunner::EdaxRunner,
+}
+
+impl Edax {
+    pub fn new(er : edaxrunner::EdaxRunner) -> Edax {
+        Edax { er }
+    }
+}
+
+impl Player for Edax {
+    fn name(&self) -> String {
+        String::from("edax")
+    }
+
+    fn play(&mut self, ban : &bitboard::BitBoard)
+            -> Result<(u8, Option<game::Search>), String> {
+        if let Some(xy) = forced(ban) {
+            return Ok((xy, None));
+        }
+
+        let (pos, _) = self.er.run(&ban.to_obf())
+            .map_err(|msg| format!("error running edax... [{msg}]"))?;
+        Ok((parse_pos(&pos, "edax")?, None))
+    }
+}
+
+/// another Ruversi as a subprocess.
+pub struct Ruversi {
+    rr : edaxrunner::RuversiRunner,
+}
+
+impl Ruversi {
+    pub fn new(rr : edaxrunner::RuversiRunner) -> Ruversi {
+        Ruversi { rr }
+    }
+}
+
+impl Player for Ruversi {
+    fn name(&self) -> String {
+        String::from("ruversi")
+    }
+
+    fn play(&mut self, ban : &bitboard::BitBoard)
+            -> Result<(u8, Option<game::Search>), String> {
+        if let Some(xy) = forced(ban) {
+            return Ok((xy, None));
+        }
+
+        let (pos, _) = self.rr.run(&ban.to_string())
+            .map_err(|msg| format!("error running ruversi... [{msg}]"))?;
+        Ok((parse_pos(&pos, "ruversi")?, None))
+    }
+}
+
+/// an engine via othello engine protocol like Cassio does.
+pub struct Cassio {
+    oep : cassio::OthelloEngineProtocolServer,
+    depth : u8,
+    verbose : myoption::Verbose,
+}
+
+impl Cassio {
+    /// launch an engine in `cconf`.
+    pub fn new(cconf : &str, depth : u8, verbose : myoption::Verbose)
+            -> Result<Cassio, String> {
+        let er = edaxrunner::CassioRunner::from_config(
+                &std::path::PathBuf::from(cconf))?;
+        let mut oep = cassio::OthelloEngineProtocolServer::new1(er.run()?);
+        oep.setturn(bitboard::SENTE);
+        oep.init()?;
+        if verbose.is_verbose() {
+            println!("opponent:{}", oep.get_version()?);
+        }
+        Ok(Cassio { oep, depth, verbose })
+    }
+}
+
+impl Player for Cassio {
+    fn name(&self) -> String {
+        String::from("cassio")
+    }
+
+    fn new_game(&mut self, _ban : &bitboard::BitBoard) -> Result<(), String> {
+        self.oep.new_position()
+    }
+
+    fn play(&mut self, ban : &bitboard::BitBoard)
+            -> Result<(u8, Option<game::Search>), String> {
+        if let Some(xy) = forced(ban) {
+            return Ok((xy, None));
+        }
+
+        let alpha = -64f32;
+        let beta = 64f32;
+        let precision = 50;
+        let res = self.oep.midgame_search(&ban.to_obf(), alpha, beta, self.depth, precision)
+            .map_err(|msg| format!("error occured: {msg}!!"))?;
+        if self.verbose.is_verbose() {println!("{res}");}
+        // "..., <score> <move>, ..."
+        let mv = res.split(',').nth(1)
+            .and_then(|e| e.split_whitespace().nth(1))
+            .ok_or_else(|| format!("lack of response.. {res}"))?;
+        parse_pos(mv, "cassio").map(|xy| (xy, None))
+    }
+
+    fn end_game(&mut self) -> Result<(), String> {
+        self.oep.quit()
+    }
+}
+
+#[test]
+fn test_player() {
+    assert_eq!(forced(&bitboard::BitBoard::new()), None);
+    // 黒しか打てない
+    let ban = bitboard::BitBoard::from("8/8/8/8/8/8/8/Aa6 w").unwrap();
+    assert_eq!(forced(&ban), Some(bitboard::PASS));
+    let ban = ban.r#move(bitboard::PASS).unwrap();
+    assert_eq!(forced(&ban), kifu::xy_from("c8"));
+    assert_eq!(parse_pos("F5", "edax"), Ok(kifu::xy_from("f5").unwrap()));
+    assert_eq!(parse_pos("z9", "edax"), Err(String::from("invalid move \"z9\" from edax.")));
+
+    let mut w = weight::Weight::new();
+    w.init();
+    let mut engine = Engine::new(nodebb::NodeBB::think_ab_simple_gk_tt, 2, &w, 1024);
+    let ban = bitboard::BitBoard::new();
+    let (xy, search) = engine.play(&ban).unwrap();
+    assert!(ban.legal_moves() & (bitboard::LSB_CELL << xy) != 0);
+    let (val, node, _) = search.unwrap();
+    assert_eq!(node.best.unwrap().xypos(), xy);
+    assert!(val.is_finite());
+}
@@ -0,0 +1,381 @@
+use super::*;
+use rand::Rng;
+use rand::prelude::SliceRandom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const HISTORY_CSV : &str = "history.csv";
+const HISTORY_HEADER : &str =
+    "generation,games,positions,mse,total,win,draw,lose,winrate,R,95%,gate,promoted";
+const BEST : &str = "best.txt";
+const SPRT_ALPHA : f64 = 0.05;
+const SPRT_BETA : f64 = 0.05;
+/// level of opening moves in self-play games w/o --level.
+pub const OPENING_LEVEL : &str = "casual";
+/// # of opening moves chosen by the level for each color.
+pub const OPENING_MOVES : usize = 4;
+
+/// criterion to promote a candidate.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Gate {
+    /// lower bound of 95% confidence interval of R is larger than this.
+    Elo(f64),
+    /// SPRT(elo0, elo1) accepts elo1. alpha = beta = 0.05.
+    Sprt(f64, f64),
+}
+
+impl std::fmt::Display for Gate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Gate::Elo(min) => write!(f, "elo:{min}"),
+            Gate::Sprt(elo0, elo1) => write!(f, "sprt:{elo0}:{elo1}"),
+        }
+    }
+}
+
+impl Gate {
+    /// # Arguments
+    /// - `txt` : "elo", "elo:<min>", "sprt" or "sprt:<elo0>:<elo1>".
+    pub fn from(txt : &str) -> Option<Gate> {
+        let elem = txt.split(':').collect::<Vec<_>>();
+        let nums = elem[1..].iter().map(|a| a.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>().ok()?;
+        match (elem[0].to_ascii_lowercase().as_str(), nums.as_slice()) {
+            ("elo", []) => Some(Gate::Elo(0.0)),
+            ("elo", [min]) => Some(Gate::Elo(*min)),
+            ("sprt", []) => Some(Gate::Sprt(0.0, 10.0)),
+            ("sprt", [elo0, elo1]) if elo0 < elo1 => Some(Gate::Sprt(*elo0, *elo1)),
+            _ => None,
+        }
+    }
+
+    /// judge a candidate by a duel result.
+    ///
+    /// # Arguments
+    /// - `dr` : result from candidate's point of view.
+    /// - `finished` : no more games.
+    ///
+    /// # Returns
+    /// Some(promote or not) or None if more games are needed.
+    pub fn judge(&self, dr : &duelresult::DuelResult, finished : bool)
+            -> Option<bool> {
+        match self {
+            Gate::Elo(min) => {
+                if !finished {return None;}
+
+                let (r, ci) = dr.elo();
+                Some(r - ci > *min)
+            },
+            Gate::Sprt(elo0, elo1) => {
+                let res = dr.sprt(*elo0, *elo1, SPRT_ALPHA, SPRT_BETA);
+                if finished {Some(res.unwrap_or(false))} else {res}
+            },
+        }
+    }
+}
+
+/// generations of self-play, training and duel.
+///
+/// history/
+/// - best.txt : the best eval table.
+/// - history.csv : results of generations.
+/// - genNNN/kifu/ : self-play kifus.
+/// - genNNN/candidate.txt : trained eval table.
+/// - genNNN/duel.txt : duel result of the candidate against the best.
+pub struct SelfPlay {
+    pub history : String,
+    pub generations : usize,
+    pub games : usize,
+    pub depth : u8,
+    pub cachesz : usize,
+    /// fn for searching and its name in kifus.
+    pub search : game::SearchFn,
+    pub think : String,
+    /// level and its name for opening moves in self-play games.
+    pub opening : (String, level::Level),
+    pub duellv : i8,
+    pub gate : Gate,
+    pub eta : f32,
+    pub repeat : usize,
+    pub minibsize : usize,
+    pub target : dataset::Target,
+}
+
+impl SelfPlay {
+    /// play a game between 2 eval tables.
+    /// # Arguments
+    /// - et1 : SENTE
+    /// - et2 : GOTE
+    /// - seed : seed for opening moves by `opening`. None : no random moves.
+    fn play(&self, rfen : &str, et1 : &weight::Weight, et2 : &weight::Weight,
+            seed : Option<u64>) -> Result<kifu::Kifu, String> {
+        let mut g = game::GameBB::from(rfen);
+        g.set_verbose(&myoption::Verbose::Silent);
+        // 探索値を教師信号に使えるように記録する
+        g.set_recordval(true);
+        g.kifu.set_game_info("ruversi", "ruversi", self.depth, &self.think);
+        let mut sente = player::Engine::new(self.search, self.depth, et1, self.cachesz);
+        let mut gote = player::Engine::new(self.search, self.depth, et2, self.cachesz);
+        if let Some(seed) = seed {
+            // 同じ局面からでも違う棋譜になるように序盤だけばらつかせる
+            let (name, lv) = &self.opening;
+            sente.set_opening(level::Strength::new(*lv, seed), OPENING_MOVES);
+            gote.set_opening(level::Strength::new(*lv, seed + 1), OPENING_MOVES);
+            g.kifu.set_info("blacklevel", &format!("{name} seed {seed} moves {OPENING_MOVES}"));
+            g.kifu.set_info("whitelevel",
+                &format!("{name} seed {} moves {OPENING_MOVES}", seed + 1));
+        }
+        g.start(&mut sente, &mut gote)?;
+        Ok(g.kifu)
+    }
+
+    fn path(&self, sub : &str) -> String {
+        std::path::Path::new(&self.history).join(sub)
+            .to_str().unwrap().to_string()
+    }
+
+    /// # of generations in history.csv.
+    fn done(&self) -> Result<usize, String> {
+        let path = self.path(HISTORY_CSV);
+        if !std::path::Path::new(&path).exists() {return Ok(0);}
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("{path}: {e}"))?;
+        Ok(content.lines().skip(1).filter(|l| !l.is_empty()).count())
+    }
+
+    /// run generations.
+    /// the loop is resumed from history.csv and best.txt if they exist.
+    ///
+    /// # Arguments
+    /// - `initial` : the first best eval table.
+    pub fn run(&self, initial : &weight::Weight) -> Result<(), String> {
+        std::fs::create_dir_all(&self.history)
+            .map_err(|e| format!("{}: {e}", self.history))?;
+        let bestpath = self.path(BEST);
+        let mut best = Box::new(weight::Weight::new());
+        let start = self.done()?;
+        if std::path::Path::new(&bestpath).exists() {
+            println!("resume from generation {} w/ {bestpath}.", start + 1);
+            best.read(&bestpath)?;
+        } else {
+            best.copy(initial);
+            best.writev11(&bestpath)?;
+        }
+        if start == 0 {
+            std::fs::write(self.path(HISTORY_CSV), format!("{HISTORY_HEADER}\n"))
+                .map_err(|e| format!("{}: {e}", self.path(HISTORY_CSV)))?;
+        }
+
+        let mut ip = initialpos::InitialPos::read(initialpos::INITIALPOSFILE)?;
+        ip.append(initialpos::INITIALPOSFILE7)?;
+        let rfentbl = ip.rfens_uniq(
+            &["ZERO", "ONE", "TWO", "THREE", "FOUR", "FIVE", "SIX", "SEVEN"]);
+        let eq = initialpos::InitialPos::read(
+            &initialpos::equalfile(self.duellv))?.rfens_all();
+
+        for gen in start + 1..=start + self.generations {
+            let gendir = self.path(&format!("gen{gen:03}"));
+            println!("generation {gen}: {gendir}");
+
+            let kifudir = format!("{gendir}/kifu/");
+            self.selfplay(&best, &rfentbl, &kifudir)?;
+
+            let mut candidate = Box::new(weight::Weight::new());
+            candidate.copy(&best);
+            let mut tr = trainer::Trainer::new(self.eta, self.repeat, &kifudir);
+            tr.set_minibatch(self.minibsize);
+            tr.set_target(self.target);
+            tr.set_log(&format!("{gendir}/trainlog.csv"));
+            let positions = tr.readkifus()?;
+            if positions.is_empty() {
+                return Err(format!("no positions in {kifudir}."));
+            }
+            let mse = tr.learn_data(&mut candidate, &positions, &[])?;
+            candidate.writev11(&format!("{gendir}/candidate.txt"))?;
+
+            let (dr, promote) = self.duel(&candidate, &best, &eq);
+            let (r, ci) = dr.elo();
+            std::fs::write(format!("{gendir}/duel.txt"),
+                format!("{dr}\ngate:{},promoted:{promote}\n", self.gate))
+                .map_err(|e| format!("{gendir}/duel.txt: {e}"))?;
+            println!("{dr}");
+            println!("gate:{} promoted:{promote}", self.gate);
+            if promote {
+                best.copy(&candidate);
+                best.writev11(&bestpath)?;
+            }
+
+            let twin = dr.win.iter().sum::<u32>();
+            let tdraw = dr.draw.iter().sum::<u32>();
+            let tlose = dr.lose.iter().sum::<u32>();
+            let line = format!("{gen},{},{},{mse},{},{twin},{tdraw},{tlose},{:.4},{r:.1},{ci:.1},{},{promote}\n",
+                self.games, positions.len(), dr.total, dr.winrate(), self.gate);
+            let mut f = std::fs::OpenOptions::new().append(true)
+                .open(self.path(HISTORY_CSV)).map_err(|e| e.to_string())?;
+            f.write_all(line.as_bytes()).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// play games w/ the best eval table and store kifus in `kifudir`.
+    fn selfplay(&self, best : &weight::Weight, rfentbl : &[String], kifudir : &str)
+            -> Result<(), String> {
+        std::fs::create_dir_all(kifudir).map_err(|e| format!("{kifudir}: {e}"))?;
+        let mut rfens = rfentbl.to_vec();
+        rfens.shuffle(&mut rng::rng());
+        let rfens = rfens.iter().cycle().take(self.games).collect::<Vec<_>>();
+        // スレッドの順番によらないように先に決めておく
+        let mut rng = rng::rng();
+        let seeds = rfens.iter().map(|_| rng.gen::<u32>() as u64 * 2)
+            .collect::<Vec<_>>();
+
+        let nthreads = thread::available_parallelism().map_or(1, |n| n.get());
+        let next = AtomicUsize::new(0);
+        let err = Mutex::new(None);
+        thread::scope(|s| {
+            for _i in 0..nthreads.clamp(1, self.games.max(1)) {
+                s.spawn(|| {
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        if idx >= rfens.len() {break;}
+
+                        let res = self.play(rfens[idx], best, best, Some(seeds[idx]))
+                            .and_then(|mut kifu| {
+                                let bestpath = self.path(BEST);
+                                kifu.set_info("blackeval", &bestpath);
+                                kifu.set_info("whiteeval", &bestpath);
+                                kifu.save(&format!("{kifudir}kifu{idx:05}.txt"))
+                            });
+                        if let Err(e) = res {
+                            *err.lock().unwrap() = Some(e);
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        match err.into_inner().unwrap() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// duel between the candidate and the best on equal positions.
+    /// a pair of games w/ both colors is played on each position
+    /// until the gate is judged.
+    ///
+    /// # Returns
+    /// (result from candidate's point of view, promote or not)
+    fn duel(&self, candidate : &weight::Weight, best : &weight::Weight,
+            eq : &[String]) -> (duelresult::DuelResult, bool) {
+        let mut dr = duelresult::DuelResult::new();
+        for (i, rfen) in eq.iter().enumerate() {
+            match self.play(rfen, candidate, best, None) {
+                Ok(kifu) => dr.sresult(kifu.winner().unwrap()),
+                Err(e) => {println!("{e}");},
+            }
+            match self.play(rfen, best, candidate, None) {
+                Ok(kifu) => dr.gresult(kifu.winner().unwrap()),
+                Err(e) => {println!("{e}");},
+            }
+            if let Some(promote) = self.gate.judge(&dr, i + 1 == eq.len()) {
+                return (dr, promote);
+            }
+        }
+        let promote = self.gate.judge(&dr, true).unwrap_or(false);
+        (dr, promote)
+    }
+}
+
+#[test]
+fn test_gate() {
+    assert_eq!(Gate::from("elo"), Some(Gate::Elo(0.0)));
+    assert_eq!(Gate::from("ELO:-5"), Some(Gate::Elo(-5.0)));
+    assert_eq!(Gate::from("sprt"), Some(Gate::Sprt(0.0, 10.0)));
+    assert_eq!(Gate::from("sprt:-5:5"), Some(Gate::Sprt(-5.0, 5.0)));
+    assert_eq!(Gate::from("sprt:5:-5"), None);
+    assert_eq!(Gate::from("sprt:5"), None);
+    assert_eq!(Gate::from("elo:x"), None);
+    assert_eq!(Gate::from("bayes"), None);
+    assert_eq!(Gate::Sprt(0.0, 10.0).to_string(), "sprt:0:10");
+    assert_eq!(Gate::Elo(2.5).to_string(), "elo:2.5");
+
+    let mut dr = duelresult::DuelResult::new();
+    for _i in 0..10 {
+        dr.sresult(kifu::SENTEWIN);
+        dr.gresult(kifu::GOTEWIN);
+    }
+    assert_eq!(Gate::Elo(0.0).judge(&dr, false), None);
+    assert_eq!(Gate::Elo(0.0).judge(&dr, true), Some(true));
+    assert_eq!(Gate::Sprt(0.0, 10.0).judge(&dr, false), Some(true));
+    let op = dr.opponent();
+    assert_eq!(Gate::Elo(0.0).judge(&op, true), Some(false));
+    assert_eq!(Gate::Sprt(0.0, 10.0).judge(&op, false), Some(false));
+
+    let mut dr = duelresult::DuelResult::new();
+    dr.sresult(kifu::SENTEWIN);
+    dr.gresult(kifu::SENTEWIN);
+    assert_eq!(Gate::Sprt(0.0, 10.0).judge(&dr, false), None);
+    assert_eq!(Gate::Sprt(0.0, 10.0).judge(&dr, true), Some(false));
+}
+
+#[test]
+fn test_selfplay() {
+    let mut w = weight::Weight::new();
+    w.init();
+    let dir = std::env::temp_dir().join(
+        format!("ruversi_test_selfplay_{}", std::process::id()));
+    let kifudir = format!("{}/", dir.to_str().unwrap());
+    let sp = SelfPlay {
+        history : dir.to_str().unwrap().to_string(),
+        generations : 1,
+        games : 3,
+        depth : 1,
+        cachesz : 1024,
+        search : nodebb::NodeBB::think_ab_simple_gk_tt,
+        think : String::from("ab"),
+        opening : (OPENING_LEVEL.to_string(), level::Level::from(OPENING_LEVEL).unwrap()),
+        duellv : 1,
+        gate : Gate::Elo(0.0),
+        eta : 0.001,
+        repeat : 1,
+        minibsize : 16,
+        target : dataset::Target::Td(0.7),
+    };
+    // 終盤から始めて読み切りを軽くする
+    let rfens = ["aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w".to_string()];
+    sp.selfplay(&w, &rfens, &kifudir).unwrap();
+    let files = dataset::listkifu(&kifudir).unwrap();
+    assert_eq!(files, ["kifu00000.txt", "kifu00001.txt", "kifu00002.txt"]);
+    let kifu = dataset::readkifu(&format!("{kifudir}kifu00000.txt")).unwrap();
+    assert_eq!(kifu.list[0].rfen, rfens[0]);
+    assert!(kifu.score.is_some());
+    assert!(kifu.list[0].val.is_some());
+
+    assert!(kifu.info("blacklevel").is_some_and(|lv| lv.starts_with(OPENING_LEVEL)));
+
+    // 序盤はseedでばらつく。読み切りが軽くなるところまで決まった手順で進める
+    let mut ban = bitboard::BitBoard::new();
+    let mut i = 0;
+    while ban.nblank() > 6 {
+        let moves = ban.genmove().unwrap();
+        ban = ban.r#move(moves[i % moves.len()]).unwrap();
+        i = (i * 7 + 3) % 101;
+    }
+    assert!(!ban.is_passpass());
+    let ban = ban.to_string();
+    let moves = |seed| {
+        sp.play(&ban, &w, &w, seed).unwrap().list.iter()
+            .take(2 * OPENING_MOVES).map(|te| te.xy()).collect::<Vec<_>>()
+    };
+    assert_eq!(moves(Some(10)), moves(Some(10)));
+    assert!((0..4).any(|seed| moves(Some(seed)) != moves(Some(10))));
+    assert_eq!(moves(None), moves(None));
+
+    // 同じ評価関数同士なら先後で結果が入れ替わるだけ
+    let (dr, promote) = sp.duel(&w, &w, &rfens);
+    assert_eq!(dr.total, 2);
+    assert_eq!(dr.winrate(), 0.5);
+    assert!(!promote);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
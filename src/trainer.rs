@@ -1,5 +1,5 @@
 use rand::prelude::SliceRandom;
-use std::{sync::{Arc, Condvar, Mutex}, collections::VecDeque};
+use std::collections::VecDeque;
 
 use super::*;
 
@@ -11,18 +11,296 @@ const BIT_OUT_TIME : u32 = 0x8;
 pub const BIT_OUT_NOSAVE : u32 = 0x10;
 pub const BIT_OUT_DEFAULT : u32 =
         BIT_OUT_PROGESS | BIT_OUT_SUMMARY | BIT_OUT_TIME;
-static mut RFENCACHE : Vec<(String, i8)> = Vec::new();
-static mut BOARDCACHE : Vec<(bitboard::BitBoard, i8)> = Vec::new();
-const STOP : u32 = 0xffffffff_u32;
-const PROGRESS : u32 = 0xfffffffe_u32;
+pub const DEFAULT_ETA : f32 = 0.001;
+pub const DEFAULT_REPEAT : usize = 100;
+/// list of (board, target from black's view).
+pub type Positions = Vec<(bitboard::BitBoard, f32)>;
+
+const CHECKPOINT_WEIGHT : &str = "weight.txt";
+const CHECKPOINT_BEST : &str = "best.txt";
+const CHECKPOINT_STATE : &str = "state.txt";
+
+/// learning rate schedule.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Schedule {
+    /// same rate in every epoch.
+    Constant,
+    /// eta * gamma ^ (epoch / step)
+    Step(usize, f32),
+    /// eta * gamma ^ epoch
+    Exp(f32),
+    /// cosine annealing from eta to 0.
+    Cosine,
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Schedule::Constant => write!(f, "const"),
+            Schedule::Step(step, gamma) => write!(f, "step:{step}:{gamma}"),
+            Schedule::Exp(gamma) => write!(f, "exp:{gamma}"),
+            Schedule::Cosine => write!(f, "cos"),
+        }
+    }
+}
+
+impl Schedule {
+    /// - const
+    /// - step:<epochs>:<gamma>
+    /// - exp:<gamma>
+    /// - cos
+    pub fn from(txt : &str) -> Option<Schedule> {
+        let lower = txt.to_ascii_lowercase();
+        let elem = lower.split(':').collect::<Vec<_>>();
+        let gamma = |g : &str| g.parse::<f32>().ok().filter(|g| *g > 0.0 && *g <= 1.0);
+        match elem.as_slice() {
+            ["const"] => Some(Schedule::Constant),
+            ["step", step, g] => {
+                let step = step.parse::<usize>().ok().filter(|s| *s > 0)?;
+                Some(Schedule::Step(step, gamma(g)?))
+            },
+            ["exp", g] => Some(Schedule::Exp(gamma(g)?)),
+            ["cos"] => Some(Schedule::Cosine),
+            _ => None,
+        }
+    }
+
+    /// learning rate in `epoch`.
+    ///
+    /// # Arguments
+    /// - `eta` : initial learning rate.
+    /// - `epoch` : 0 ~ repeat - 1.
+    /// - `repeat` : # of epochs.
+    pub fn eta(&self, eta : f32, epoch : usize, repeat : usize) -> f32 {
+        match self {
+            Schedule::Constant => eta,
+            Schedule::Step(step, gamma) => eta * gamma.powi((epoch / step) as i32),
+            Schedule::Exp(gamma) => eta * gamma.powi(epoch as i32),
+            Schedule::Cosine => {
+                let t = epoch as f32 / repeat.max(1) as f32;
+                eta * 0.5 * (1.0 + (std::f32::consts::PI * t).cos())
+            },
+        }
+    }
+}
+
+/// empty cells are grouped by this in Metrics.
+const EMPTIES_STEP : usize = 10;
+/// 0-9, 10-19, ... 50-59, 60.
+pub const N_EMPTIES : usize = bitboard::CELL_2D / EMPTIES_STEP;
+
+/// errors of evaluations in a group of positions.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Errors {
+    pub n : usize,
+    se : f64,
+    ae : f64,
+    sign : usize,
+}
+
+impl Errors {
+    /// 石差0.5未満は引き分けとみなして符号を比べる。
+    fn add(&mut self, out : f32, target : f32) {
+        let sign = |v : f32| if v.abs() < 0.5 {0.0} else {v.signum()};
+        let diff = (out - target) as f64;
+        self.n += 1;
+        self.se += diff * diff;
+        self.ae += diff.abs();
+        if sign(out) == sign(target) {self.sign += 1;}
+    }
+
+    fn merge(&mut self, other : &Errors) {
+        self.n += other.n;
+        self.se += other.se;
+        self.ae += other.ae;
+        self.sign += other.sign;
+    }
+
+    /// # Returns
+    /// (mse, mae, sign accuracy). zeros w/o positions.
+    pub fn get(&self) -> (f32, f32, f32) {
+        if self.n == 0 {return (0.0, 0.0, 0.0);}
+
+        let n = self.n as f64;
+        ((self.se / n) as f32, (self.ae / n) as f32, (self.sign as f64 / n) as f32)
+    }
+}
+
+/// errors of an eval table on positions for every stage and empty cells.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Metrics {
+    stage : [Errors ; weight::N_PROGRESS_DIV],
+    empties : [Errors ; N_EMPTIES + 1],
+}
+
+impl Metrics {
+    /// evaluate positions w/ threads.
+    ///
+    /// # Arguments
+    /// - `weight` : eval table.
+    /// - `data` : list of (board, target from black's view).
+    /// - `nthreads` : # of threads.
+    pub fn measure(weight : &weight::Weight,
+            data : &[(bitboard::BitBoard, f32)], nthreads : usize) -> Metrics {
+        let measure = |chunk : &[(bitboard::BitBoard, f32)]| {
+            let mut m = Metrics::default();
+            for (ban, target) in chunk.iter() {
+                let (out, ..) = weight.forward(ban, ban.progress());
+                m.add(ban.progress(), ban.nblank(), out, *target);
+            }
+            m
+        };
+        if data.is_empty() {return Metrics::default();}
+
+        let chunk = data.len().div_ceil(nthreads.max(1));
+        thread::scope(|s| {
+            let handles = data.chunks(chunk).map(|c| {
+                s.spawn(move || measure(c))
+            }).collect::<Vec<_>>();
+            handles.into_iter().fold(Metrics::default(), |mut sum, h| {
+                sum.merge(&h.join().unwrap());
+                sum
+            })
+        })
+    }
+
+    /// # Arguments
+    /// - `stage` : stage of the position.
+    /// - `empties` : # of empty cells of the position.
+    /// - `out` : evaluation.
+    /// - `target` : true value.
+    pub fn add(&mut self, stage : usize, empties : u32, out : f32, target : f32) {
+        self.stage[stage].add(out, target);
+        self.empties[empties as usize / EMPTIES_STEP].add(out, target);
+    }
+
+    pub fn merge(&mut self, other : &Metrics) {
+        for (a, b) in self.stage.iter_mut().zip(other.stage.iter()) {
+            a.merge(b);
+        }
+        for (a, b) in self.empties.iter_mut().zip(other.empties.iter()) {
+            a.merge(b);
+        }
+    }
+
+    /// errors of a stage. None for all stages.
+    pub fn errors(&self, stage : Option<usize>) -> Errors {
+        match stage {
+            Some(p) => self.stage[p],
+            None => self.stage.iter().fold(Errors::default(), |mut sum, e| {
+                sum.merge(e);
+                sum
+            }),
+        }
+    }
 
-pub static mut WEIGHTBUFFER : Option<weight::Weight> = None;
+    /// (mse, mae, sign accuracy) of a stage. None for all stages.
+    pub fn get(&self, stage : Option<usize>) -> (f32, f32, f32) {
+        self.errors(stage).get()
+    }
+
+    /// # Returns
+    /// list of (name, errors) for all, every stage and empty cells.
+    pub fn rows(&self) -> Vec<(String, Errors)> {
+        let mut rows = vec![(String::from("all"), self.errors(None))];
+        for (i, e) in self.stage.iter().enumerate() {
+            rows.push((format!("stage{i}"), *e));
+        }
+        for (i, e) in self.empties.iter().enumerate() {
+            let name = if i == N_EMPTIES {
+                format!("e{}", i * EMPTIES_STEP)
+            } else {
+                format!("e{}-{}", i * EMPTIES_STEP, (i + 1) * EMPTIES_STEP - 1)
+            };
+            rows.push((name, *e));
+        }
+        rows
+    }
+
+    pub fn mse(&self) -> f32 {
+        self.get(None).0
+    }
+
+    /// "mse,mae,sign" for all stages and every stage.
+    pub fn csv_header(prefix : &str) -> String {
+        let mut cols = vec![format!("{prefix}mse,{prefix}mae,{prefix}sign")];
+        for p in 0..weight::N_PROGRESS_DIV {
+            cols.push(format!("{prefix}mse{p},{prefix}mae{p},{prefix}sign{p}"));
+        }
+        cols.join(",")
+    }
+
+    pub fn to_csv(&self) -> String {
+        let stages = std::iter::once(None).chain((0..weight::N_PROGRESS_DIV).map(Some));
+        stages.map(|p| {
+            let (mse, mae, sign) = self.get(p);
+            format!("{mse},{mae},{sign}")
+        }).collect::<Vec<_>>().join(",")
+    }
+}
+
+impl std::fmt::Display for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (mse, mae, sign) = self.get(None);
+        write!(f, "mse:{mse:.3} mae:{mae:.3} sign:{:.1}%", sign * 100.0)?;
+        for p in 0..weight::N_PROGRESS_DIV {
+            let (mse, mae, sign) = self.get(Some(p));
+            write!(f, " [{p}]{mse:.3},{mae:.3},{:.1}%", sign * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// training state in a checkpoint.
+#[derive(Debug, PartialEq)]
+struct State {
+    /// # of finished epochs.
+    epoch : usize,
+    /// the best loss so far.
+    best : f32,
+    /// # of epochs w/o improvement.
+    wait : usize,
+}
+
+impl State {
+    fn new() -> State {
+        State {epoch : 0, best : f32::INFINITY, wait : 0}
+    }
+
+    fn from(txt : &str) -> Option<State> {
+        let mut st = State::new();
+        for line in txt.lines() {
+            let (key, val) = line.split_once(',')?;
+            match key {
+                "epoch" => {st.epoch = val.parse().ok()?;},
+                "best" => {st.best = val.parse().ok()?;},
+                "wait" => {st.wait = val.parse().ok()?;},
+                _ => {},
+            }
+        }
+        Some(st)
+    }
+
+    fn to_str(&self) -> String {
+        format!("epoch,{}\nbest,{}\nwait,{}\n", self.epoch, self.best, self.wait)
+    }
+}
 
 pub struct Trainer {
     eta: f32,  // 学習率
     repeat: usize,  // 学習回数
-    path: String,  // 評価関数ファイルパス
+    path: String,  // 棋譜フォルダ
     progress: Vec<u32>,  // 途中経過出力回数
+    minibsize: usize,  // ミニバッチのサイズ
+    nthreads: usize,  // 勾配計算のスレッド数
+    target: dataset::Target,  // 教師信号
+    valsplit: f32,  // 検証用の棋譜の割合
+    schedule: Schedule,  // 学習率の変え方
+    optimizer: optimizer::Optimizer,  // 重みの更新方法
+    patience: usize,  // 改善しないときに打ち切るまでのエポック数
+    checkpoint: String,  // チェックポイントのフォルダ
+    resume: bool,  // チェックポイントから再開する
+    log: String,  // エポック毎の記録
     pub nfiles: usize,  // 棋譜ファイル数
     pub total: i32,  // 棋譜ファイル数
     pub win: i32,  // 先手勝ち数
@@ -38,6 +316,16 @@ impl Trainer {
             repeat,
             path: String::from(path),
             progress: Vec::new(),
+            minibsize: 1,
+            nthreads: thread::available_parallelism().map_or(1, |n| n.get()),
+            target: dataset::Target::Result,
+            valsplit: 0.0,
+            schedule: Schedule::Constant,
+            optimizer: optimizer::Optimizer::sgd(),
+            patience: 0,
+            checkpoint: String::new(),
+            resume: false,
+            log: String::new(),
             nfiles: 0,
             total: 0,
             win: 0,
@@ -56,8 +344,8 @@ impl Trainer {
     ///   - summary  : show play summary.
     ///   - time     : show processing time.
     ///   - default: progress,summary,time
-    pub fn read_opt_out(&mut self, txt : &str) {
-        if txt.is_empty() {return;}
+    pub fn read_opt_out(&mut self, txt : &str) -> Result<(), String> {
+        if txt.is_empty() {return Ok(());}
 
         let slist : Vec<_> = txt.split(',').collect();
         let mut res = BIT_OUT_NONE;
@@ -69,11 +357,12 @@ impl Trainer {
                 "progress" => { res |= BIT_OUT_PROGESS },
                 "summary" => { res |= BIT_OUT_SUMMARY },
                 "time" => { res |= BIT_OUT_TIME },
-                _ => { panic!("unknown option: \"{opt}\"")}
+                _ => { return Err(format!("unknown option: \"{opt}\""))}
             }
         }
         // println!("res:{res:x}");
         self.output = res;
+        Ok(())
     }
 
     pub fn need_exrfens(&self) -> bool {
@@ -110,1263 +399,523 @@ impl Trainer {
         self.progress = prgs.to_vec();
     }
 
-    #[allow(dead_code)]
-    pub fn learn_win(&self, files : &mut Vec<String>) {
-        let mut rng = rand::thread_rng();
-        for i in 0..self.repeat {
-            // println!("{} / {}", i, self.repeat);
-            // rng.shuffle(files);
-            files.shuffle(&mut rng);
-            for fname in files.iter() {
-                let path = format!("kifu/{}", fname);
-                print!("{} / {} : {}\r", i, self.repeat, path);
-                let content =
-                    std::fs::read_to_string(path).unwrap();
-                let lines:Vec<&str> = content.split("\n").collect();
-                let kifu = kifu::Kifu::from(&lines);
-                unsafe {
-                    self.run4win(&kifu, node::WEIGHT.as_mut().unwrap()).unwrap();
-                }
-            }
-            println!();
-        }
-        println!("Done.");
+    /// set size of a minibatch. 1 means training one by one.
+    pub fn set_minibatch(&mut self, size : usize) {
+        self.minibsize = size.max(1);
     }
 
+    /// set # of threads to calculate gradients.
     #[allow(dead_code)]
-    pub fn run4win(&self, kifu: &kifu::Kifu, weight: &mut weight::Weight) -> Result<(), String> {
-        let winner = kifu.winner();
-        if winner.is_none() {
-            return Err(String::from("invalid kifu."));
-        }
-
-        let winner = winner.unwrap();
-        for l in kifu.list.iter() {
-            if weight.train(&l.rfen, winner, self.eta, 10).is_err() {
-                return Err(String::from("error while training"));
-            }
-        }
-        Ok(())
+    pub fn set_threads(&mut self, n : usize) {
+        self.nthreads = n.max(1);
     }
 
-    #[allow(dead_code)]
-    pub fn learn_stones(&self, files : &mut Vec<String>) {
-        let mut rng = rand::thread_rng();
-        for i in 0..self.repeat {
-            // println!("{} / {}", i, self.repeat);
-            // rng.shuffle(files);
-            files.shuffle(&mut rng);
-            for fname in files.iter() {
-                let path = format!("kifu/{}", fname);
-                print!("{} / {} : {}\r", i, self.repeat, path);
-                let content =
-                    std::fs::read_to_string(path).unwrap();
-                let lines:Vec<&str> = content.split("\n").collect();
-                let kifu = kifu::Kifu::from(&lines);
-                unsafe {
-                    self.run4stones(&kifu, node::WEIGHT.as_mut().unwrap()).unwrap();
-                }
-            }
-            println!();
-        }
-        println!("Done.");
+    /// set the training target. see dataset::Target.
+    pub fn set_target(&mut self, target : dataset::Target) {
+        self.target = target;
     }
 
-    /**
-     * 読み込んだ棋譜をキャッシュする版
-     */
-    pub fn learn_stones_cache(&self, files : &mut [String]) {
-        let showprgs = self.need_progress();
-        let mut rng = rand::thread_rng();
-        let mut kifucache : Vec<(String, kifu::Kifu)> = Vec::new();
-        for i in 0..1 {
-            for fname in files.iter() {
-                let path = format!("kifu/{}", fname);
-                if showprgs {print!("{} / {} : {}\r", i, self.repeat, path);}
-                let content = std::fs::read_to_string(&path).unwrap();
-                let lines:Vec<&str> = content.split("\n").collect();
-                let kifu = kifu::Kifu::from(&lines);
-                let p = String::from(&path);
-                kifucache.push((p, kifu.copy()));
-                if let Err(msg) =
-                    unsafe {
-                        if cfg!(feature="bitboard") {
-                            self.run4stones(
-                                &kifu, nodebb::WEIGHT.as_mut().unwrap())
-                        } else {
-                            self.run4stones(
-                                &kifu, node::WEIGHT.as_mut().unwrap())
-                        }
-                    } {
-                        panic!("{}", msg);
-                    };
-            }
-            if showprgs {println!();}
-        }
-        let n = files.len();
-        let mut numbers = (0..n).collect::<Vec<usize>>();
-        for i in 1..self.repeat {
-            numbers.shuffle(&mut rng);
-            if showprgs {print!("{} / {}\r", i, self.repeat);}
-            for idx in numbers.iter() {
-                let (_path, kifu) = kifucache.get(*idx).unwrap();
-                if let Err(msg) =
-                    unsafe {
-                        if cfg!(feature="bitboard") {
-                            self.run4stones(
-                                kifu, nodebb::WEIGHT.as_mut().unwrap())
-                        } else {
-                            self.run4stones(
-                                kifu, node::WEIGHT.as_mut().unwrap())
-                        }
-                    } {
-                        panic!("{}", msg);
-                    };
-            }
-            if showprgs {println!();}
-        }
-        println!("Done.");
+    /// set the ratio of kifus for validation. 0 means no validation.
+    pub fn set_validation(&mut self, ratio : f32) {
+        self.valsplit = ratio.clamp(0.0, 1.0);
     }
 
-    pub fn run4stones(&self, kifu: &kifu::Kifu, weight: &mut weight::Weight) -> Result<(), String> {
-        let score = kifu.score;
-        if score.is_none() {
-            return Err(String::from("invalid score."));
-        }
-        let score = score.unwrap();
-        for l in kifu.list.iter() {
-            if weight.train(&l.rfen, score, self.eta, 10).is_err() {
-                return Err(String::from("error while training"));
-            }
-        }
-        Ok(())
+    pub fn set_schedule(&mut self, schedule : Schedule) {
+        self.schedule = schedule;
     }
 
-    /**
-     * 棋譜の読み込みと学習を別スレでやる版
-     */
-    #[allow(dead_code)]
-    pub fn learn_stones_para(&mut self) {
-        let (tosub, frmain) = std::sync::mpsc::channel::<Arc<kifu::Kifu>>();
-        let (tomain, frsub) = std::sync::mpsc::channel::<()>();
-        // let rpt = self.repeat;
-        let eta = self.eta;
-
-        let sub = std::thread::spawn(move || {
-            let weight = unsafe {nodebb::WEIGHT.as_mut().unwrap()};
-
-            tomain.send(()).unwrap();
-            let mut i = 0;
-            let mut etai = eta;
-            loop {
-                match frmain.recv() {
-                    Ok(kifu) => {
-                        // print!("{rfen:?},{score} \r");
-                        if kifu.is_none() {
-                            i += 1;
-                            etai = eta * 10.0 / (10.0 + i as f32);
-                            tomain.send(()).unwrap();
-                            continue;
-                        }
-                        if kifu.is_invalid() {
-                            // println!("score > 64");
-                            break;
-                        }
-                        for te in kifu.list.iter() {
-                            let rfen = &te.rfen;
-                            let score = kifu.score.unwrap();
-                            if weight.train(rfen, score, etai, 0).is_err() {
-                                println!("error while training");
-                                break;
-                            }
-                        }
-                    },
-                    Err(e) => {panic!("{}", e.to_string())}
-                }
-            }
-        });
-
-        // list up kifu
-        let files = std::fs::read_dir(&self.path).unwrap();
-        let mut files = files.filter_map(|entry| {
-            entry.ok().and_then(|e|
-                e.path().file_name().and_then(|n|
-                    n.to_str().map(String::from)
-                )
-            )}).collect::<Vec<String>>().iter().filter(|&fnm| {
-                fnm.contains("kifu")
-                // fnm.contains(".txt")
-            }).cloned().collect::<Vec<String>>();
-        // println!("{:?}", files);
-
-        self.nfiles = files.len();
-        files.sort();
-
-        let showprgs = self.need_progress();
-        let mut rng = rand::thread_rng();
-        let mut kifucache : Vec<Arc<kifu::Kifu>> = Vec::new();
-        for fname in files.iter() {
-            let path = format!("{}{}", self.path, fname);
-            if showprgs {print!("0 / {} : {}\r", self.repeat, path);}
-
-            let content = std::fs::read_to_string(&path).unwrap();
-            let lines:Vec<&str> = content.split("\n").collect();
-
-            let kifu = Arc::new(kifu::Kifu::from(&lines));
-            match tosub.send(kifu.clone()) {
-                Ok(_) => {
-                    kifucache.push(kifu.clone());
-                },
-                Err(e) => {
-                    panic!("{}", e.to_string());
-                },
-            }
+    /// set how to update weights. plain SGD by default.
+    pub fn set_optimizer(&mut self, optimizer : optimizer::Optimizer) {
+        self.optimizer = optimizer;
+    }
 
-            self.total += 1;
-            match kifu.winner().unwrap() {
-                kifu::SENTEWIN => {self.win += 1;},
-                kifu::DRAW => {self.draw += 1;},
-                kifu::GOTEWIN => {self.lose += 1;},
-                _ => {}
-            }
-        }
-        let sep = Arc::new(kifu::Kifu::new());
-        match tosub.send(sep.clone()) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
-        }
-        frsub.recv().unwrap();
-        if showprgs {println!();}
+    /// stop training when the loss does not improve in `n` epochs.
+    /// the best weights are restored then. 0 means no early stopping.
+    pub fn set_patience(&mut self, n : usize) {
+        self.patience = n;
+    }
 
-        let n = kifucache.len();
-        // println!("{n} rfens.");
-        let mut numbers = (0..n).collect::<Vec<usize>>();
-        for i in 1..self.repeat {
-            if showprgs {
-                print!("{i} / {}\r", self.repeat);
-                std::io::stdout().flush().unwrap();
-            }
-            numbers.shuffle(&mut rng);
-            for &idx in numbers.iter() {
-                match tosub.send(kifucache[idx].clone()) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
-            }
-            match tosub.send(sep.clone()) {
-                Ok(_) => {}
-                Err(e) => {
-                    panic!("{}", e.to_string());
-                },
-            }
-            frsub.recv().unwrap();
-        }
-        if showprgs {println!();}
-        // println!("_ _ _");
-        let stop = Arc::new(kifu::Kifu::invalid());
-        match tosub.send(stop) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
-        }
+    /// store weights and state in `dir` after every epoch.
+    ///
+    /// # Arguments
+    /// - `dir` : directory for a checkpoint.
+    /// - `resume` : start from the checkpoint if it exists.
+    pub fn set_checkpoint(&mut self, dir : &str, resume : bool) {
+        self.checkpoint = String::from(dir);
+        self.resume = resume;
+    }
 
-        sub.join().unwrap();
+    /// put losses and metrics of every epoch into a csv file.
+    pub fn set_log(&mut self, path : &str) {
+        self.log = String::from(path);
     }
 
-    /**
-     * 棋譜の読み込みと学習を別スレでやる版
-     */
-    #[allow(dead_code)]
-    pub fn learn_stones_para_rfen(&mut self) {
-        let (tosub, frmain) = std::sync::mpsc::channel::<(String, i8)>();
-        let (tomain, frsub) = std::sync::mpsc::channel::<()>();
-        let eta = self.eta;
-
-        let sub = std::thread::spawn(move || {
-            let weight = unsafe {nodebb::WEIGHT.as_mut().unwrap()};
-
-            tomain.send(()).unwrap();
-
-            loop {
-                match frmain.recv() {
-                    Ok( (rfen, score) ) => {
-                        // print!("{rfen:?},{score} \r");
-                        if rfen.is_empty() {
-                            tomain.send(()).unwrap();
-                            continue;
-                        }
-                        if score > 64 {
-                            // println!("score > 64");
-                            break;
-                        }
-                        //
-                        if weight.train_rotate(&rfen, score, eta, 10).is_err() {
-                            println!("error while training");
-                            break;
-                        }
-                    },
-                    Err(e) => {panic!("{}", e.to_string())}
-                }
-            }
-        });
-
-        // list up kifu
-        let files = std::fs::read_dir(&self.path).unwrap();
-        let mut files = files.filter_map(|entry| {
-            entry.ok().and_then(|e|
-                e.path().file_name().and_then(|n|
-                    n.to_str().map(String::from)
-                )
-            )}).collect::<Vec<String>>().iter().filter(|&fnm| {
-                fnm.contains("kifu")
-                // fnm.contains(".txt")
-            }).cloned().collect::<Vec<String>>();
-        // println!("{:?}", files);
+    /// read kifus in `path` as training data.
+    /// every position is kept even if another kifu has the same one,
+    /// so that a shared opening is trained on every result.
+    ///
+    /// # Returns
+    /// list of (board, target from black's view) or error message.
+    pub fn readkifus(&mut self) -> Result<Positions, String> {
+        let (mut train, val) = self.readkifus_split(0.0)?;
+        train.extend(val);
+        Ok(train)
+    }
 
+    /// read kifus in `path` and split them into training and validation.
+    /// kifus are split evenly by the order of file names,
+    /// so that positions in a game are in the same set.
+    ///
+    /// # Arguments
+    /// - `ratio` : ratio of kifus for validation.
+    ///
+    /// # Returns
+    /// (training data, validation data) or error message.
+    pub fn readkifus_split(&mut self, ratio : f32)
+            -> Result<(Positions, Positions), String> {
+        let files = dataset::listkifu(&self.path)?;
         self.nfiles = files.len();
-        files.sort();
 
         let showprgs = self.need_progress();
-        let mut rng = rand::thread_rng();
-        let mut rfencache : Vec<(String, i8)> = Vec::new();
-        for fname in files.iter() {
+        let mut ds = dataset::Dataset::new(false);
+        ds.set_target(self.target);
+        let mut isval = Vec::new();
+        for (i, fname) in files.iter().enumerate() {
             let path = format!("{}{}", self.path, fname);
-            if showprgs {print!("0 / {} : {}\r", self.repeat, path);}
-
-            let content = std::fs::read_to_string(&path).unwrap();
-            let lines:Vec<&str> = content.split("\n").collect();
-
-            let kifu = kifu::Kifu::from(&lines);
-            let score = kifu.score.unwrap();
-            for te in kifu.list.iter() {
-                let rfen = te.rfen.clone();
-                let rfen2 = rfen.clone();
-                match tosub.send((rfen, score)) {
-                    Ok(_) => {
-                        rfencache.push((rfen2, score));
-                    }
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
+            if showprgs {print!("reading {path}\r");}
+
+            let kifu = dataset::readkifu(&path)?;
+            if self.need_exrfens() && kifu.list.len() > 1 {
+                println!("{}", kifu.list[1].rfen);
             }
+            let n = ds.positions.len();
+            ds.add_kifu(&kifu).map_err(|e| format!("{path}: {e}"))?;
+            let val = ((i + 1) as f32 * ratio).floor() > (i as f32 * ratio).floor();
+            isval.resize(isval.len() + ds.positions.len() - n, val);
 
             self.total += 1;
-            match kifu.winner().unwrap() {
-                kifu::SENTEWIN => {self.win += 1;},
-                kifu::DRAW => {self.draw += 1;},
-                kifu::GOTEWIN => {self.lose += 1;},
+            match kifu.winner() {
+                Some(kifu::SENTEWIN) => {self.win += 1;},
+                Some(kifu::DRAW) => {self.draw += 1;},
+                Some(kifu::GOTEWIN) => {self.lose += 1;},
                 _ => {}
             }
         }
-        match tosub.send((String::new(), 0)) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
-        }
-        frsub.recv().unwrap();
         if showprgs {println!();}
+        let (val, train) : (Vec<_>, Vec<_>) =
+            ds.positions.into_iter().zip(isval).partition(|(_p, v)| *v);
+        Ok((train.into_iter().map(|(p, _v)| (p.ban, p.target)).collect(),
+            val.into_iter().map(|(p, _v)| (p.ban, p.target)).collect()))
+    }
 
-        let n = rfencache.len();
-        // println!("{n} rfens.");
-        let mut numbers = (0..n).collect::<Vec<usize>>();
-        for i in 1..self.repeat {
-            if showprgs {print!("{i} / {}", self.repeat);}
-            numbers.shuffle(&mut rng);
-            for idx in numbers.iter() {
-                let (rfen, score) = rfencache.get(*idx).unwrap();
-                match tosub.send((rfen.clone(), *score)) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
-            }
-            match tosub.send((String::new(), 0)) {
-                Ok(_) => {}
-                Err(e) => {
-                    panic!("{}", e.to_string());
-                },
-            }
-            frsub.recv().unwrap();
-            if showprgs {println!();}
-        }
-        // println!("_ _ _");
-        match tosub.send((String::from("stop"), 100)) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
+    /// train `weight` w/ kifus in `path`.
+    ///
+    /// # Arguments
+    /// - `weight` : weights to be trained.
+    ///
+    /// # Returns
+    /// mean squared error in the last iteration or error message.
+    pub fn learn(&mut self, weight : &mut weight::Weight) -> Result<f32, String> {
+        let (data, val) = self.readkifus_split(self.valsplit)?;
+        if data.is_empty() {
+            return Err(format!("no positions in {}.", self.path));
         }
+        println!("{} positions, {} for validation.", data.len(), val.len());
 
-        sub.join().unwrap();
+        self.learn_data(weight, &data, &val)
     }
 
-    /**
-     * 棋譜の読み込みと学習を別スレでやる版
-     * rfenを纏めてやり取りする版
-     */
-    #[allow(dead_code)]
-    pub fn learn_stones_para_rfengrp(&mut self) {
-        let (tosub, frmain) = std::sync::mpsc::channel::<Vec<u32>>();
-        let (tomain, frsub) = std::sync::mpsc::channel::<()>();
-        let (txprogress, rxprogress) = std::sync::mpsc::channel();
-        let eta = self.eta;
-
-        let sub = std::thread::spawn(move || {
-            let weight = unsafe {nodebb::WEIGHT.as_mut().unwrap()};
-
-            tomain.send(()).unwrap();
-
-            loop {
-                match frmain.recv() {
-                    Ok( rfenidxgrp ) => {
-                        // print!("{rfen:?},{score} \r");
-                        if rfenidxgrp.is_empty() {
-                            tomain.send(()).unwrap();
-                            continue;
-                        }
-                        if rfenidxgrp[0] == STOP  {
-                            // println!("score > 64");
-                            break;
-                        }
-                        if rfenidxgrp[0] == PROGRESS {
-                            let mut w = weight::Weight::new();
-                            w.copy(weight);
-                            txprogress.send(w).unwrap();
-                            continue;
-                        }
-                        //
-                        for i in rfenidxgrp {
-                            let (rfen, score) = unsafe {&RFENCACHE[i as usize]};
-                            if weight.train(rfen, *score, eta, 0).is_err() {
-                                println!("error while training");
-                                break;
-                            }
-                        }
-                    },
-                    Err(e) => {panic!("{}", e.to_string())}
-                }
-            }
-        });
-        let prgstbl = self.progress.clone();
-        let repeat = self.repeat as u32;
-        let subprgs = std::thread::spawn(move || {
-            for prgs in prgstbl {
-                if prgs >= repeat {
-                    println!("WARNING: progress {prgs} >= {repeat}...");
-                    break;
-                }
-                let weight = rxprogress.recv().unwrap();
-                if cfg!(feature="nnv4") {
-                    weight.writev4(&format!("kifu/newevaltable.r{prgs}.txt"));
-                } else {
-                    weight.writev5(&format!("kifu/newevaltable.r{prgs}.txt"));
-                }
-            }
-        });
-
-        // list up kifu
-        let files = std::fs::read_dir(&self.path).unwrap();
-        let mut files = files.filter_map(|entry| {
-            entry.ok().and_then(|e|
-                e.path().file_name().and_then(|n|
-                    n.to_str().map(String::from)
-                )
-            )}).collect::<Vec<String>>().iter().filter(|&fnm| {
-                fnm.contains("kifu")
-                // fnm.contains(".txt")
-            }).cloned().collect::<Vec<String>>();
-        // println!("{:?}", files);
-
-        self.nfiles = files.len();
-        files.sort();
-
+    /// train `weight` w/ positions.
+    ///
+    /// # Arguments
+    /// - `weight` : weights to be trained.
+    /// - `data` : list of (board, target from black's view).
+    /// - `val` : positions for validation. early stopping uses this if any.
+    ///
+    /// # Returns
+    /// mean squared error in the last iteration or error message.
+    pub fn learn_data(&self, weight : &mut weight::Weight,
+            data : &[(bitboard::BitBoard, f32)],
+            val : &[(bitboard::BitBoard, f32)]) -> Result<f32, String> {
         let showprgs = self.need_progress();
-        let mut rng = rand::thread_rng();
-        // let mut rfencache : Vec<(String, i8)> = Vec::new();
-        for fname in files.iter() {
-            let path = format!("{}{}", self.path, fname);
-            if showprgs {print!("reading {path}\r");}
-
-            let content = std::fs::read_to_string(&path).unwrap();
-            let lines:Vec<&str> = content.split("\n").collect();
-
-            let kifu = kifu::Kifu::from(&lines);
-            let score = kifu.score.unwrap();
-            for te in kifu.list.iter() {
-                let rfen = te.rfen.clone();
-                // 最終手はカウントで良いので学習しなくて良い
-                if bitboard::count_emptycells(&rfen).unwrap() < 1 {
-                    continue;
-                }
-                unsafe {RFENCACHE.push((rfen.clone(), score));}
-                let b = bitboard::BitBoard::from(&rfen).unwrap();
-                let b90 = b.rotate90();
-                unsafe {RFENCACHE.push((b90.to_str(), score));}
-                let b180 = b.rotate180();
-                unsafe {RFENCACHE.push((b180.to_str(), score));}
-                let b270 = b90.rotate180();
-                unsafe {RFENCACHE.push((b270.to_str(), score));}
-                /*if score.abs() > 32 {
-                    // 大差がついている棋譜は多めに覚える
-                    unsafe {RFENCACHE.push((rfen, score));}
-                }*/
-            }
-
-            self.total += 1;
-            match kifu.winner().unwrap() {
-                kifu::SENTEWIN => {self.win += 1;},
-                kifu::DRAW => {self.draw += 1;},
-                kifu::GOTEWIN => {self.lose += 1;},
-                _ => {}
-            }
-        }
-        match tosub.send(Vec::new()) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
-        }
-        frsub.recv().unwrap();
-        if showprgs {println!();}
-
-        let n = unsafe {RFENCACHE.len()};
-        // println!("{n} rfens.");
-        let mut numbers = (0..n as u32).collect::<Vec<u32>>();
-        let invalidprogress = 99999999;
+        let mut grads = vec![vec![0f32 ; weight::N_WEIGHT_ALL] ; self.nthreads];
+        let mut numbers = (0..data.len()).collect::<Vec<usize>>();
+        let mut rng = rng::rng();
         let mut prgs = VecDeque::from(self.progress.clone());
-        let mut nprgs = prgs.pop_front().unwrap_or(invalidprogress) as usize;
-        for i in 0..self.repeat {
-            if showprgs {
-                print!("{i} / {}\r", self.repeat);
-                std::io::stdout().flush().unwrap();
-            }
+        let mut best = Box::new(weight::Weight::new());
+        best.copy(weight);
+        let mut opt = self.optimizer.clone();
+        let mut state = self.load_checkpoint(weight, &mut best, &mut opt)?;
+        while prgs.front().is_some_and(|&p| (p as usize) < state.epoch) {
+            prgs.pop_front();
+        }
+        let mut log = self.open_log(state.epoch)?;
+        let mut mse = 0.0;
+        for i in state.epoch..self.repeat {
+            let eta = self.schedule.eta(self.eta, i, self.repeat);
             numbers.shuffle(&mut rng);
-            for idx in 0..10 {
-                let grp = numbers[idx * n / 10 .. (idx + 1) * n / 10].to_vec();
-                match tosub.send(grp) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
-            }
-            match tosub.send(Vec::new()) {
-                Ok(_) => {}
-                Err(e) => {
-                    panic!("{}", e.to_string());
-                },
-            }
-            if nprgs == i {
-                match tosub.send(vec![PROGRESS]) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
-                nprgs = prgs.pop_front().unwrap_or(invalidprogress) as usize;
+            let mut loss = 0.0;
+            for mb in numbers.chunks(self.minibsize) {
+                loss += accumulate(weight, data, mb, &mut grads);
+                let n = mb.len() as f32;
+                grads[0].iter_mut().for_each(|g| *g /= n);
+                opt.update(weight, &grads[0], eta);
+            }
+            mse = loss / data.len() as f32;
+            let metrics = if val.is_empty() {
+                None
+            } else {
+                Some(Metrics::measure(weight, val, self.nthreads))
+            };
+            if let Some(f) = log.as_mut() {
+                let vcsv = metrics.as_ref().map_or(String::new(), |m| m.to_csv());
+                writeln!(f, "{i},{eta},{mse},{vcsv}").map_err(|e| e.to_string())?;
             }
-            frsub.recv().unwrap();
-        }
-        if showprgs {println!();}
-        // println!("_ _ _");
-        match tosub.send(vec![STOP]) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
-        }
-        subprgs.join().unwrap();
-        sub.join().unwrap();
-    }
-
-    /**
-     * 棋譜の読み込みと学習を別スレでやる版
-     * boardを纏めてやり取りする版
-     */
-    #[allow(dead_code)]
-    pub fn learn_stones_para_boardgrp(&mut self) {
-        let (tosub, frmain) = std::sync::mpsc::channel::<Vec<u32>>();
-        let (tomain, frsub) = std::sync::mpsc::channel::<()>();
-        let (txprogress, rxprogress) = std::sync::mpsc::channel();
-        let eta = self.eta;
-
-        let sub = std::thread::spawn(move || {
-            let weight = unsafe {nodebb::WEIGHT.as_mut().unwrap()};
-
-            tomain.send(()).unwrap();
-
-            loop {
-                match frmain.recv() {
-                    Ok( rfenidxgrp ) => {
-                        // print!("{rfen:?},{score} \r");
-                        if rfenidxgrp.is_empty() {
-                            tomain.send(()).unwrap();
-                            continue;
-                        }
-                        if rfenidxgrp[0] == STOP  {
-                            // println!("score > 64");
-                            break;
-                        }
-                        if rfenidxgrp[0] == PROGRESS {
-                            let mut w = weight::Weight::new();
-                            w.copy(weight);
-                            txprogress.send(w).unwrap();
-                            continue;
-                        }
-                        //
-                        for i in rfenidxgrp {
-                            let (ban, score) = unsafe {&BOARDCACHE[i as usize]};
-                            if weight.train_bitboard(ban, *score, eta, 0).is_err() {
-                                println!("error while training");
-                                break;
-                            }
-                        }
-                    },
-                    Err(e) => {panic!("{}", e.to_string())}
+            if showprgs {
+                match metrics.as_ref() {
+                    Some(m) => print!("{i} / {} mse:{mse:.3} val {m}\r", self.repeat),
+                    None => print!("{i} / {} mse:{mse:.3}\r", self.repeat),
                 }
+                std::io::stdout().flush().unwrap();
             }
-        });
-        let prgstbl = self.progress.clone();
-        let repeat = self.repeat as u32;
-        let subprgs = std::thread::spawn(move || {
-            for prgs in prgstbl {
-                if prgs >= repeat {
-                    println!("WARNING: progress {prgs} >= {repeat}...");
-                    break;
-                }
-                let weight = rxprogress.recv().unwrap();
-                if cfg!(feature="nnv4") {
-                    weight.writev4(&format!("kifu/newevaltable.r{prgs}.txt"));
-                } else {
-                    weight.writev5(&format!("kifu/newevaltable.r{prgs}.txt"));
-                }
+            if prgs.front().is_some_and(|&p| p as usize == i) {
+                let p = prgs.pop_front().unwrap();
+                weight.writev11(&format!("{}newevaltable.r{p}.txt", self.path))?;
             }
-        });
-
-        // list up kifu
-        let files = std::fs::read_dir(&self.path).unwrap();
-        let mut files = files.filter_map(|entry| {
-            entry.ok().and_then(|e|
-                e.path().file_name().and_then(|n|
-                    n.to_str().map(String::from)
-                )
-            )}).collect::<Vec<String>>().iter().filter(|&fnm| {
-                fnm.contains("kifu")
-                // fnm.find(".txt").is_some()
-            }).cloned().collect::<Vec<String>>();
-        // println!("{:?}", files);
-
-        self.nfiles = files.len();
-        files.sort();
 
-        let showprgs = self.need_progress();
-        let mut rng = rand::thread_rng();
-        // let mut rfencache : Vec<(String, i8)> = Vec::new();
-        for fname in files.iter() {
-            let path = format!("{}{}", self.path, fname);
-            if showprgs {print!("reading {path}\r");}
-
-            let content = std::fs::read_to_string(&path).unwrap();
-            let lines:Vec<&str> = content.split("\n").collect();
-
-            let kifu = kifu::Kifu::from(&lines);
-            let score = kifu.score.unwrap();
-            for te in kifu.list.iter() {
-                let rfen = te.rfen.clone();
-                // 最終手はカウントで良いので学習しなくて良い
-                if bitboard::count_emptycells(&rfen).unwrap() < 1 {
-                    continue;
-                }
-                let b = bitboard::BitBoard::from(&rfen).unwrap();
-                let b90 = b.rotate90();
-                let b180 = b.rotate180();
-                let b270 = b90.rotate180();
-                unsafe {
-                    BOARDCACHE.push((b, score));
-                    BOARDCACHE.push((b90, score));
-                    BOARDCACHE.push((b180, score));
-                    BOARDCACHE.push((b270, score));
-                }
-                /*if score.abs() > 32 {
-                    // 大差がついている棋譜は多めに覚える
-                    unsafe {RFENCACHE.push((rfen, score));}
-                }*/
-            }
-            unsafe {
-                BOARDCACHE.sort_by(|a, b| {
-                    a.0.black.cmp(&b.0.black).then(a.0.white.cmp(&b.0.white))});
-                BOARDCACHE.dedup_by(|a, b| {
-                    a.0.black == b.0.black && a.0.white == b.0.white
-                        && a.0.teban == b.0.teban});
+            let score = metrics.as_ref().map_or(mse, |m| m.mse());
+            if score < state.best {
+                state.best = score;
+                state.wait = 0;
+                best.copy(weight);
+            } else {
+                state.wait += 1;
             }
-
-            self.total += 1;
-            match kifu.winner().unwrap() {
-                kifu::SENTEWIN => {self.win += 1;},
-                kifu::DRAW => {self.draw += 1;},
-                kifu::GOTEWIN => {self.lose += 1;},
-                _ => {}
+            state.epoch = i + 1;
+            self.save_checkpoint(weight, &best, &opt, &state)?;
+            if self.patience > 0 && state.wait >= self.patience {
+                if showprgs {println!();}
+                println!("early stopping at epoch {i}. best:{:.3}", state.best);
+                break;
             }
         }
-        match tosub.send(Vec::new()) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
-        }
-        frsub.recv().unwrap();
         if showprgs {println!();}
-
-        let n = unsafe {BOARDCACHE.len()};
-        println!("{n} rfens.");
-        let mut numbers : Vec<u32> = (0..n as u32).collect::<Vec<u32>>();
-        let invalidprogress = 99999999;
-        let mut prgs = VecDeque::from(self.progress.clone());
-        let mut nprgs = prgs.pop_front().unwrap_or(invalidprogress) as usize;
-        for i in 0..self.repeat {
-            if showprgs {
-                print!("{i} / {}\r", self.repeat);
-                std::io::stdout().flush().unwrap();
-            }
-            numbers.shuffle(&mut rng);
-            for idx in 0..10 {
-                let grp = numbers[idx * n / 10 .. (idx + 1) * n / 10].to_vec();
-                match tosub.send(grp) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
-            }
-            match tosub.send(Vec::new()) {
-                Ok(_) => {}
-                Err(e) => {
-                    panic!("{}", e.to_string());
-                },
-            }
-            if nprgs == i {
-                match tosub.send(vec![PROGRESS]) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
-                nprgs = prgs.pop_front().unwrap_or(invalidprogress) as usize;
-            }
-            frsub.recv().unwrap();
+        if let Some(p) = prgs.front() {
+            println!("WARNING: progress {p} >= {}...", self.repeat);
         }
-        if showprgs {println!();}
-        // println!("_ _ _");
-        match tosub.send(vec![STOP]) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
+        if self.patience > 0 {
+            weight.copy(&best);
         }
-        subprgs.join().unwrap();
-        sub.join().unwrap();
+        Ok(mse)
     }
 
-    /**
-     * 棋譜の読み込みと学習を別スレでやる版
-     * boardを纏めてやり取りする版
-     * minibatch版
-     */
-    #[allow(dead_code)]
-    #[cfg(target_arch="x86_64")]
-    pub fn learn_stones_para_boardgrp_minibatch(&mut self, mbsize : usize) {
-        let (tosub, frmain) = std::sync::mpsc::channel::<Vec<u32>>();
-        let (tomain, frsub) = std::sync::mpsc::channel::<()>();
-        let (txprogress, rxprogress) = std::sync::mpsc::channel();
-        let eta = self.eta;
-
-        let sub = std::thread::spawn(move || {
-            let weight = unsafe {nodebb::WEIGHT.as_mut().unwrap()};
-            let mut bufweight = weight::Weight::new();
-            let mut banscores = Vec::with_capacity(mbsize);
-
-            tomain.send(()).unwrap();
-
-            loop {
-                match frmain.recv() {
-                    Ok(rfenidxgrp) => {
-                        // print!("{rfen:?},{score} \r");
-                        if rfenidxgrp.is_empty() {
-                            tomain.send(()).unwrap();
-                            continue;
-                        }
-                        if rfenidxgrp[0] == STOP {
-                            // println!("score > 64");
-                            break;
-                        }
-                        if rfenidxgrp[0] == PROGRESS {
-                            let mut w = weight::Weight::new();
-                            w.copy(&weight);
-                            txprogress.send(w).unwrap();
-                            continue;
-                        }
-                        //
-                        for i in rfenidxgrp {
-                            banscores.push(unsafe {&BOARDCACHE[i as usize]});
-                        }
-                        bufweight.clear();
-                        if weight.train_bitboard_mb(
-                                &banscores, eta, &mut bufweight).is_err() {
-                            println!("error while training");
-                            break;
-                        }
-                        weight.updatemb(&bufweight, mbsize);
-                        banscores.clear();
-                    },
-                    Err(e) => {panic!("{}", e.to_string())}
-                }
-            }
-        });
-        let prgstbl = self.progress.clone();
-        let repeat = self.repeat as u32;
-        let subprgs = std::thread::spawn(move || {
-            for prgs in prgstbl {
-                if prgs >= repeat {
-                    println!("WARNING: progress {prgs} >= {repeat}...");
-                    break;
-                }
-                let weight = rxprogress.recv().unwrap();
-                if cfg!(feature="nnv4") {
-                    weight.writev4(&format!("kifu/newevaltable.r{prgs}.txt"));
-                } else {
-                    weight.writev5(&format!("kifu/newevaltable.r{prgs}.txt"));
-                }
-            }
-        });
-
-        // list up kifu
-        let files = std::fs::read_dir(&self.path).unwrap();
-        let mut files = files.filter_map(|entry| {
-            entry.ok().and_then(|e|
-                e.path().file_name().and_then(|n|
-                    n.to_str().map(String::from)
-                )
-            )}).collect::<Vec<String>>().iter().filter(|&fnm| {
-                fnm.contains("kifu")
-                // fnm.contains(".txt")
-            }).cloned().collect::<Vec<String>>();
-        // println!("{:?}", files);
-
-        self.nfiles = files.len();
-        files.sort();
+    /// read a checkpoint if resuming.
+    ///
+    /// # Arguments
+    /// - `weight` : weights to be restored.
+    /// - `best` : the best weights to be restored.
+    /// - `opt` : optimizer to be restored. kept as is w/o its state file.
+    ///
+    /// # Returns
+    /// training state or error message.
+    fn load_checkpoint(&self, weight : &mut weight::Weight,
+            best : &mut weight::Weight, opt : &mut optimizer::Optimizer)
+            -> Result<State, String> {
+        let dir = std::path::Path::new(&self.checkpoint);
+        let statepath = dir.join(CHECKPOINT_STATE);
+        if self.checkpoint.is_empty() || !self.resume || !statepath.exists() {
+            return Ok(State::new());
+        }
+
+        let txt = std::fs::read_to_string(&statepath)
+            .map_err(|e| format!("{}: {e}", statepath.display()))?;
+        let state = State::from(&txt)
+            .ok_or(format!("{}: invalid state.", statepath.display()))?;
+        weight.read(dir.join(CHECKPOINT_WEIGHT).to_str().unwrap())?;
+        best.read(dir.join(CHECKPOINT_BEST).to_str().unwrap())?;
+        let optpath = dir.join(optimizer::STATEFILE);
+        if optpath.exists() {
+            let txt = std::fs::read_to_string(&optpath)
+                .map_err(|e| format!("{}: {e}", optpath.display()))?;
+            opt.read_state(&txt).map_err(|e| format!("{}: {e}", optpath.display()))?;
+        }
+        println!("resume from epoch {} ({} updates) in {}.",
+            state.epoch, opt.step(), self.checkpoint);
+        Ok(state)
+    }
 
-        let showprgs = self.need_progress();
-        let mut rng = rand::thread_rng();
-        // let mut rfencache : Vec<(String, i8)> = Vec::new();
-        for fname in files.iter() {
-            let path = format!("{}{}", self.path, fname);
-            if showprgs {print!("reading {path}\r");}
+    /// store weights and state if checkpoint is specified.
+    fn save_checkpoint(&self, weight : &weight::Weight, best : &weight::Weight,
+            opt : &optimizer::Optimizer, state : &State) -> Result<(), String> {
+        if self.checkpoint.is_empty() {return Ok(());}
+
+        let dir = std::path::Path::new(&self.checkpoint);
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", self.checkpoint))?;
+        weight.writev11(dir.join(CHECKPOINT_WEIGHT).to_str().unwrap())?;
+        best.writev11(dir.join(CHECKPOINT_BEST).to_str().unwrap())?;
+        let path = dir.join(optimizer::STATEFILE);
+        std::fs::write(&path, opt.to_str())
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        // 状態は最後に書いて、途中で止まっても前のエポックから再開できるようにする
+        let path = dir.join(CHECKPOINT_STATE);
+        std::fs::write(&path, state.to_str())
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
 
-            let content = std::fs::read_to_string(&path).unwrap();
-            let lines:Vec<&str> = content.split("\n").collect();
+    /// open the log file. lines are appended when resuming.
+    fn open_log(&self, epoch : usize) -> Result<Option<std::fs::File>, String> {
+        if self.log.is_empty() {return Ok(None);}
 
-            let kifu = kifu::Kifu::from(&lines);
-            let score = kifu.score.unwrap();
-            for te in kifu.list.iter() {
-                let rfen = te.rfen.clone();
-                // 最終手はカウントで良いので学習しなくて良い
-                if bitboard::count_emptycells(&rfen).unwrap() < 1 {
-                    continue;
-                }
+        let append = epoch > 0 && std::path::Path::new(&self.log).exists();
+        let mut file = std::fs::OpenOptions::new().create(true).write(true)
+            .append(append).truncate(!append).open(&self.log)
+            .map_err(|e| format!("{}: {e}", self.log))?;
+        if !append {
+            writeln!(file, "epoch,eta,mse,{}", Metrics::csv_header("val"))
+                .map_err(|e| format!("{}: {e}", self.log))?;
+        }
+        Ok(Some(file))
+    }
+}
 
-                let b = bitboard::BitBoard::from(&rfen).unwrap();
-                let b90 = b.rotate90();
-                let b180 = b.rotate180();
-                let b270 = b90.rotate180();
-                unsafe {
-                    BOARDCACHE.push((b, score));
-                    BOARDCACHE.push((b90, score));
-                    BOARDCACHE.push((b180, score));
-                    BOARDCACHE.push((b270, score));
-                }
-                /*if score.abs() > 32 {
-                    // 大差がついている棋譜は多めに覚える
-                    unsafe {RFENCACHE.push((rfen, score));}
-                }*/
-            }
-            unsafe {
-                BOARDCACHE.sort_by(|a, b| {
-                    a.0.black.cmp(&b.0.black).then(a.0.white.cmp(&b.0.white))});
-                BOARDCACHE.dedup_by(|a, b| {
-                    a.0.black == b.0.black && a.0.white == b.0.white
-                        && a.0.teban == b.0.teban});
-            }
+/// calculate gradients of a minibatch w/ threads.
+/// gradients are summed up into `grads[0]`.
+///
+/// # Arguments
+/// - `weight` : weights to be trained.
+/// - `data` : list of (board, target from black's view).
+/// - `idxs` : indices of `data` in the minibatch.
+/// - `grads` : a buffer for every thread.
+///
+/// # Returns
+/// sum of squared errors.
+fn accumulate(weight : &weight::Weight, data : &[(bitboard::BitBoard, f32)],
+        idxs : &[usize], grads : &mut [Vec<f32>]) -> f32 {
+    let backward = |ids : &[usize], grad : &mut [f32]| {
+        ids.iter().map(|&i| {
+            let (ban, target) = &data[i];
+            let target = *target;
+            let diff = weight.backward(ban, target, grad) - target;
+            diff * diff
+        }).sum::<f32>()
+    };
+
+    let nthreads = grads.len().min(idxs.len());
+    for g in grads.iter_mut().take(nthreads) {
+        g.fill(0.0);
+    }
+    if nthreads <= 1 {
+        return backward(idxs, &mut grads[0]);
+    }
 
-            self.total += 1;
-            match kifu.winner().unwrap() {
-                kifu::SENTEWIN => {self.win += 1;},
-                kifu::DRAW => {self.draw += 1;},
-                kifu::GOTEWIN => {self.lose += 1;},
-                _ => {}
-            }
-        }
-        match tosub.send(Vec::new()) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
-        }
-        let _ = frsub.recv().unwrap();
-        if showprgs {println!();}
+    let chunk = idxs.len().div_ceil(nthreads);
+    let loss = thread::scope(|s| {
+        let handles = idxs.chunks(chunk).zip(grads.iter_mut()).map(|(ids, g)| {
+            s.spawn(move || backward(ids, g))
+        }).collect::<Vec<_>>();
+        handles.into_iter().map(|h| h.join().unwrap()).sum()
+    });
 
-        let n = unsafe {BOARDCACHE.len()};
-        println!("{n} rfens.");
-        let mut numbers = (0..n as u32).collect::<Vec<u32>>();
-        let invalidprogress = 99999999;
-        let mut prgs = VecDeque::from(self.progress.clone());
-        let mut nprgs = prgs.pop_front().unwrap_or(invalidprogress) as usize;
-        for i in 0..self.repeat {
-            if showprgs {
-                print!("{i} / {}\r", self.repeat);
-                std::io::stdout().flush().unwrap();
-            }
-            numbers.shuffle(&mut rng);
-            let mut j = mbsize;
-            while j < numbers.len() {
-                let grp1 = numbers[j - mbsize .. j].to_vec();
-                match tosub.send(grp1) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
-                j += mbsize;
-            }
-            match tosub.send(Vec::new()) {
-                Ok(_) => {}
-                Err(e) => {
-                    panic!("{}", e.to_string());
-                },
-            }
-            if nprgs == i {
-                match tosub.send(vec![PROGRESS]) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
-                nprgs = prgs.pop_front().unwrap_or(invalidprogress) as usize;
-            }
-            frsub.recv().unwrap();
-        }
-        if showprgs {println!();}
-        // println!("_ _ _");
-        match tosub.send(vec![STOP]) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
+    let (sum, rest) = grads.split_at_mut(1);
+    for g in rest.iter().take(nthreads - 1) {
+        for (a, b) in sum[0].iter_mut().zip(g.iter()) {
+            *a += b;
         }
-        subprgs.join().unwrap();
-        sub.join().unwrap();
     }
+    loss
+}
 
-    #[allow(dead_code)]
-    #[cfg(target_arch="aarch64")]
-    pub fn learn_stones_para_boardgrp_minibatch(&mut self, mbsize : usize) {
-        panic!("not implemented for aarch64.");
+#[test]
+fn test_learn() {
+    let mut w = weight::Weight::new();
+    w.init();
+    let rfens = [
+        ("8/8/8/3Aa3/3aA3/8/8/8 b", 0),
+        ("2A1A1A1/3c2/Ac1bA/3c2/2cAa1/1a1Aa2A/A3a3/4A3 w", 20),
+        ("aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w", -10),
+        ("8/8/3A4/3Ba2/3aA3/8/8/8 w", 4),
+        ("8/8/8/2Ca2/3aA3/8/8/8 w", -6),
+    ];
+    let data = rfens.iter().map(|(rfen, score)| {
+        (bitboard::BitBoard::from(rfen).unwrap(), *score as f32)
+    }).collect::<Vec<_>>();
+
+    // same gradients w/ or w/o threads.
+    let idxs = (0..data.len()).collect::<Vec<_>>();
+    let mut single = vec![vec![0f32 ; weight::N_WEIGHT_ALL]];
+    let mut multi = vec![vec![0f32 ; weight::N_WEIGHT_ALL] ; 3];
+    let loss1 = accumulate(&w, &data, &idxs, &mut single);
+    let loss3 = accumulate(&w, &data, &idxs, &mut multi);
+    assert!((loss1 - loss3).abs() <= loss1 * 1e-5);
+    for (a, b) in single[0].iter().zip(multi[0].iter()) {
+        assert!((a - b).abs() <= 1e-4 * a.abs().max(1.0));
     }
 
-    /**
-     * 棋譜の読み込みと学習を別スレでやる版
-     * boardを纏めてやり取りする版
-     * minibatch 2スレ版
-     */
-    #[allow(dead_code)]
-    #[cfg(target_arch="x86_64")]
-    pub fn learn_stones_para_boardgrp_minibatch2(&mut self, mbsize : usize) {
-        let (tosub, frmain) = std::sync::mpsc::channel::<Vec<u32>>();
-        let (tosub2, frmain2) = std::sync::mpsc::channel::<Vec<u32>>();
-        let (tomain, frsub) = std::sync::mpsc::channel::<()>();
-        let (txprogress, rxprogress) = std::sync::mpsc::channel();
-        let eta = self.eta;
-        let updated = Arc::new((Mutex::new(0), Condvar::new()));
-        let updated2 = Arc::clone(&updated);
-        unsafe {WEIGHTBUFFER = Some(weight::Weight::new());};
-
-        let sub = std::thread::spawn(move || {
-            let weight = unsafe {nodebb::WEIGHT.as_mut().unwrap()};
-            let mut bufweight = weight::Weight::new();
-            let bufweight2 = unsafe {WEIGHTBUFFER.as_mut().unwrap()};
-            let (status, cdvar) = &*updated;
-            let mut banscores = Vec::with_capacity(mbsize / 2);
-
-            tomain.send(()).unwrap();
-
-            loop {
-                match frmain.recv() {
-                    Ok( rfenidxgrp ) => {
-                        // print!("{rfen:?},{score} \r");
-                        if rfenidxgrp.is_empty() {
-                            tomain.send(()).unwrap();
-                            continue;
-                        }
-                        if rfenidxgrp[0] == STOP  {
-                            // println!("score > 64");
-                            break;
-                        }
-                        if rfenidxgrp[0] == PROGRESS {
-                            let mut w = weight::Weight::new();
-                            {let _u = status.lock().unwrap();
-                            w.copy(&weight);}
-                            txprogress.send(w).unwrap();
-                            continue;
-                        }
-                        //
-                        for i in rfenidxgrp {
-                            banscores.push(unsafe {&BOARDCACHE[i as usize]});
-                        }
-                        bufweight.clear();
-                        if weight.train_bitboard_mb(
-                                &banscores, eta, &mut bufweight).is_err() {
-                            println!("error while training");
-                            break;
-                        }
-                        banscores.clear();
-                        {
-                            let mut _upd = status.lock().unwrap();
-                            weight.updatemb2(&bufweight, &bufweight2, mbsize);
-                        }
-                        {
-                            let mut _uupd = cdvar.wait_while(
-                                status.lock().unwrap(), |upd| {
-                                let notready = *upd == 0;
-                                if !notready {
-                                    *upd = 0;
-                                    cdvar.notify_one();
-                                }
-                                notready
-                            }).unwrap();
-                        }
-                    },
-                    Err(e) => {panic!("{}", e.to_string())}
-                }
-            }
-        });
-        let sub2 = std::thread::spawn(move || {
-            let weight = unsafe {nodebb::WEIGHT.as_mut().unwrap()};
-            let bufweight = unsafe {WEIGHTBUFFER.as_mut().unwrap()};
-            // let mut bufweight = weight::Weight::new();
-            let (status, cdvar) = &*updated2;
-            let mut banscores = Vec::with_capacity(mbsize / 2);
-
-            //tomain.send(()).unwrap();
-
-            loop {
-                match frmain2.recv() {
-                    Ok( rfenidxgrp ) => {
-                        // print!("{rfen:?},{score} \r");
-                        if rfenidxgrp[0] == STOP  {
-                            break;
-                        }
-                        //
-                        for i in rfenidxgrp {
-                            banscores.push(unsafe {&BOARDCACHE[i as usize]});
-                        }
-                        // wait updating
-                        {let _uu = cdvar.wait_while(status.lock().unwrap(), |upd| {
-                            *upd != 0
-                        }).unwrap();}
-
-                        bufweight.clear();
-                        if weight.train_bitboard_mb(
-                                &banscores, eta, bufweight).is_err() {
-                            println!("error while training");
-                            break;
-                        }
-                        banscores.clear();
-
-                        {
-                            let mut upd = status.lock().unwrap();
-                            // weight.updatemb(&bufweight, n * 2);
-                            *upd = 1;
-                            cdvar.notify_one();
-                        }
-                    },
-                    Err(e) => {panic!("{}", e.to_string())}
-                }
-            }
-        });
-        let prgstbl = self.progress.clone();
-        let repeat = self.repeat as u32;
-        let subprgs = std::thread::spawn(move || {
-            for prgs in prgstbl {
-                if prgs >= repeat {
-                    println!("WARNING: progress {prgs} >= {repeat}...");
-                    break;
-                }
-                let weight = rxprogress.recv().unwrap();
-                if cfg!(feature="nnv4") {
-                    weight.writev4(&format!("kifu/newevaltable.r{prgs}.txt"));
-                } else {
-                    weight.writev5(&format!("kifu/newevaltable.r{prgs}.txt"));
-                }
-            }
-        });
-
-        // list up kifu
-        let files = std::fs::read_dir(&self.path).unwrap();
-        let mut files = files.filter_map(|entry| {
-            entry.ok().and_then(|e|
-                e.path().file_name().and_then(|n|
-                    n.to_str().map(String::from)
-                )
-            )}).collect::<Vec<String>>().iter().filter(|&fnm| {
-                fnm.find("kifu").is_some()
-                // fnm.find(".txt").is_some()
-            }).cloned().collect::<Vec<String>>();
-        // println!("{:?}", files);
-
-        self.nfiles = files.len();
-        files.sort();
+    let mse = loss1 / data.len() as f32;
+    let mut tr = Trainer::new(0.001, 50, "kifu/");
+    tr.read_opt_out("summary").unwrap();
+    tr.set_minibatch(2);
+    tr.set_threads(2);
+    let after = tr.learn_data(&mut w, &data, &[]).unwrap();
+    assert!(after < mse * 0.5, "{after} >= {mse} * 0.5");
 
-        let showprgs = self.need_progress();
-        let mut rng = rand::thread_rng();
-        // let mut rfencache : Vec<(String, i8)> = Vec::new();
-        for fname in files.iter() {
-            let path = format!("{}{}", self.path, fname);
-            if showprgs {print!("reading {path}\r");}
-
-            let content = std::fs::read_to_string(&path).unwrap();
-            let lines:Vec<&str> = content.split("\n").collect();
+    assert!(tr.read_opt_out("progress,what").is_err());
+}
 
-            let kifu = kifu::Kifu::from(&lines);
-            let score = kifu.score.unwrap();
-            for te in kifu.list.iter() {
-                let rfen = te.rfen.clone();
-                // 最終手はカウントで良いので学習しなくて良い
-                if bitboard::count_emptycells(&rfen).unwrap() < 1 {
-                    continue;
-                }
-                let b = bitboard::BitBoard::from(&rfen).unwrap();
-                let b90 = b.rotate90();
-                let b180 = b.rotate180();
-                let b270 = b90.rotate180();
-                unsafe {
-                    BOARDCACHE.push((b, score));
-                    BOARDCACHE.push((b90, score));
-                    BOARDCACHE.push((b180, score));
-                    BOARDCACHE.push((b270, score));
-                }
-                /*if score.abs() > 32 {
-                    // 大差がついている棋譜は多めに覚える
-                    unsafe {RFENCACHE.push((rfen, score));}
-                }*/
-            }
-            unsafe {
-                BOARDCACHE.sort_by(|a, b| {
-                    a.0.black.cmp(&b.0.black).then(a.0.white.cmp(&b.0.white))});
-                BOARDCACHE.dedup_by(|a, b| {
-                    a.0.black == b.0.black && a.0.white == b.0.white
-                      && a.0.teban == b.0.teban});
-            }
+#[test]
+fn test_schedule() {
+    assert_eq!(Schedule::from("const"), Some(Schedule::Constant));
+    assert_eq!(Schedule::from("step:10:0.5"), Some(Schedule::Step(10, 0.5)));
+    assert_eq!(Schedule::from("EXP:0.9"), Some(Schedule::Exp(0.9)));
+    assert_eq!(Schedule::from("cos"), Some(Schedule::Cosine));
+    assert_eq!(Schedule::from("step:0:0.5"), None);
+    assert_eq!(Schedule::from("step:10"), None);
+    assert_eq!(Schedule::from("exp:1.5"), None);
+    assert_eq!(Schedule::from("linear"), None);
+    assert_eq!(Schedule::Step(10, 0.5).to_string(), "step:10:0.5");
+
+    assert_eq!(Schedule::Constant.eta(0.1, 50, 100), 0.1);
+    assert_eq!(Schedule::Step(10, 0.5).eta(0.1, 9, 100), 0.1);
+    assert_eq!(Schedule::Step(10, 0.5).eta(0.1, 25, 100), 0.025);
+    assert!((Schedule::Exp(0.5).eta(0.1, 3, 100) - 0.0125).abs() < 1e-7);
+    assert_eq!(Schedule::Cosine.eta(0.1, 0, 100), 0.1);
+    assert!((Schedule::Cosine.eta(0.1, 50, 100) - 0.05).abs() < 1e-7);
+}
 
-            self.total += 1;
-            match kifu.winner().unwrap() {
-                kifu::SENTEWIN => {self.win += 1;},
-                kifu::DRAW => {self.draw += 1;},
-                kifu::GOTEWIN => {self.lose += 1;},
-                _ => {}
-            }
-        }
-        match tosub.send(Vec::new()) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
-        }
-        let _ = frsub.recv().unwrap();
-        if showprgs {println!();}
+#[test]
+fn test_metrics() {
+    let mut m = Metrics::default();
+    m.add(0, 60, 3.0, 1.0);
+    m.add(0, 55, -1.0, 1.0);
+    m.add(2, 5, 0.2, 0.0);
+    let mut m2 = Metrics::default();
+    m2.add(2, 9, -4.2, -4.0);
+    m.merge(&m2);
+    let rows = m.rows();
+    assert_eq!(rows.len(), 1 + weight::N_PROGRESS_DIV + N_EMPTIES + 1);
+    assert_eq!(rows[0], (String::from("all"), m.errors(None)));
+    // 空きマスごと
+    let n = |name : &str| rows.iter().find(|(nm, _)| nm == name).unwrap().1.n;
+    assert_eq!((n("e0-9"), n("e50-59"), n("e60"), n("e10-19")), (2, 1, 1, 0));
+    assert_eq!(m.get(Some(0)), (4.0, 2.0, 0.5));
+    assert_eq!(m.get(Some(1)), (0.0, 0.0, 0.0));
+    let (mse, mae, sign) = m.get(Some(2));
+    assert!((mse - 0.04).abs() < 1e-6 && (mae - 0.2).abs() < 1e-6 && sign == 1.0);
+    let (mse, _mae, sign) = m.get(None);
+    assert!((mse - 2.02).abs() < 1e-6 && sign == 0.75);
+    assert_eq!(Metrics::csv_header("val").split(',').count(),
+               m.to_csv().split(',').count());
+
+    let mut w = weight::Weight::new();
+    w.init();
+    let data = ["8/8/8/3Aa3/3aA3/8/8/8 b", "8/8/3A4/3Ba2/3aA3/8/8/8 w"].iter()
+        .map(|rfen| (bitboard::BitBoard::from(rfen).unwrap(), 2.0)).collect::<Vec<_>>();
+    let m1 = Metrics::measure(&w, &data, 1);
+    assert_eq!(m1, Metrics::measure(&w, &data, 2));
+    let expected = data.iter().map(|(ban, t)| {
+        let diff = w.forward(ban, ban.progress()).0 - t;
+        diff * diff
+    }).sum::<f32>() / 2.0;
+    assert!((m1.mse() - expected).abs() <= expected * 1e-5);
+}
 
-        let n = unsafe {BOARDCACHE.len()};
-        println!("{n} rfens.");
-        let mut numbers : Vec<u32> = Vec::with_capacity(n);
-        unsafe { numbers.set_len(n); }
-        // for (i, it) in numbers.iter_mut().enumerate() {
-        //     *it = i;
-        // }
-        for i in 0..n {
-            numbers[i] = i as u32;
-        }
-        let invalidprogress = 99999999;
-        let mut prgs = VecDeque::from(self.progress.clone());
-        let mut nprgs = prgs.pop_front().unwrap_or(invalidprogress) as usize;
-        for i in 0..self.repeat {
-            if showprgs {
-                print!("{i} / {}\r", self.repeat);
-                std::io::stdout().flush().unwrap();
-            }
-            numbers.shuffle(&mut rng);
-            let mut j = mbsize;
-            while j < numbers.len() {
-                let grp1 = numbers[j - mbsize .. j - mbsize / 2].to_vec();
-                let grp2 = numbers[j - mbsize / 2 .. j].to_vec();
-                match tosub.send(grp1) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
-                match tosub2.send(grp2) {
-                    Ok(_) => {},
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
-                j += mbsize;
-            }
-            match tosub.send(Vec::new()) {
-                Ok(_) => {}
-                Err(e) => {
-                    panic!("{}", e.to_string());
-                },
-            }
-            if nprgs == i {
-                match tosub.send(vec![PROGRESS]) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        panic!("{}", e.to_string());
-                    },
-                }
-                nprgs = prgs.pop_front().unwrap_or(invalidprogress) as usize;
-            }
-            frsub.recv().unwrap();
-        }
-        if showprgs {println!();}
-        // println!("_ _ _");
-        match tosub2.send(vec![STOP]) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
-        }
-        match tosub.send(vec![STOP]) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{}", e.to_string());
-            },
-        }
-        subprgs.join().unwrap();
-        sub.join().unwrap();
-        sub2.join().unwrap();
-    }
+#[test]
+fn test_checkpoint() {
+    let mut w = weight::Weight::new();
+    w.init();
+    let rfens = [
+        ("8/8/8/3Aa3/3aA3/8/8/8 b", 0.0),
+        ("8/8/3A4/3Ba2/3aA3/8/8/8 w", 4.0),
+        ("8/8/8/2Ca2/3aA3/8/8/8 w", -6.0),
+    ];
+    let data = rfens.iter().map(|(rfen, score)| {
+        (bitboard::BitBoard::from(rfen).unwrap(), *score)
+    }).collect::<Vec<_>>();
+    let dir = std::env::temp_dir().join(
+        format!("ruversi_test_checkpoint_{}", std::process::id()));
+    let cpdir = dir.to_str().unwrap();
+    let logpath = dir.join("trainlog.csv");
+
+    let adam = optimizer::Optimizer::new(
+        optimizer::Method::Adam(0.9, 0.999), 0.01, [1.0 ; weight::N_LAYER]);
+    let mut tr = Trainer::new(0.001, 3, "kifu/");
+    tr.output = BIT_OUT_NONE;
+    tr.set_optimizer(adam.clone());
+    tr.set_checkpoint(cpdir, true);
+    tr.set_log(logpath.to_str().unwrap());
+    std::fs::create_dir_all(&dir).unwrap();
+    tr.learn_data(&mut w, &data, &data[..1]).unwrap();
+    let state = std::fs::read_to_string(dir.join(CHECKPOINT_STATE)).unwrap();
+    let state = State::from(&state).unwrap();
+    assert_eq!(state.epoch, 3);
+    let log = std::fs::read_to_string(&logpath).unwrap();
+    assert_eq!(log.lines().count(), 1 + 3);
+    assert!(log.starts_with("epoch,eta,mse,valmse,valmae,valsign,valmse0,"));
+
+    // 続きから5エポックまで
+    let mut w2 = weight::Weight::new();
+    w2.init();
+    let mut tr = Trainer::new(0.001, 5, "kifu/");
+    tr.output = BIT_OUT_NONE;
+    tr.set_optimizer(adam.clone());
+    tr.set_checkpoint(cpdir, true);
+    tr.set_log(logpath.to_str().unwrap());
+    tr.learn_data(&mut w2, &data, &data[..1]).unwrap();
+    let state = std::fs::read_to_string(dir.join(CHECKPOINT_STATE)).unwrap();
+    assert_eq!(State::from(&state).unwrap().epoch, 5);
+    // 1局面ずつ5エポック分の更新回数
+    let mut opt = adam.clone();
+    let txt = std::fs::read_to_string(dir.join(optimizer::STATEFILE)).unwrap();
+    opt.read_state(&txt).unwrap();
+    assert_eq!(opt.step(), 3 * 5);
+    let log = std::fs::read_to_string(&logpath).unwrap();
+    assert_eq!(log.lines().count(), 1 + 5);
+    assert!(log.lines().last().unwrap().starts_with("4,"));
+    assert!(State::from("epoch,x").is_none());
+
+    // 学習率0なら改善しないので打ち切る
+    let mut tr = Trainer::new(0.0, 100, "kifu/");
+    tr.output = BIT_OUT_NONE;
+    tr.set_patience(2);
+    tr.set_log(logpath.to_str().unwrap());
+    // 足す順番で誤差が変わらないように1局面で
+    tr.learn_data(&mut w2, &data[..1], &[]).unwrap();
+    let log = std::fs::read_to_string(&logpath).unwrap();
+    assert_eq!(log.lines().count(), 1 + 3);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
 
-    #[allow(dead_code)]
-    #[cfg(target_arch="aarch64")]
-    pub fn learn_stones_para_boardgrp_minibatch2(&mut self, mbsize : usize) {
-        panic!("not implemented for aarch64.");
+#[test]
+fn test_split() {
+    let dir = std::env::temp_dir().join(
+        format!("ruversi_test_split_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut kifus = Vec::new();
+    for i in 0..4 {
+        let mut ban = bitboard::BitBoard::new();
+        let mut kifu = kifu::Kifu::new();
+        for j in 0..10 {
+            let moves = ban.genmove().unwrap();
+            let mv = moves[(i * 5 + j * j) % moves.len()];
+            kifu.append(mv, ban.teban, ban.to_string());
+            ban = ban.r#move(mv).unwrap();
+        }
+        kifu.winneris(i as i8);
+        std::fs::write(dir.join(format!("kifu{i}.txt")),
+            format!("{}{}", kifu::HEADER, kifu.to_str())).unwrap();
+        kifus.push(kifu);
     }
+    let mut tr = Trainer::new(0.001, 1, &format!("{}/", dir.to_str().unwrap()));
+    tr.output = BIT_OUT_NONE;
+    let all = tr.readkifus().unwrap();
+    // 同じ初期局面も棋譜ごとに読む
+    assert_eq!(all.len(), 4 * 10);
+    let (train, val) = tr.readkifus_split(0.25).unwrap();
+    assert_eq!(train.len() + val.len(), all.len());
+    assert!(!val.is_empty());
+    // 4つ目の棋譜が検証用
+    assert!(val.iter().all(|(ban, target)| {
+        *target == 3.0 && kifus[3].list.iter().any(|te| te.rfen == ban.to_string())
+    }));
+    std::fs::remove_dir_all(&dir).unwrap();
 }
@@ -0,0 +1,387 @@
+use super::*;
+
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use player::Player;
+
+/// shortcuts shown at the bottom.
+const KEYS : &str = "arrows/hjkl:cursor space:put u:undo i:hint n:new game f:flip r:resign q:quit";
+/// width of the board on the screen.
+const BOARD_WIDTH : usize = 28;
+/// # of moves in the panel.
+const MOVELIST : usize = 10;
+/// status while the engine is searching.
+const THINKING : &str = "thinking...";
+
+/// keys on the terminal.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// space or enter.
+    Put,
+    Char(char),
+    Other,
+}
+
+fn byte(input : &mut dyn Read) -> io::Result<u8> {
+    let mut buf = [0u8 ; 1];
+    input.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+/// read a key. arrow keys come as "ESC [ A" ~ "ESC [ D".
+fn read_key(input : &mut dyn Read) -> io::Result<Key> {
+    Ok(match byte(input)? {
+        0x1b => {
+            if byte(input)? != b'[' {return Ok(Key::Other);}
+            match byte(input)? {
+                b'A' => Key::Up,
+                b'B' => Key::Down,
+                b'C' => Key::Right,
+                b'D' => Key::Left,
+                _ => Key::Other,
+            }
+        },
+        b'k' => Key::Up,
+        b'j' => Key::Down,
+        b'l' => Key::Right,
+        b'h' => Key::Left,
+        b' ' | b'\n' | b'\r' => Key::Put,
+        c if c.is_ascii_graphic() => Key::Char(c as char),
+        _ => Key::Other,
+    })
+}
+
+/// run stty on the terminal of the standard input.
+fn stty(args : &[&str]) -> Result<String, String> {
+    let out = Command::new("stty").args(args).stdin(Stdio::inherit()).output()
+        .map_err(|e| format!("stty: {e}"))?;
+    if !out.status.success() {
+        return Err(format!("stty: {}", String::from_utf8_lossy(&out.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// the terminal w/o line buffering and echo on the alternate screen.
+/// it is restored when dropped.
+struct Terminal {
+    saved : String,
+}
+
+impl Terminal {
+    fn new() -> Result<Terminal, String> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "min", "1"])?;
+        print!("\x1b[?1049h\x1b[?25l");
+        io::stdout().flush().unwrap();
+        Ok(Terminal { saved })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        io::stdout().flush().unwrap();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stone(teban : i8) -> &'static str {
+    if teban == bitboard::SENTE {bitboard::STONE_SENTE} else {bitboard::STONE_GOTE}
+}
+
+fn pos(xy : u8) -> String {
+    kifu::Te::new(xy, bitboard::SENTE, String::new()).pos()
+}
+
+/// "m:ss"
+fn clock(d : &Duration) -> String {
+    let s = d.as_secs();
+    format!("{}:{:02}", s / 60, s % 60)
+}
+
+/// you on the terminal w/ an engine for hints and evaluation.
+struct Tui<'a> {
+    engine : player::Engine<'a>,
+    cursor : u8,
+    /// your turn.
+    side : i8,
+    /// (move, value, PV) by the engine on the current board.
+    hint : Option<(u8, f32, String)>,
+    /// thinking time of black and white.
+    clock : [Duration ; 2],
+    /// when the last act() returned.
+    since : Option<Instant>,
+    status : String,
+    quit : bool,
+}
+
+impl<'a> Tui<'a> {
+    fn new(engine : player::Engine<'a>) -> Tui<'a> {
+        Tui {
+            engine,
+            cursor : bitboard::cell(4, 4),
+            side : bitboard::SENTE,
+            hint : None,
+            clock : [Duration::ZERO ; 2],
+            since : None,
+            status : String::new(),
+            quit : false,
+        }
+    }
+
+    fn clock_of(&mut self, teban : i8) -> &mut Duration {
+        &mut self.clock[if teban == bitboard::SENTE {0} else {1}]
+    }
+
+    /// lines of the screen. the board on the left and the panel on the right.
+    ///
+    /// # Arguments
+    /// - `marks` : cells marked w/ "..".
+    /// - `cursor` : cell to be highlighted if any.
+    fn screen(&self, ban : &bitboard::BitBoard, kifu : &kifu::Kifu, marks : u64,
+            cursor : Option<u8>) -> Vec<String> {
+        let last = kifu.list.iter().rev().map(|te| te.xy())
+            .find(|xy| *xy != bitboard::PASS);
+        let mut board = ban.render(last, marks).lines()
+            .map(|l| format!("{l:<BOARD_WIDTH$}")).collect::<Vec<_>>();
+        if let Some(xy) = cursor {
+            let (x, y) = bitboard::cell2xy(xy);
+            let l = &board[y as usize];
+            let a = x as usize * 3 - 1;
+            board[y as usize] = format!("{}\x1b[7m{}\x1b[0m{}", &l[..a], &l[a..a + 2], &l[a + 2..]);
+        }
+
+        let mut panel = vec![
+            format!("you:{}  engine:{}", stone(self.side), stone(-self.side)),
+            format!("clock {} {}  {} {}", stone(bitboard::SENTE), clock(&self.clock[0]),
+                stone(bitboard::GOTE), clock(&self.clock[1])),
+            format!("eval  {:+.1} (static, black's view)", self.engine.eval(ban)),
+        ];
+        if let Some(te) = kifu.list.iter().rev().find(|te| te.val.is_some()) {
+            panel.push(format!("last  {} val:{:+.1} pv:{}", te.pos(), te.val.unwrap(),
+                te.pv.as_deref().unwrap_or("")));
+        }
+        if let Some((xy, val, pv)) = &self.hint {
+            panel.push(format!("hint  {} val:{val:+.1} pv:{pv}", pos(*xy)));
+        }
+        panel.push(String::new());
+        panel.push(String::from("moves:"));
+        let skip = kifu.list.len().saturating_sub(MOVELIST);
+        for (i, te) in kifu.list.iter().enumerate().skip(skip) {
+            panel.push(format!("{:>3} {} {}", i + 1, stone(te.teban), te.pos()));
+        }
+
+        let n = board.len().max(panel.len());
+        board.resize(n, " ".repeat(BOARD_WIDTH));
+        panel.resize(n, String::new());
+        let mut lines = board.into_iter().zip(panel).map(|(b, p)| b + &p)
+            .collect::<Vec<_>>();
+        lines.push(String::new());
+        lines.push(self.status.clone());
+        lines.push(KEYS.to_string());
+        lines
+    }
+
+    fn draw(&self, ban : &bitboard::BitBoard, kifu : &kifu::Kifu, marks : u64,
+            cursor : Option<u8>) {
+        print!("\x1b[H\x1b[2J{}", self.screen(ban, kifu, marks, cursor).join("\n"));
+        io::stdout().flush().unwrap();
+    }
+
+    /// wait for a move or a command.
+    fn choose(&mut self, ban : &bitboard::BitBoard, kifu : &kifu::Kifu)
+            -> Result<game::Action, String> {
+        if self.status == THINKING {self.status.clear();}
+        let legal = ban.legal_moves();
+        if legal == 0 {
+            self.status = String::from("no legal moves. passed.");
+            return Ok(game::Action::Move(bitboard::PASS, None));
+        }
+
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        loop {
+            self.draw(ban, kifu, legal, Some(self.cursor));
+            let key = read_key(&mut input).map_err(|e| format!("no input. {e}"))?;
+            self.status.clear();
+            let (x, y) = (self.cursor % 8, self.cursor / 8);
+            match key {
+                Key::Up => self.cursor = x + (y + 7) % 8 * 8,
+                Key::Down => self.cursor = x + (y + 1) % 8 * 8,
+                Key::Left => self.cursor = (x + 7) % 8 + y * 8,
+                Key::Right => self.cursor = (x + 1) % 8 + y * 8,
+                Key::Put => {
+                    if legal & (bitboard::LSB_CELL << self.cursor) == 0 {
+                        self.status = format!("{} is not allowed.", pos(self.cursor));
+                        continue;
+                    }
+                    self.hint = None;
+                    self.status = String::from(THINKING);
+                    let ban = ban.r#move(self.cursor)?;
+                    let mut kifu = kifu.copy();
+                    kifu.append(self.cursor, -ban.teban, String::new());
+                    self.draw(&ban, &kifu, 0, None);
+                    return Ok(game::Action::Move(self.cursor, None));
+                },
+                Key::Char('u') => {
+                    if kifu.list.iter().any(|te| te.teban == ban.teban && te.xy() != bitboard::PASS) {
+                        self.hint = None;
+                        return Ok(game::Action::Undo);
+                    }
+                    self.status = String::from("nothing to undo.");
+                },
+                Key::Char('i') => {
+                    self.status = String::from(THINKING);
+                    self.draw(ban, kifu, legal, Some(self.cursor));
+                    self.status.clear();
+                    if let (xy, Some((val, node, _))) = self.engine.play(ban)? {
+                        self.hint = Some((xy, val, node.best_order()));
+                        self.cursor = xy;
+                    }
+                },
+                Key::Char('n') => {
+                    self.hint = None;
+                    self.clock = [Duration::ZERO ; 2];
+                    return Ok(game::Action::Restart);
+                },
+                Key::Char('f') => {
+                    self.hint = None;
+                    self.status = String::from(THINKING);
+                    self.draw(ban, kifu, 0, None);
+                    return Ok(game::Action::Flip);
+                },
+                Key::Char('r') => return Ok(game::Action::Resign),
+                Key::Char('q') => {
+                    self.quit = true;
+                    return Err(String::from("quit."));
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+impl Player for Tui<'_> {
+    fn name(&self) -> String {
+        String::from("you")
+    }
+
+    fn play(&mut self, ban : &bitboard::BitBoard)
+            -> Result<(u8, Option<game::Search>), String> {
+        match self.act(ban, &kifu::Kifu::new())? {
+            game::Action::Move(xy, search) => Ok((xy, search)),
+            _ => Err(String::from("only moves are allowed here.")),
+        }
+    }
+
+    fn act(&mut self, ban : &bitboard::BitBoard, kifu : &kifu::Kifu)
+            -> Result<game::Action, String> {
+        let st = Instant::now();
+        // 前回からの時間は相手の持ち時間
+        if let Some(since) = self.since.take() {
+            *self.clock_of(-ban.teban) += st - since;
+        }
+        self.side = ban.teban;
+        let res = self.choose(ban, kifu);
+        *self.clock_of(ban.teban) += st.elapsed();
+        self.since = Some(Instant::now());
+        res
+    }
+}
+
+/// the result for the status line.
+fn result(kifu : &kifu::Kifu) -> String {
+    let score = kifu.score.unwrap_or(0);
+    let txt = match score.signum() {
+        1 => format!("{} won by {score}.", stone(bitboard::SENTE)),
+        -1 => format!("{} won by {}.", stone(bitboard::GOTE), -score),
+        _ => String::from("draw."),
+    };
+    match kifu.info("resign") {
+        Some(who) => format!("{who} resigned. {txt}"),
+        None => txt,
+    }
+}
+
+/// play games against `opponent` on the terminal.
+///
+/// # Arguments
+/// - `g` : game to be played. a new game starts from its first position.
+/// - `opponent` : opponent.
+/// - `helper` : engine for hints and evaluation.
+/// - `turn` : your turn.
+///
+/// # Returns
+/// () or error message.
+pub fn run(g : &mut game::GameBB, opponent : &mut dyn player::Player,
+        helper : player::Engine, turn : i8) -> Result<(), String> {
+    let _term = Terminal::new()?;
+    let mut tui = Tui::new(helper);
+    g.set_recordval(true);
+    let stdin = io::stdin();
+    loop {
+        let res = if turn == bitboard::SENTE {
+            g.start_with(&mut [&mut tui, opponent], &mut |_| {})
+        } else {
+            g.start_with(&mut [opponent, &mut tui], &mut |_| {})
+        };
+        if tui.quit {return Ok(());}
+        res?;
+
+        tui.status = format!("{} n:new game q:quit", result(&g.kifu));
+        tui.since = None;
+        tui.draw(g.board(), &g.kifu, 0, None);
+        loop {
+            match read_key(&mut stdin.lock()).map_err(|e| format!("no input. {e}"))? {
+                Key::Char('n') => break,
+                Key::Char('q') => return Ok(()),
+                _ => {},
+            }
+        }
+        g.restart()?;
+        tui.clock = [Duration::ZERO ; 2];
+        tui.status.clear();
+    }
+}
+
+#[test]
+fn test_tui() {
+    let mut input : &[u8] = b"\x1b[A\x1b[Dk h\nu\x1bx";
+    let keys = std::iter::from_fn(|| read_key(&mut input).ok()).collect::<Vec<_>>();
+    assert_eq!(keys, [Key::Up, Key::Left, Key::Up, Key::Put, Key::Left, Key::Put,
+        Key::Char('u'), Key::Other]);
+    assert_eq!(clock(&Duration::from_secs(75)), "1:15");
+
+    let mut w = weight::Weight::new();
+    w.init();
+    let mut tui = Tui::new(player::Engine::new(nodebb::NodeBB::think_ab_simple_gk_tt, 2, &w, 1024));
+    let mut kifu = kifu::Kifu::from_moves("f5d6").unwrap();
+    kifu.list[1].val = Some(1.5);
+    kifu.list[1].pv = Some(String::from("d6c3"));
+    let ban = bitboard::BitBoard::from(&kifu.list[1].rfen).unwrap()
+        .r#move(kifu.list[1].xy()).unwrap();
+    tui.hint = Some((bitboard::cell(3, 3), -2.0, String::from("c3")));
+    let lines = tui.screen(&ban, &kifu, ban.legal_moves(), Some(bitboard::cell(1, 1)));
+    assert!(lines[1].starts_with("1|\x1b[7m__\x1b[0m|__|"), "{}", lines[1]);
+    assert!(lines[0].ends_with("you:@@  engine:[]"));
+    assert!(lines[1].ends_with("clock @@ 0:00  [] 0:00"));
+    assert!(lines.iter().any(|l| l.ends_with(&format!("last  {} val:+1.5 pv:d6c3",
+        kifu.list[1].pos()))));
+    assert!(lines.iter().any(|l| l.ends_with("hint  c3 val:-2.0 pv:c3")));
+    assert!(lines.iter().any(|l| l.ends_with(&format!("  2 [] {}", kifu.list[1].pos()))));
+    assert_eq!(lines.last().unwrap(), KEYS);
+    // 盤の幅は揃える
+    let width = lines[2].find("eval").unwrap();
+    assert_eq!(width, BOARD_WIDTH);
+
+    kifu.winneris(-10);
+    assert_eq!(result(&kifu), "[] won by 10.");
+    kifu.set_info("resign", "black");
+    assert_eq!(result(&kifu), "black resigned. [] won by 10.");
+}
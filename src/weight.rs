@@ -45,6 +45,10 @@ const N_WEIGHT : usize =
 
 const N_WEIGHT_PAD :usize = N_WEIGHT.div_ceil(8) * 8;
 pub const N_PROGRESS_DIV : usize = 3;  // 序盤中盤終盤
+/// size of a buffer for every parameter incl. padding. ex. gradients.
+pub const N_WEIGHT_ALL : usize = N_WEIGHT_PAD * N_PROGRESS_DIV;
+/// input, hidden and output layers.
+pub const N_LAYER : usize = 3;
 
 #[allow(dead_code)]
 const WSZV1 : usize = (bitboard::CELL_2D + 1 + 1) * 4 + 4 + 1;
@@ -213,7 +217,7 @@ impl Weight {
     }
 
     pub fn init(&mut self) {
-        let mut rng = rand::thread_rng();
+        let mut rng = rng::rng();
         let range =
             f64::sqrt(6.0) /
                 f64::sqrt((N_INPUT + N_HIDDEN + N_HIDDEN2 + N_OUTPUT) as f64);
@@ -437,17 +441,22 @@ impl Weight {
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn writev11(&self, path : &str) {
-        let mut f = fs::File::create(path).unwrap();
-        f.write_all(
-            format!("{}\n", EvalFile::V11).as_bytes()).unwrap();
+    /// write eval table w/ V11 format.
+    ///
+    /// # Arguments
+    /// - `path` : file path to be written.
+    pub fn writev11(&self, path : &str) -> Result<(), String> {
+        let mut f = fs::File::create(path).map_err(|e| e.to_string())?;
+        f.write_all(format!("{}\n", EvalFile::V11).as_bytes())
+            .map_err(|e| e.to_string())?;
         for prgs in 0..N_PROGRESS_DIV {
             let offset = prgs * N_WEIGHT_PAD;
             let w = &self.weight[offset..offset + N_WEIGHT];
             let sv = w.iter().map(|a| a.to_string()).collect::<Vec<String>>();
-            f.write_all((sv.join(",") + "¥n").as_bytes()).unwrap();
+            f.write_all((sv.join(",") + "\n").as_bytes())
+                .map_err(|e| e.to_string())?;
         }
+        Ok(())
     }
 
     pub fn copy(&mut self, src : &Weight) {
@@ -516,6 +525,232 @@ impl Weight {
         sum
     }
 
+    /// evaluate w/ a specified progress and keep values in hidden layers.
+    ///
+    /// # Arguments
+    /// - `ban` : board to be evaluated.
+    /// - `prgs` : progress(stage) of weights to be used.
+    ///
+    /// # Returns
+    /// (output, 1st hidden layer before relu, 2nd hidden layer before relu)
+    pub fn forward(&self, ban : &bitboard::BitBoard, prgs : usize)
+            -> (f32, [f32 ; N_HIDDEN], [f32 ; N_HIDDEN2]) {
+        #[cfg(feature = "fixed_stones")]
+        let fs = ban.fixedstones();
+        let ow = self.wbanv(prgs);
+        let wtbn = self.wteban(prgs, ban.teban);
+        #[cfg(feature = "fixed_stones")]
+        let wfs = self.wfixedstones(prgs);
+
+        let mut hid = [0f32 ; N_HIDDEN];
+        hid.copy_from_slice(self.wibias(prgs));
+        for (h, w) in hid.iter_mut().zip(wtbn.iter()) {
+            *h += w;
+        }
+        #[cfg(feature = "fixed_stones")]
+        for (i, h) in hid.iter_mut().enumerate() {
+            *h = wfs[i].mul_add(fs.0 as f32, *h);
+            *h = wfs[i + N_HIDDEN].mul_add(fs.1 as f32, *h);
+        }
+        for idx in 0..bitboard::CELL_2D {
+            let bit = bitboard::LSB_CELL << idx;
+            let start = if (ban.black & bit) != 0 {
+                idx * N_HIDDEN * 2
+            } else if (ban.white & bit) != 0 {
+                idx * N_HIDDEN * 2 + N_HIDDEN
+            } else {
+                continue;  // no stone
+            };
+            for (h, w) in hid.iter_mut().zip(ow.iter().skip(start)) {
+                *h += w;
+            }
+        }
+
+        let mut sum = self.wl2bias(prgs);
+        let wh = self.wlayer1(prgs);
+        let wh2 = self.wlayer2(prgs);
+        let mut hid2 = [0f32 ; N_HIDDEN2];
+        hid2.copy_from_slice(self.wl1bias(prgs));
+        for (i, h2) in hid2.iter_mut().enumerate() {
+            for (j, h1) in hid.iter().enumerate() {
+                *h2 = h1.max(0f32).mul_add(wh[j + i * N_HIDDEN], *h2);
+            }
+            sum += h2.max(0f32) * wh2[i];
+        }
+        (sum, hid, hid2)
+    }
+
+    /// back propagation of a squared error, 0.5 * (output - target)^2.
+    /// gradients are added to `grad`, so clear it before a minibatch.
+    ///
+    /// # Arguments
+    /// - `ban` : board to be learned.
+    /// - `target` : expected value from black's view.
+    /// - `grad` : gradients w/ the same layout as `weight`. N_WEIGHT_ALL.
+    ///
+    /// # Returns
+    /// output before learning.
+    pub fn backward(&self, ban : &bitboard::BitBoard, target : f32,
+            grad : &mut [f32]) -> f32 {
+        let prgs = ban.progress();
+        let (output, hid, hid2) = self.forward(ban, prgs);
+        let offset = prgs * N_WEIGHT_PAD;
+        let grad = &mut grad[offset..offset + N_WEIGHT];
+        let dout = output - target;
+
+        // output layer
+        grad[N_WEIGHT - 1] += dout;
+        let wh2 = self.wlayer2(prgs);
+        let mut dhid2 = [0f32 ; N_HIDDEN2];
+        for (i, h2) in hid2.iter().enumerate() {
+            if *h2 <= 0.0 {continue;}
+
+            grad[N_WEIGHT_LAYER2 + i] += dout * h2;
+            dhid2[i] = dout * wh2[i];
+        }
+
+        // 2nd hidden layer
+        let wh = self.wlayer1(prgs);
+        let mut dhid = [0f32 ; N_HIDDEN];
+        for (i, d2) in dhid2.iter().enumerate() {
+            if *d2 == 0.0 {continue;}
+
+            grad[N_WEIGHT_LAYER1BIAS + i] += d2;
+            let gl1 = &mut grad[N_WEIGHT_LAYER1 + i * N_HIDDEN..];
+            for (j, h1) in hid.iter().enumerate() {
+                if *h1 <= 0.0 {continue;}
+
+                gl1[j] += d2 * h1;
+                dhid[j] += d2 * wh[j + i * N_HIDDEN];
+            }
+        }
+
+        // 1st hidden layer
+        let teban = ban.teban as f32;
+        #[cfg(feature = "fixed_stones")]
+        let fs = ban.fixedstones();
+        for (j, d1) in dhid.iter().enumerate() {
+            if *d1 == 0.0 {continue;}
+
+            grad[N_WEIGHT_INPUTBIAS + j] += d1;
+            grad[N_WEIGHT_TEBAN + j] += d1 * teban;
+            #[cfg(feature = "fixed_stones")] {
+                grad[N_WEIGHT_FIXST_B + j] += d1 * fs.0 as f32;
+                grad[N_WEIGHT_FIXST_W + j] += d1 * fs.1 as f32;
+            }
+            let gin = &mut grad[j * N_INPUT_TEBAN..(j + 1) * N_INPUT_TEBAN];
+            let mut black = ban.black;
+            while black != 0 {
+                let idx = black.trailing_zeros() as usize;
+                gin[N_INPUT_BLACK + idx] += d1;
+                black &= black - 1;
+            }
+            let mut white = ban.white;
+            while white != 0 {
+                let idx = white.trailing_zeros() as usize;
+                gin[N_INPUT_WHITE + idx] += d1;
+                white &= white - 1;
+            }
+        }
+        output
+    }
+
+    /// update weights by gradients. weight -= eta * grad.
+    ///
+    /// # Arguments
+    /// - `grad` : gradients w/ the same layout as `weight`. N_WEIGHT_ALL.
+    /// - `eta` : learning rate.
+    pub fn update(&mut self, grad : &[f32], eta : f32) {
+        for (w, g) in self.weight.iter_mut().zip(grad.iter()) {
+            *w -= eta * g;
+        }
+        self.exchange();
+    }
+
+    /// every parameter incl. padding in the same order as gradients.
+    pub fn params(&self) -> &[f32] {
+        &self.weight
+    }
+
+    /// layer of a parameter.
+    ///
+    /// # Arguments
+    /// - `i` : index in params() or gradients.
+    ///
+    /// # Returns
+    /// - Some(0) : input layer incl. teban, fixed stones and its bias.
+    /// - Some(1) : hidden layer and its bias.
+    /// - Some(2) : output layer and its bias.
+    /// - None : padding.
+    pub fn layer_of(i : usize) -> Option<usize> {
+        match i % N_WEIGHT_PAD {
+            i if i < N_WEIGHT_LAYER1 => Some(0),
+            i if i < N_WEIGHT_LAYER2 => Some(1),
+            i if i < N_WEIGHT => Some(2),
+            _ => None,
+        }
+    }
+
+    /// explain an evaluation value by contributions of every stone.
+    ///
+    /// # Arguments
+    /// - `ban` : board to be explained.
+    /// - `method` : how to attribute the value to cells.
+    ///
+    /// # Returns
+    /// contributions of cells. empty cells are always zero.
+    pub fn explain(&self, ban : &bitboard::BitBoard, method : Attribution)
+            -> Explanation {
+        let prgs = ban.progress();
+        let (value, hid, hid2) = self.forward(ban, prgs);
+        let mut cells = [0f32 ; bitboard::CELL_2D];
+        match method {
+            Attribution::Occlusion => {
+                // same weights are used even if # of stones crosses a stage.
+                for (idx, c) in cells.iter_mut().enumerate() {
+                    let bit = bitboard::LSB_CELL << idx;
+                    if (bit & (ban.black | ban.white)) == 0 {continue;}
+
+                    let mut removed = ban.clone();
+                    removed.black &= !bit;
+                    removed.white &= !bit;
+                    *c = value - self.forward(&removed, prgs).0;
+                }
+            },
+            Attribution::GradientInput => {
+                // back propagation to the 1st hidden layer.
+                let wh = self.wlayer1(prgs);
+                let wh2 = self.wlayer2(prgs);
+                let mut dhid = [0f32 ; N_HIDDEN];
+                for (i, h2) in hid2.iter().enumerate() {
+                    if *h2 <= 0.0 {continue;}
+
+                    for (j, d) in dhid.iter_mut().enumerate() {
+                        *d += wh2[i] * wh[j + i * N_HIDDEN];
+                    }
+                }
+                for (d, h) in dhid.iter_mut().zip(hid.iter()) {
+                    if *h <= 0.0 {*d = 0.0;}
+                }
+                // input is 1 for a stone, so gradient x input is gradient.
+                let ow = self.wbanv(prgs);
+                for (idx, c) in cells.iter_mut().enumerate() {
+                    let bit = bitboard::LSB_CELL << idx;
+                    let start = if (ban.black & bit) != 0 {
+                        idx * N_HIDDEN * 2
+                    } else if (ban.white & bit) != 0 {
+                        idx * N_HIDDEN * 2 + N_HIDDEN
+                    } else {
+                        continue;
+                    };
+                    *c = dhid.iter().zip(ow.iter().skip(start))
+                        .map(|(d, w)| d * w).sum();
+                }
+            },
+        }
+        Explanation {rfen : ban.to_string(), method, value, cells}
+    }
+
     #[cfg(target_arch="x86_64")]
     pub fn evaluatev9bb_simd(&self, ban : &bitboard::BitBoard) -> f32 {
         let prgs = ban.progress();
@@ -1133,6 +1368,79 @@ impl Weight {
     }
 }
 
+/// methods to attribute an evaluation value to cells.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Attribution {
+    /// difference from the value without the stone.
+    Occlusion,
+    /// gradient of the output w.r.t. the input times the input.
+    GradientInput,
+}
+
+impl std::fmt::Display for Attribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}",
+            match self {
+                Attribution::Occlusion => "occlusion",
+                Attribution::GradientInput => "gradient",
+            })
+    }
+}
+
+impl Attribution {
+    pub fn from(txt : &str) -> Option<Attribution> {
+        match txt {
+            "occlusion" => Some(Attribution::Occlusion),
+            "gradient" => Some(Attribution::GradientInput),
+            _ => None,
+        }
+    }
+}
+
+/// per-cell contributions to an evaluation value.
+pub struct Explanation {
+    pub rfen : String,
+    pub method : Attribution,
+    /// evaluation value. positive is good for SENTE.
+    pub value : f32,
+    /// contributions. index is same as a bit in BitBoard.
+    pub cells : [f32 ; bitboard::CELL_2D],
+}
+
+impl Explanation {
+    /// # Returns
+    /// 8x8 heat grid. "." for empty cells.
+    pub fn to_grid(&self, ban : &bitboard::BitBoard) -> String {
+        let mut ret = format!("val:{:+.3} ({})\n", self.value, self.method);
+        ret += "  |   a   |   b   |   c   |   d   |   e   |   f   |   g   |   h   |\n";
+        for y in 0..bitboard::NUMCELL {
+            ret += &format!("{} |", y + 1);
+            for x in 0..bitboard::NUMCELL {
+                let idx = x + y * bitboard::NUMCELL;
+                ret += &if ban.is_filled(idx as u8) {
+                    format!("{:+7.2}|", self.cells[idx])
+                } else {
+                    "   .   |".to_string()
+                };
+            }
+            ret += "\n";
+        }
+        ret
+    }
+
+    /// # Returns
+    /// JSON text. "cells" is an array of rows from 1 to 8.
+    pub fn to_json(&self) -> String {
+        let rows = self.cells.chunks(bitboard::NUMCELL).map(|row| {
+            format!("[{}]",
+                row.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(","))
+            }).collect::<Vec<_>>();
+        format!(
+            "{{\"rfen\":\"{}\",\"method\":\"{}\",\"value\":{},\"cells\":[{}]}}",
+            self.rfen, self.method, self.value, rows.join(","))
+    }
+}
+
 #[allow(dead_code)]
 fn dbg_assert_eq_vec(va : &[f32], vb : &[f32]) -> bool {
     for (a, b) in va.iter().zip(vb.iter()) {
@@ -1278,3 +1586,124 @@ fn testweight() {
         // println!("{res_nosimd} == {res_simd} == {res_simdavx} ???");
     }
 }
+
+#[test]
+fn test_explain() {
+    let mut w = weight::Weight::new();
+    w.init();
+    let rfens = [
+        "8/8/8/3Aa3/3aA3/8/8/8 b",
+        "2A1A1A1/3c2/Ac1bA/3c2/2cAa1/1a1Aa2A/A3a3/4A3 w",
+        "aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w",
+    ];
+    for rfen in rfens.iter() {
+        let ban = bitboard::BitBoard::from(rfen).unwrap();
+        let prgs = ban.progress();
+        let (val, _, _) = w.forward(&ban, prgs);
+        assert!(dbg_assert_eqi(&val, &w.evaluatev9bb(&ban)));
+
+        let occ = w.explain(&ban, Attribution::Occlusion);
+        let grad = w.explain(&ban, Attribution::GradientInput);
+        assert_eq!(occ.value, val);
+        assert_eq!(grad.value, val);
+        assert_eq!(occ.rfen, *rfen);
+        for idx in 0..bitboard::CELL_2D {
+            if !ban.is_filled(idx as u8) {
+                assert_eq!(occ.cells[idx], 0.0);
+                assert_eq!(grad.cells[idx], 0.0);
+                continue;
+            }
+
+            let mut removed = ban.clone();
+            removed.black &= !(bitboard::LSB_CELL << idx);
+            removed.white &= !(bitboard::LSB_CELL << idx);
+            let diff = val - w.forward(&removed, prgs).0;
+            assert!(dbg_assert_eq(&occ.cells[idx], &diff));
+        }
+
+        let grid = occ.to_grid(&ban);
+        assert_eq!(grid.lines().count(), 2 + bitboard::NUMCELL);
+        let json = occ.to_json();
+        assert!(json.starts_with(&format!("{{\"rfen\":\"{rfen}\",\"method\":\"occlusion\",")));
+        assert_eq!(json.matches('[').count(), 1 + bitboard::NUMCELL);
+    }
+}
+
+#[test]
+fn test_backward() {
+    let mut w = weight::Weight::new();
+    w.init();
+    let rfens = [
+        "8/8/8/3Aa3/3aA3/8/8/8 b",
+        "2A1A1A1/3c2/Ac1bA/3c2/2cAa1/1a1Aa2A/A3a3/4A3 w",
+        "aAaAaAaA/AaAaAaAa/aCaC/AcAc/bBb2/BbBb/dD/Dd w",
+    ];
+    let target = 10.0f32;
+    for rfen in rfens.iter() {
+        let ban = bitboard::BitBoard::from(rfen).unwrap();
+        let prgs = ban.progress();
+        let mut grad = vec![0f32 ; N_WEIGHT_ALL];
+        let val = w.backward(&ban, target, &mut grad);
+        assert_eq!(val, w.forward(&ban, prgs).0);
+        // other stages are not used.
+        for (i, g) in grad.iter().enumerate() {
+            if i / N_WEIGHT_PAD != prgs {assert_eq!(*g, 0.0);}
+        }
+
+        // compare w/ numerical differentiation for large gradients.
+        let mut idxs = (0..N_WEIGHT_ALL).collect::<Vec<_>>();
+        idxs.sort_by(|a, b| grad[*b].abs().total_cmp(&grad[*a].abs()));
+        let loss = |w : &Weight| {
+            let d = w.forward(&ban, prgs).0 - target;
+            0.5 * d * d
+        };
+        let eps = 1e-3f32;
+        let l0 = loss(&w);
+        let mut checked = 0;
+        for &i in idxs.iter().take(30) {
+            let mut wp = weight::Weight::new();
+            wp.copy(&w);
+            wp.weight[i] += eps;
+            wp.exchange();
+            let mut wm = weight::Weight::new();
+            wm.copy(&w);
+            wm.weight[i] -= eps;
+            wm.exchange();
+            let (lp, lm) = (loss(&wp), loss(&wm));
+            // 活性化関数の折れ目をまたぐところは数値微分できない
+            let (fwd, bwd) = ((lp - l0) / eps, (l0 - lm) / eps);
+            if (fwd - bwd).abs() > 0.05 * grad[i].abs() + 0.01 {continue;}
+
+            let num = (lp - lm) / (2.0 * eps);
+            assert!((num - grad[i]).abs() <= 0.05 * grad[i].abs() + 0.01,
+                "{rfen} @{i} {num} != {}", grad[i]);
+            checked += 1;
+        }
+        assert!(checked >= 20, "{rfen} {checked}");
+
+        // a step to the opposite direction reduces the error.
+        let mut wu = weight::Weight::new();
+        wu.copy(&w);
+        wu.update(&grad, 1e-4);
+        assert!((wu.evaluatev9bb(&ban) - target).abs() < (val - target).abs());
+    }
+}
+
+#[test]
+fn test_writev11() {
+    let mut w = weight::Weight::new();
+    w.init();
+    let path = std::env::temp_dir().join(
+        format!("ruversi_test_writev11_{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    w.writev11(path).unwrap();
+    let mut r = weight::Weight::new();
+    r.read(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    for p in 0..N_PROGRESS_DIV {
+        assert_eq!(w.wban(p)[..N_WEIGHT], r.wban(p)[..N_WEIGHT]);
+    }
+    let ban = bitboard::BitBoard::from(
+        "2A1A1A1/3c2/Ac1bA/3c2/2cAa1/1a1Aa2A/A3a3/4A3 w").unwrap();
+    assert_eq!(w.evaluatev9bb(&ban), r.evaluatev9bb(&ban));
+}
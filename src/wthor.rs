@@ -0,0 +1,400 @@
+use super::*;
+
+const HEADER_SIZE : usize = 16;
+const GAME_SIZE : usize = 68;
+const N_MOVES : usize = 60;
+/// record size in .jou.
+const PLAYER_SIZE : usize = 20;
+/// record size in .trn.
+const TOURNAMENT_SIZE : usize = 26;
+
+/// a game in a .wtb file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Game {
+    pub year : u16,
+    pub tournament : u16,
+    pub black : u16,
+    pub white : u16,
+    /// # of black discs at the end. empty cells go to the winner.
+    pub discs : u8,
+    /// # of black discs w/ perfect play.
+    pub theoretical : u8,
+    /// 10 * row + column. 1~8 for both.
+    pub moves : Vec<u8>,
+}
+
+impl Game {
+    fn from(year : u16, rec : &[u8]) -> Game {
+        let u16le = |i : usize| u16::from_le_bytes([rec[i], rec[i + 1]]);
+        Game {
+            year,
+            tournament : u16le(0),
+            black : u16le(2),
+            white : u16le(4),
+            discs : rec[6],
+            theoretical : rec[7],
+            moves : rec[8..8 + N_MOVES].iter().copied()
+                .take_while(|&mv| mv != 0).collect(),
+        }
+    }
+
+    #[cfg(test)]
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(GAME_SIZE);
+        buf.extend_from_slice(&self.tournament.to_le_bytes());
+        buf.extend_from_slice(&self.black.to_le_bytes());
+        buf.extend_from_slice(&self.white.to_le_bytes());
+        buf.push(self.discs);
+        buf.push(self.theoretical);
+        buf.extend_from_slice(&self.moves);
+        buf.resize(GAME_SIZE, 0);
+        buf
+    }
+
+    /// final score from black's view in WTHOR.
+    pub fn score(&self) -> i8 {
+        (self.discs as i16 * 2 - bitboard::CELL_2D as i16) as i8
+    }
+
+    /// replay moves.
+    /// WTHOR starts from the standard position which is
+    /// BitBoard::new() upside down, so rows are flipped.
+    ///
+    /// # Returns
+    /// kifu or error message for an illegal move or a wrong score.
+    /// the score is the difference of stones if the game is over,
+    /// score() otherwise.
+    pub fn to_kifu(&self) -> Result<kifu::Kifu, String> {
+        let mut kifu = kifu::Kifu::new();
+        let mut ban = bitboard::BitBoard::new();
+        for (i, &mv) in self.moves.iter().enumerate() {
+            let (row, col) = (mv / 10, mv % 10);
+            if !(1..=8).contains(&row) || !(1..=8).contains(&col) {
+                return Err(format!("invalid move {mv} at {}.", i + 1));
+            }
+
+            let xy = (col - 1) + (8 - row) * bitboard::NUMCELL as u8;
+            kifu.play(&mut ban, xy).map_err(|e| format!("move {} {mv}: {e}", i + 1))?;
+        }
+
+        if !kifu.finish(&mut ban) {
+            // 投了などで途中で終わった
+            kifu.winneris(self.score());
+            return Ok(kifu);
+        }
+
+        let score = ban.count();
+        let nblank = ban.nblank() as i8;
+        let expected = score + nblank * score.signum();
+        if expected != self.score() {
+            return Err(format!("score {} != {expected}.", self.score()));
+        }
+        kifu.winneris(score);
+        Ok(kifu)
+    }
+}
+
+/// read games in a .wtb file.
+pub fn read_games(path : &str) -> Result<Vec<Game>, String> {
+    let buf = std::fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+    if buf.len() < HEADER_SIZE {
+        return Err(format!("{path}: too short."));
+    }
+
+    let ngames = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]) as usize;
+    let year = u16::from_le_bytes([buf[10], buf[11]]);
+    let boardsize = buf[12];
+    if boardsize != 0 && boardsize != 8 {
+        return Err(format!("{path}: board size {boardsize} is not supported."));
+    }
+    if buf.len() != HEADER_SIZE + ngames * GAME_SIZE {
+        return Err(format!("{path}: {} bytes for {ngames} games.", buf.len()));
+    }
+
+    Ok(buf[HEADER_SIZE..].chunks(GAME_SIZE).map(|rec| Game::from(year, rec)).collect())
+}
+
+/// read names in a .jou or .trn file.
+///
+/// # Arguments
+/// - `path` : .jou for players or .trn for tournaments.
+///
+/// # Returns
+/// names in the order of their numbers or error message.
+pub fn read_names(path : &str) -> Result<Vec<String>, String> {
+    let recsize = if path.to_ascii_lowercase().ends_with(".trn") {
+        TOURNAMENT_SIZE
+    } else {
+        PLAYER_SIZE
+    };
+    let buf = std::fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+    if buf.len() < HEADER_SIZE {
+        return Err(format!("{path}: too short."));
+    }
+
+    let n = u16::from_le_bytes([buf[8], buf[9]]) as usize;
+    if buf.len() < HEADER_SIZE + n * recsize {
+        return Err(format!("{path}: {} bytes for {n} names.", buf.len()));
+    }
+    Ok(buf[HEADER_SIZE..HEADER_SIZE + n * recsize].chunks(recsize).map(|rec| {
+        // ISO-8859-1
+        rec.iter().take_while(|&&c| c != 0).map(|&c| c as char)
+            .collect::<String>().trim().to_string()
+    }).collect())
+}
+
+/// conditions to import games.
+#[derive(Debug, Default, PartialEq)]
+pub struct Filter {
+    /// from, to.
+    years : Option<(u16, u16)>,
+    /// a part of the name of black or white. lower case.
+    player : Option<String>,
+    /// min, max from black's view.
+    score : Option<(i8, i8)>,
+}
+
+impl Filter {
+    /// COMMA separated conditions.
+    /// - year:<year>[:<to>]
+    /// - player:<name> : a part of the name. case insensitive.
+    /// - score:<min>:<max> : final score from black's view.
+    pub fn from(txt : &str) -> Option<Filter> {
+        let mut filter = Filter::default();
+        if txt.is_empty() {return Some(filter);}
+
+        for cond in txt.split(',') {
+            let elem = cond.split(':').collect::<Vec<_>>();
+            match elem.as_slice() {
+                ["year", y] => {
+                    let y = y.parse().ok()?;
+                    filter.years = Some((y, y));
+                },
+                ["year", from, to] => {
+                    filter.years = Some((from.parse().ok()?, to.parse().ok()?));
+                },
+                ["player", name] if !name.is_empty() => {
+                    filter.player = Some(name.to_lowercase());
+                },
+                ["score", min, max] => {
+                    filter.score = Some((min.parse().ok()?, max.parse().ok()?));
+                },
+                _ => {return None;},
+            }
+        }
+        Some(filter)
+    }
+
+    pub fn need_players(&self) -> bool {
+        self.player.is_some()
+    }
+
+    fn accept(&self, game : &Game, players : &[String]) -> bool {
+        if self.years.is_some_and(|(from, to)| game.year < from || game.year > to) {
+            return false;
+        }
+        if self.score.is_some_and(|(min, max)| game.score() < min || game.score() > max) {
+            return false;
+        }
+        if let Some(name) = self.player.as_ref() {
+            let matches = |n : u16| {
+                players.get(n as usize).is_some_and(|p| p.to_lowercase().contains(name))
+            };
+            return matches(game.black) || matches(game.white);
+        }
+        true
+    }
+}
+
+/// find a file w/ `ext` in the same directory as `path`.
+fn find_sibling(path : &std::path::Path, ext : &str) -> Option<String> {
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."));
+    let mut files = std::fs::read_dir(dir).ok()?.filter_map(|e| {
+        let p = e.ok()?.path();
+        p.extension()?.to_str()?.eq_ignore_ascii_case(ext)
+            .then(|| p.to_str().map(String::from))?
+    }).collect::<Vec<_>>();
+    files.sort();
+    files.into_iter().next()
+}
+
+/// import games in WTHOR files as kifu files.
+///
+/// # Arguments
+/// - `path` : a .wtb file or a directory including them.
+///   names are read from .jou and .trn in the same directory if any.
+/// - `outdir` : directory for kifu files.
+/// - `filter` : games to be imported.
+pub fn run(path : &str, outdir : &str, filter : &Filter) -> Result<(), String> {
+    let p = std::path::Path::new(path);
+    let mut wtbs = if p.is_dir() {
+        std::fs::read_dir(p).map_err(|e| format!("{path}: {e}"))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|f| f.extension().is_some_and(|e| e.eq_ignore_ascii_case("wtb")))
+            .collect::<Vec<_>>()
+    } else {
+        vec![p.to_path_buf()]
+    };
+    wtbs.sort();
+    if wtbs.is_empty() {
+        return Err(format!("no .wtb files in {path}."));
+    }
+    let players = match find_sibling(&wtbs[0], "jou") {
+        Some(jou) => read_names(&jou)?,
+        None if filter.need_players() => {
+            return Err(format!("no .jou file for the player filter in {path}."));
+        },
+        None => Vec::new(),
+    };
+    let tournaments = match find_sibling(&wtbs[0], "trn") {
+        Some(trn) => read_names(&trn)?,
+        None => Vec::new(),
+    };
+    std::fs::create_dir_all(outdir).map_err(|e| format!("{outdir}: {e}"))?;
+
+    let name = |names : &[String], n : u16| {
+        names.get(n as usize).cloned().unwrap_or(format!("#{n}"))
+    };
+    let (mut imported, mut filtered, mut invalid) = (0, 0, 0);
+    for wtb in wtbs.iter() {
+        let wtbpath = wtb.to_str().unwrap();
+        let stem = wtb.file_stem().and_then(|s| s.to_str()).unwrap_or("wthor");
+        for (i, game) in read_games(wtbpath)?.iter().enumerate() {
+            if !filter.accept(game, &players) {
+                filtered += 1;
+                continue;
+            }
+
+            let mut kifu = match game.to_kifu() {
+                Ok(kifu) => kifu,
+                Err(msg) => {
+                    eprintln!("{wtbpath}: game {i}: {msg}");
+                    invalid += 1;
+                    continue;
+                },
+            };
+            kifu.set_info("black", &name(&players, game.black));
+            kifu.set_info("white", &name(&players, game.white));
+            kifu.set_info("place", &name(&tournaments, game.tournament));
+            kifu.set_info("date", &game.year.to_string());
+            let kifupath = std::path::Path::new(outdir)
+                .join(format!("kifu{stem}_{i:05}.txt"));
+            kifu.save(kifupath.to_str().unwrap())?;
+            imported += 1;
+        }
+    }
+    println!("{imported} games imported, {filtered} filtered out, {invalid} invalid \
+        in {} files.", wtbs.len());
+    Ok(())
+}
+
+#[cfg(test)]
+fn wthor_header(n1 : u32, n2 : u16, year : u16) -> Vec<u8> {
+    let mut buf = vec![20, 24, 1, 1];
+    buf.extend_from_slice(&n1.to_le_bytes());
+    buf.extend_from_slice(&n2.to_le_bytes());
+    buf.extend_from_slice(&year.to_le_bytes());
+    buf.extend_from_slice(&[8, 0, 0, 0]);
+    buf
+}
+
+#[test]
+fn test_wthor() {
+    // 決まった手順で最後まで打ってWTHORの形式にする
+    let mut games = Vec::new();
+    let mut bans = Vec::new();
+    for seed in 0..3 {
+        let mut ban = bitboard::BitBoard::new();
+        let mut moves = Vec::new();
+        let mut i = seed;
+        while !ban.is_passpass() && !ban.is_full() {
+            let mvs = ban.genmove().unwrap();
+            let mv = mvs[i % mvs.len()];
+            if mv != bitboard::PASS {
+                let (x, y) = (mv % 8, mv / 8);
+                moves.push((8 - y) * 10 + x + 1);
+            }
+            ban = ban.r#move(mv).unwrap();
+            i = (i * 7 + 3) % 101;
+        }
+        let score = ban.count();
+        let discs = (score as i16 + ban.nblank() as i16 * score.signum() as i16 + 64) / 2;
+        games.push(Game {year : 2024, tournament : 1, black : seed as u16, white : 2,
+            discs : discs as u8, theoretical : 0, moves});
+        bans.push(ban);
+    }
+    // 途中で終わった棋譜
+    let mut short = games[0].clone();
+    short.moves.truncate(20);
+    short.discs = 40;
+    games.push(short);
+    // 打てない手
+    let mut illegal = games[1].clone();
+    illegal.moves[0] = 11;
+    games.push(illegal);
+
+    for (game, ban) in games.iter().zip(bans.iter()) {
+        let kifu = game.to_kifu().unwrap();
+        assert_eq!(kifu.score, Some(ban.count()));
+        // 最後はパスで終局の盤面
+        let last = kifu.list.last().unwrap();
+        assert_eq!(last.pos(), "PS");
+        assert_eq!(bitboard::BitBoard::from(&last.rfen).unwrap().count(), ban.count());
+    }
+    assert_eq!(games[3].to_kifu().unwrap().score, Some(16));
+    assert_eq!(games[3].to_kifu().unwrap().list.len(), 20);
+    assert_eq!(games[4].to_kifu().err().unwrap(),
+        "move 1 11: illegal move a8 on 8/8/8/3Aa3/3aA3/8/8/8 b.");
+    // 標準の初期配置での f5 f6 d3
+    let opening = Game {year : 2024, tournament : 0, black : 0, white : 0,
+        discs : 32, theoretical : 32, moves : vec![56, 66, 34]};
+    assert_eq!(opening.to_kifu().unwrap().list.len(), 3);
+    let mut wrong = games[0].clone();
+    wrong.discs ^= 2;
+    assert!(wrong.to_kifu().err().unwrap().starts_with("score "));
+
+    // ファイルにして読み込む
+    let dir = std::env::temp_dir().join(format!("ruversi_test_wthor_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut wtb = wthor_header(games.len() as u32, 0, 2024);
+    for g in games.iter() {
+        wtb.extend(g.to_bytes());
+    }
+    std::fs::write(dir.join("WTH_2024.wtb"), &wtb).unwrap();
+    let mut jou = wthor_header(0, 3, 0);
+    for name in ["Alice", "Bob", "Carol"] {
+        let mut rec = name.as_bytes().to_vec();
+        rec.resize(PLAYER_SIZE, 0);
+        jou.extend(rec);
+    }
+    std::fs::write(dir.join("WTHOR.JOU"), &jou).unwrap();
+    let wtbpath = dir.join("WTH_2024.wtb");
+    let read = read_games(wtbpath.to_str().unwrap()).unwrap();
+    assert!(read == games);
+    assert_eq!(read_names(dir.join("WTHOR.JOU").to_str().unwrap()).unwrap(),
+        vec!["Alice", "Bob", "Carol"]);
+
+    let outdir = dir.join("kifu");
+    let outdir = outdir.to_str().unwrap();
+    let filter = Filter::from("player:bob,year:2020:2024").unwrap();
+    run(dir.to_str().unwrap(), outdir, &filter).unwrap();
+    let files = dataset::listkifu(outdir).unwrap();
+    assert_eq!(files, vec!["kifuWTH_2024_00001.txt"]);
+    let content = std::fs::read_to_string(
+        std::path::Path::new(outdir).join(&files[0])).unwrap();
+    assert!(content.contains("# black: Bob\n# white: Carol\n# place: #1\n# date: 2024\n"));
+    let kifu = dataset::readkifu(std::path::Path::new(outdir).join(&files[0])
+        .to_str().unwrap()).unwrap();
+    assert_eq!(kifu.score, Some(bans[1].count()));
+    assert_eq!(kifu.info("white"), Some("Carol"));
+    run(wtbpath.to_str().unwrap(), outdir, &Filter::from("").unwrap()).unwrap();
+    // 打てない手の棋譜は除く
+    assert_eq!(dataset::listkifu(outdir).unwrap().len(), 4);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(Filter::from("score:-10:10").unwrap().score, Some((-10, 10)));
+    assert_eq!(Filter::from("year:2001").unwrap().years, Some((2001, 2001)));
+    assert!(Filter::from("player:").is_none());
+    assert!(Filter::from("month:1").is_none());
+}